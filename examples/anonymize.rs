@@ -0,0 +1,29 @@
+//! Anonymize a recorded result page for use as a test fixture.
+//!
+//! ```bash
+//! cargo run --example anonymize --features anonymize -- page.html > tests/fixtures/new.json
+//! ```
+//!
+//! Reads an HTML page or its `__NEXT_DATA__` JSON from the given file (or
+//! stdin) and prints the anonymized JSON.
+
+use std::io::Read;
+
+use anyhow::Result;
+use yxhoo_transit::{anonymize::anonymize_next_data, transit_dto::load_next_data};
+
+fn main() -> Result<()> {
+    let input = match std::env::args().nth(1) {
+        Some(path) => std::fs::read_to_string(path)?,
+        None => {
+            let mut input = String::new();
+            std::io::stdin().read_to_string(&mut input)?;
+            input
+        }
+    };
+
+    let anonymized = anonymize_next_data(&load_next_data(&input)?);
+    eprintln!("replaced {} names", anonymized.replacements.len());
+    println!("{}", serde_json::to_string_pretty(&anonymized.next_data)?);
+    Ok(())
+}
@@ -0,0 +1,268 @@
+//! Anonymize recorded result pages before contributing them as fixtures.
+//!
+//! Recorded `__NEXT_DATA__` documents embed the searched places. This
+//! replaces them with placeholders while keeping the structure, times and
+//! fares, so the parser sees the same page apart from the names.
+
+use serde_json::Value;
+
+use crate::yxhoo::encode_query;
+
+/// Keys whose values are treated as coordinates.
+const COORDINATE_KEYS: [&str; 5] = ["lat", "lon", "lng", "latitude", "longitude"];
+
+/// An anonymized document and what was replaced.
+#[derive(Debug, Clone)]
+pub struct Anonymized {
+    /// The rewritten `__NEXT_DATA__` JSON.
+    pub next_data: Value,
+    /// Each replaced name with its placeholder, in the order found.
+    ///
+    /// Contains the original names; do not publish it with the fixture.
+    pub replacements: Vec<(String, String)>,
+}
+
+/// Replace the searched places of a `__NEXT_DATA__` document.
+///
+/// The names are the query's `from` / `to` / `via`, the resolved
+/// `fromName` / `toName`, and the first and last station of every route.
+/// Each becomes "地点A", "地点B", ... in the order found, everywhere it
+/// appears in a string (also percent-encoded), so the same name always gets
+/// the same placeholder. Values under coordinate keys (`lat`, `lon`, ...)
+/// are replaced by placeholder coordinates near 35°N 139°E, again one per
+/// distinct original. The output is the same for the same input.
+pub fn anonymize_next_data(root: &Value) -> Anonymized {
+    let mut names = Vec::new();
+    let mut add = |value: &Value| {
+        if let Some(name) = value.as_str().map(str::trim).filter(|s| !s.is_empty())
+            && !names.iter().any(|n| n == name)
+        {
+            names.push(name.to_string());
+        }
+    };
+
+    let page_props = &root["props"]["pageProps"];
+    let query = &page_props["pageQuery"];
+    add(&query["from"]);
+    add(&query["to"]);
+    match &query["via"] {
+        Value::Array(via) => via.iter().for_each(&mut add),
+        via => add(via),
+    }
+    let display = &page_props["naviSearchParam"]["displayInfo"];
+    add(&display["fromName"]);
+    add(&display["toName"]);
+    let features = page_props["naviSearchParam"]["featureInfoList"].as_array();
+    for edges in features
+        .into_iter()
+        .flatten()
+        .filter_map(|f| f["edgeInfoList"].as_array())
+    {
+        if let (Some(first), Some(last)) = (edges.first(), edges.last()) {
+            add(&first["stationName"]);
+            add(&last["stationName"]);
+        }
+    }
+
+    let replacements: Vec<(String, String)> = names
+        .into_iter()
+        .enumerate()
+        .map(|(i, name)| (name, placeholder(i)))
+        .collect();
+
+    // Longest first, so a name containing another is replaced whole.
+    let mut terms: Vec<(String, String)> = replacements
+        .iter()
+        .flat_map(|(name, placeholder)| {
+            [
+                (name.clone(), placeholder.clone()),
+                (encoded(name), encoded(placeholder)),
+            ]
+        })
+        .collect();
+    terms.sort_by_key(|(term, _)| std::cmp::Reverse(term.len()));
+    terms.dedup_by(|a, b| a.0 == b.0);
+
+    let mut next_data = root.clone();
+    let mut coordinates = Vec::new();
+    rewrite(&mut next_data, &terms, &mut coordinates);
+    Anonymized {
+        next_data,
+        replacements,
+    }
+}
+
+/// "地点A" ... "地点Z", then "地点AA", "地点AB", ...
+fn placeholder(index: usize) -> String {
+    let mut letters = Vec::new();
+    let mut n = index + 1;
+    while n > 0 {
+        n -= 1;
+        letters.push(char::from(b'A' + (n % 26) as u8));
+        n /= 26;
+    }
+    letters.iter().rev().fold(String::from("地点"), |mut s, c| {
+        s.push(*c);
+        s
+    })
+}
+
+fn encoded(name: &str) -> String {
+    encode_query(&[(String::new(), name.to_string())])
+        .trim_start_matches('=')
+        .to_string()
+}
+
+fn rewrite(value: &mut Value, terms: &[(String, String)], coordinates: &mut Vec<String>) {
+    match value {
+        Value::String(s) => {
+            for (term, placeholder) in terms {
+                if s.contains(term.as_str()) {
+                    *s = s.replace(term.as_str(), placeholder);
+                }
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                rewrite(item, terms, coordinates);
+            }
+        }
+        Value::Object(map) => {
+            for (key, item) in map.iter_mut() {
+                if COORDINATE_KEYS.contains(&key.to_ascii_lowercase().as_str()) {
+                    fake_coordinate(item, key, coordinates);
+                } else {
+                    rewrite(item, terms, coordinates);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Replace a coordinate, keeping it a string or a number as it was.
+fn fake_coordinate(value: &mut Value, key: &str, seen: &mut Vec<String>) {
+    let original = match value {
+        Value::String(s) => s.clone(),
+        Value::Number(n) => n.to_string(),
+        _ => return,
+    };
+    let index = seen.iter().position(|c| *c == original).unwrap_or_else(|| {
+        seen.push(original);
+        seen.len() - 1
+    });
+    let base = if key.to_ascii_lowercase().starts_with("lat") {
+        35.0
+    } else {
+        139.0
+    };
+    let fake = format!("{:.6}", base + 0.001 * (index + 1) as f64);
+    *value = match value {
+        Value::String(_) => Value::String(fake),
+        _ => fake.parse().map(Value::Number).unwrap_or(Value::Null),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transit_dto::{load_next_data, next_data_to_transit_dto};
+
+    /// Check `anon` equals `orig` with every replacement applied to its
+    /// strings.
+    fn assert_same_shape(orig: &Value, anon: &Value, replacements: &[(String, String)]) {
+        match (orig, anon) {
+            (Value::String(o), Value::String(a)) => {
+                let mut expected = o.clone();
+                let mut sorted = replacements.to_vec();
+                sorted.sort_by_key(|(name, _)| std::cmp::Reverse(name.len()));
+                for (name, placeholder) in &sorted {
+                    expected = expected.replace(name.as_str(), placeholder);
+                }
+                assert_eq!(&expected, a);
+            }
+            (Value::Array(o), Value::Array(a)) => {
+                assert_eq!(o.len(), a.len());
+                for (o, a) in o.iter().zip(a) {
+                    assert_same_shape(o, a, replacements);
+                }
+            }
+            (Value::Object(o), Value::Object(a)) => {
+                assert!(o.keys().eq(a.keys()));
+                for (key, o) in o {
+                    assert_same_shape(o, &a[key], replacements);
+                }
+            }
+            (o, a) => assert_eq!(o, a),
+        }
+    }
+
+    #[test]
+    fn test_anonymized_fixtures_parse_the_same() {
+        for fixture in [
+            include_str!("../tests/fixtures/basic.json"),
+            include_str!("../tests/fixtures/shinkansen.json"),
+            include_str!("../tests/fixtures/unknown_lines.json"),
+        ] {
+            let root = load_next_data(fixture).unwrap();
+            let Anonymized {
+                next_data,
+                replacements,
+            } = anonymize_next_data(&root);
+            assert!(!replacements.is_empty());
+
+            let text = next_data.to_string();
+            for (name, _) in &replacements {
+                assert!(!text.contains(name.as_str()), "{name} left in output");
+                assert!(!text.contains(&encoded(name)), "{name} left encoded");
+            }
+
+            let orig = serde_json::to_value(next_data_to_transit_dto(&root).unwrap()).unwrap();
+            let anon = serde_json::to_value(next_data_to_transit_dto(&next_data).unwrap()).unwrap();
+            assert_same_shape(&orig, &anon, &replacements);
+
+            // deterministic
+            assert_eq!(anonymize_next_data(&root).next_data, next_data);
+        }
+    }
+
+    #[test]
+    fn test_placeholders_and_coordinates() {
+        let root = serde_json::json!({
+            "props": {"pageProps": {
+                "pageQuery": {"from": "渋谷", "to": "渋谷ヒカリエ", "via": ["代々木"]},
+                "places": [
+                    {"name": "渋谷ヒカリエ", "lat": "35.659", "lon": 139.7036},
+                    {"name": "渋谷駅前", "Lat": "35.659", "Lon": 139.7016},
+                ],
+                "url": "/search?from=%E6%B8%8B%E8%B0%B7",
+            }}
+        });
+        let anonymized = anonymize_next_data(&root);
+        let names: Vec<_> = anonymized
+            .replacements
+            .iter()
+            .map(|(n, p)| (n.as_str(), p.as_str()))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                ("渋谷", "地点A"),
+                ("渋谷ヒカリエ", "地点B"),
+                ("代々木", "地点C")
+            ]
+        );
+
+        let props = &anonymized.next_data["props"]["pageProps"];
+        assert_eq!(props["places"][0]["name"], "地点B");
+        assert_eq!(props["places"][1]["name"], "地点A駅前");
+        assert_eq!(props["places"][0]["lat"], props["places"][1]["Lat"]);
+        assert_eq!(props["places"][0]["lat"], "35.001000");
+        assert_eq!(props["places"][0]["lon"], 139.002);
+        assert_eq!(props["places"][1]["Lon"], 139.003);
+        assert_eq!(props["url"], format!("/search?from={}", encoded("地点A")));
+
+        assert_eq!(placeholder(25), "地点Z");
+        assert_eq!(placeholder(26), "地点AA");
+    }
+}
@@ -1,7 +1,13 @@
-use chrono::{DateTime, FixedOffset};
+use std::str::FromStr;
+
+use chrono::{DateTime, Datelike, FixedOffset, TimeDelta, Utc};
 use serde::{Deserialize, Serialize};
 
-use crate::dt_minute_tz;
+use crate::{
+    dt_minute_tz,
+    error::{ArgError, ArgWarning, PlaceField},
+    protocol,
+};
 
 /// Arguments for place suggestions.
 #[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
@@ -49,6 +55,19 @@ impl SeatPreference {
     }
 }
 
+/// Whose fares a search shows.
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+#[non_exhaustive]
+pub enum PassengerType {
+    /// 大人 (12 and over).
+    #[default]
+    Adult,
+    /// 小児 (6 to 11).
+    Child,
+}
+
 /// Walking speed when transferring.
 #[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, Hash)]
@@ -95,16 +114,32 @@ impl AvailableMeans {
     }
 }
 
+/// `FromStr` parsing the upstream code of a protocol enum, via the tables
+/// in [`protocol`].
+macro_rules! from_code_str {
+    ($ty:ty, $lookup:ident, $table:ident) => {
+        impl FromStr for $ty {
+            type Err = anyhow::Error;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                protocol::$lookup(&protocol::$table, s).ok_or_else(|| {
+                    anyhow::anyhow!(concat!("unknown ", stringify!($ty), " code {:?}"), s)
+                })
+            }
+        }
+    };
+}
+
+from_code_str!(TransitTicketPreference, from_code, TICKETS);
+from_code_str!(SeatPreference, from_numeric_code, SEATS);
+from_code_str!(WalkingSpeed, from_numeric_code, WALKING_SPEEDS);
+from_code_str!(AvailableMeans, from_code, MEANS);
+from_code_str!(DateKind, from_numeric_code, DATE_KINDS);
+from_code_str!(TransitCriteria, from_numeric_code, CRITERIAS);
+
 #[inline]
 fn default_available_means() -> Vec<AvailableMeans> {
-    vec![
-        AvailableMeans::Airlane,
-        AvailableMeans::Shinkansen,
-        AvailableMeans::PaidExpress,
-        AvailableMeans::HighwayBus,
-        AvailableMeans::Bus,
-        AvailableMeans::Ferry,
-    ]
+    protocol::MEANS.map(|(means, _)| means).into()
 }
 
 /// Optional route search options.
@@ -117,6 +152,10 @@ pub struct TransitOptions {
     #[serde(default)]
     pub ticket_preference: TransitTicketPreference,
     /// Seat preference: non-reserved, reserved, or green car (if applicable).
+    ///
+    /// Applies to every limited express / Shinkansen leg of a route; the
+    /// site has no per-leg setting. `SegmentDto::is_reservable` tells which
+    /// legs it affects.
     #[serde(default)]
     pub seat_preference: SeatPreference,
     /// Walking speed when transferring (default: Leisurely).
@@ -125,6 +164,32 @@ pub struct TransitOptions {
     /// Available means of transportation (default: all)
     #[serde(default = "default_available_means")]
     pub available_means: Vec<AvailableMeans>,
+    /// Operators to avoid (e.g. "東急", "京王"), matched against each
+    /// segment's operator or, when unknown, the start of its line name.
+    ///
+    /// The site has no such filter, so this is applied client-side: the
+    /// search fetches several ranks, drops routes using an avoided operator
+    /// and keeps the site rank of the rest, so ranks may have gaps. It
+    /// costs several requests per search and can leave no routes at all.
+    #[serde(default)]
+    pub avoid_operators: Vec<String>,
+    /// Operators a route may use exclusively (e.g. "JR" for a rail pass),
+    /// matched like `avoid_operators`.
+    ///
+    /// Applied client-side in the same way: only routes with at least one
+    /// ride, every one run by a listed operator, are kept, which may be none
+    /// of the fetched ranks.
+    #[serde(default)]
+    pub only_operators: Vec<String>,
+    /// Passenger type to show fares for (default: adult).
+    ///
+    /// The result page lists adult fares only, so child fares are computed
+    /// client-side by the half-fare rule and the route is marked
+    /// `fareIsEstimate`. Ticket fares are rounded per segment as the
+    /// operator does: JR drops fractions of 10 yen, other operators round
+    /// them up. See [`child_fare`](crate::transit_dto::child_fare).
+    #[serde(default)]
+    pub passenger: Option<PassengerType>,
 }
 
 impl Default for TransitOptions {
@@ -134,6 +199,9 @@ impl Default for TransitOptions {
             seat_preference: SeatPreference::default(),
             walking_speed: WalkingSpeed::default(),
             available_means: default_available_means(),
+            avoid_operators: Vec::new(),
+            only_operators: Vec::new(),
+            passenger: None,
         }
     }
 }
@@ -179,17 +247,57 @@ const fn default_rank() -> u32 {
     1
 }
 
+const JST_OFFSET_SECONDS: i32 = 9 * 3600;
+
+/// Normalize a user-typed place name.
+///
+/// Trims surrounding whitespace, including the full-width space `　`, and
+/// collapses any inner run of whitespace into a single ASCII space, so
+/// `"　新宿 "` becomes `"新宿"`.
+pub fn normalize_place(name: &str) -> String {
+    name.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Default for how many days ahead a search date can be before
+/// [`TransitArgs::warnings`] reports [`ArgWarning::BeyondHorizon`]: about
+/// three months, roughly how far ahead timetables are published.
+pub const DEFAULT_DATE_HORIZON_DAYS: u32 = 92;
+
+/// Years accepted by [`TransitArgs::validate`].
+const VALID_YEARS: std::ops::RangeInclusive<i32> = 2000..=2100;
+
+/// Current date/time in Japan Standard Time (UTC+09:00).
+pub fn jst_now() -> DateTime<FixedOffset> {
+    Utc::now().with_timezone(&jst_offset())
+}
+
+fn jst_offset() -> FixedOffset {
+    FixedOffset::east_opt(JST_OFFSET_SECONDS).expect("valid JST offset")
+}
+
 /// Arguments for transit route search.
+///
+/// `Default` uses the current JST time as `date`, a departure-time search
+/// and rank 1. `from` and `to` are empty and must be set before searching.
 #[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[cfg_attr(feature = "schemars", schemars(deny_unknown_fields))]
-#[derive(Debug, Default, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 #[serde(rename_all(deserialize = "camelCase"), deny_unknown_fields)]
 pub struct TransitArgs {
     /// Origin name (station / place).
+    ///
+    /// Leading/trailing whitespace (including full-width `　`) is removed and
+    /// inner runs collapse to one space before searching; see [`normalize_place`].
     pub from: String,
-    /// Destination name (station / place).
+    /// Destination name (station / place). Normalized like `from`.
     pub to: String,
-    /// Date/time with timezone. Minutes precision.
+    /// Places the route must pass through, in order. Normalized like
+    /// `from`; blank entries and entries repeating `from`, `to` or an
+    /// earlier via are left out of the query (see `TransitArgs::warnings`).
+    #[serde(default)]
+    pub via: Vec<String>,
+    /// Date/time with timezone. Minutes precision. Any offset is accepted;
+    /// the search uses the same instant in JST.
     #[serde(with = "dt_minute_tz")]
     #[cfg_attr(feature = "schemars", schemars(schema_with = "dt_minute_tz::schema"))]
     pub date: DateTime<FixedOffset>,
@@ -210,3 +318,330 @@ pub struct TransitArgs {
     #[serde(default)]
     pub options: TransitOptions,
 }
+
+impl Default for TransitArgs {
+    fn default() -> Self {
+        Self {
+            from: String::new(),
+            to: String::new(),
+            via: Vec::new(),
+            date: jst_now(),
+            date_kind: DateKind::default(),
+            criteria: TransitCriteria::default(),
+            rank: default_rank(),
+            options: TransitOptions::default(),
+        }
+    }
+}
+
+impl TransitArgs {
+    /// Check the arguments and collect every problem found.
+    ///
+    /// No `date_kind` is incompatible with a `date`: the time of day is
+    /// always sent, and [`DateKind::FirstTrain`] / [`DateKind::LastTrain`]
+    /// simply search the first or last trains of that day.
+    ///
+    /// # Errors
+    /// Returns all [`ArgError`]s at once, in field order.
+    pub fn validate(&self) -> Result<(), Vec<ArgError>> {
+        let mut errors = Vec::new();
+        if self.from.trim().is_empty() {
+            errors.push(ArgError::EmptyPlace(PlaceField::From));
+        }
+        if self.to.trim().is_empty() {
+            errors.push(ArgError::EmptyPlace(PlaceField::To));
+        }
+        let from = normalize_place(&self.from);
+        if !from.is_empty() && from == normalize_place(&self.to) {
+            errors.push(ArgError::SamePlace);
+        }
+        let year = self.jst_date().year();
+        if !VALID_YEARS.contains(&year) {
+            errors.push(ArgError::DateOutOfRange { year });
+        }
+        if self.rank == 0 {
+            errors.push(ArgError::ZeroRank);
+        }
+        if self.options.available_means.is_empty() {
+            errors.push(ArgError::NoAvailableMeans);
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// `date` as JST wall-clock time, which is what the site reads.
+    pub(crate) fn jst_date(&self) -> DateTime<FixedOffset> {
+        self.date.with_timezone(&jst_offset())
+    }
+
+    /// Problems that are fixed up when the query is built instead of being
+    /// rejected by [`validate`](Self::validate), in field order.
+    ///
+    /// The client adds them to [`TransitDto::warnings`](crate::transit_dto::TransitDto::warnings).
+    /// Dates are checked against the current JST time and
+    /// [`DEFAULT_DATE_HORIZON_DAYS`]; see [`warnings_at`](Self::warnings_at).
+    pub fn warnings(&self) -> Vec<ArgWarning> {
+        self.warnings_at(jst_now(), DEFAULT_DATE_HORIZON_DAYS)
+    }
+
+    /// [`warnings`](Self::warnings) as of `now`, reporting dates more than
+    /// a day before `now` or more than `horizon_days` after it. The date is
+    /// not checked for [`DateKind::NotSpecified`], which ignores it.
+    pub fn warnings_at(&self, now: DateTime<FixedOffset>, horizon_days: u32) -> Vec<ArgWarning> {
+        let mut warnings = self.clean_via().1;
+        if self.date_kind != DateKind::NotSpecified {
+            let ahead = self.date - now;
+            if ahead < -TimeDelta::days(1) {
+                warnings.push(ArgWarning::PastDate {
+                    days_ago: -ahead.num_days(),
+                });
+            } else if ahead > TimeDelta::days(horizon_days.into()) {
+                warnings.push(ArgWarning::BeyondHorizon {
+                    days_ahead: ahead.num_days(),
+                    horizon_days,
+                });
+            }
+        }
+        warnings
+    }
+
+    /// The normalized via places sent to the site, and why any were left
+    /// out.
+    pub(crate) fn clean_via(&self) -> (Vec<String>, Vec<ArgWarning>) {
+        let mut seen = vec![normalize_place(&self.from), normalize_place(&self.to)];
+        let mut via = Vec::new();
+        let mut warnings = Vec::new();
+        for (index, place) in self.via.iter().enumerate() {
+            let name = normalize_place(place);
+            if name.is_empty() {
+                warnings.push(ArgWarning::BlankVia { index });
+            } else if seen.contains(&name) {
+                warnings.push(ArgWarning::RedundantVia { index, name });
+            } else {
+                seen.push(name.clone());
+                via.push(name);
+            }
+        }
+        (via, warnings)
+    }
+}
+
+/// Parse the compact form `from>to` or `from>to@datetime`.
+///
+/// `datetime` is ISO 8601 with a timezone and minute precision
+/// (`2025-12-18T09:30+09:00`); without it the current JST time is used.
+/// Everything else takes its [`Default`] value (departure time, rank 1).
+///
+/// ```
+/// use yxhoo_transit::args::TransitArgs;
+///
+/// let args: TransitArgs = "新宿>渋谷@2025-12-18T09:30+09:00".parse().unwrap();
+/// assert_eq!(args.from, "新宿");
+/// ```
+impl FromStr for TransitArgs {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (route, date) = match s.split_once('@') {
+            Some((route, date)) => (route, Some(date.trim())),
+            None => (s, None),
+        };
+        let Some((from, to)) = route.split_once('>') else {
+            anyhow::bail!("expected `from>to[@datetime]`, missing `>` in {s:?}");
+        };
+        if to.contains('>') {
+            anyhow::bail!("expected a single `>` in {s:?}");
+        }
+        let (from, to) = (from.trim(), to.trim());
+        if from.is_empty() || to.is_empty() {
+            anyhow::bail!("`from` and `to` must not be empty in {s:?}");
+        }
+
+        let mut args = TransitArgs {
+            from: from.to_string(),
+            to: to.to_string(),
+            ..Default::default()
+        };
+        if let Some(date) = date {
+            args.date = dt_minute_tz::parse_str(date)
+                .map_err(|e| anyhow::anyhow!("invalid datetime {date:?}: {e}"))?;
+        }
+        Ok(args)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_place() {
+        assert_eq!(normalize_place("　新宿 "), "新宿");
+        assert_eq!(normalize_place("東京\u{3000}\u{3000}駅"), "東京 駅");
+        assert_eq!(normalize_place(" \t　"), "");
+    }
+
+    #[test]
+    fn test_from_str() {
+        let args: TransitArgs = "新宿>渋谷@2025-12-18T09:30+09:00".parse().unwrap();
+        assert_eq!(args.from, "新宿");
+        assert_eq!(args.to, "渋谷");
+        assert_eq!(args.date.to_rfc3339(), "2025-12-18T09:30:00+09:00");
+        assert_eq!(args.date_kind, DateKind::DepartureTime);
+        assert_eq!(args.rank, 1);
+
+        let args: TransitArgs = " 東京 > 品川 ".parse().unwrap();
+        assert_eq!((args.from.as_str(), args.to.as_str()), ("東京", "品川"));
+
+        for bad in [
+            "新宿",
+            "新宿>",
+            ">渋谷",
+            "新宿>渋谷>品川",
+            "新宿>渋谷@",
+            "新宿>渋谷@2025-12-18T09:30",
+            "新宿>渋谷@tomorrow",
+        ] {
+            assert!(bad.parse::<TransitArgs>().is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        let args = TransitArgs {
+            from: "新宿".into(),
+            to: "渋谷".into(),
+            ..Default::default()
+        };
+        assert_eq!(args.validate(), Ok(()));
+
+        let args = TransitArgs {
+            from: " ".into(),
+            rank: 0,
+            options: TransitOptions {
+                available_means: Vec::new(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert_eq!(
+            args.validate(),
+            Err(vec![
+                ArgError::EmptyPlace(PlaceField::From),
+                ArgError::EmptyPlace(PlaceField::To),
+                ArgError::ZeroRank,
+                ArgError::NoAvailableMeans,
+            ])
+        );
+
+        let args = TransitArgs {
+            from: "新宿".into(),
+            to: " 新宿".into(),
+            ..Default::default()
+        };
+        assert_eq!(args.validate(), Err(vec![ArgError::SamePlace]));
+
+        for (year, ok) in [(1999, false), (2000, true), (2100, true), (2101, false)] {
+            let args = TransitArgs {
+                from: "新宿".into(),
+                to: "渋谷".into(),
+                date: args.date.with_year(year).unwrap(),
+                ..Default::default()
+            };
+            let expected = if ok {
+                Ok(())
+            } else {
+                Err(vec![ArgError::DateOutOfRange { year }])
+            };
+            assert_eq!(args.validate(), expected, "{year}");
+        }
+
+        for date_kind in [
+            DateKind::FirstTrain,
+            DateKind::LastTrain,
+            DateKind::NotSpecified,
+        ] {
+            let args = TransitArgs {
+                from: "新宿".into(),
+                to: "渋谷".into(),
+                date_kind,
+                ..Default::default()
+            };
+            assert_eq!(args.validate(), Ok(()), "{date_kind:?}");
+        }
+    }
+
+    #[test]
+    fn test_date_warnings() {
+        let now = dt_minute_tz::parse_str("2025-12-18T09:30+09:00").unwrap();
+        let warnings = |date: &str, date_kind| {
+            TransitArgs {
+                from: "新宿".into(),
+                to: "渋谷".into(),
+                date: dt_minute_tz::parse_str(date).unwrap(),
+                date_kind,
+                ..Default::default()
+            }
+            .warnings_at(now, DEFAULT_DATE_HORIZON_DAYS)
+        };
+        let departure = DateKind::DepartureTime;
+
+        // earlier today, yesterday and the next few months are fine
+        assert_eq!(warnings("2025-12-18T06:00+09:00", departure), []);
+        assert_eq!(warnings("2025-12-17T09:30+09:00", departure), []);
+        assert_eq!(warnings("2026-03-20T09:30+09:00", departure), []);
+
+        assert_eq!(
+            warnings("2023-12-18T09:30+09:00", DateKind::LastTrain),
+            [ArgWarning::PastDate { days_ago: 731 }]
+        );
+        assert_eq!(
+            warnings("2026-12-18T09:30+09:00", departure),
+            [ArgWarning::BeyondHorizon {
+                days_ahead: 365,
+                horizon_days: DEFAULT_DATE_HORIZON_DAYS
+            }]
+        );
+        assert_eq!(
+            warnings("2023-12-18T09:30+09:00", DateKind::NotSpecified),
+            []
+        );
+    }
+
+    #[test]
+    fn test_clean_via() {
+        let args = TransitArgs {
+            from: "新宿\n".into(),
+            to: "渋谷\u{3000}".into(),
+            via: vec![
+                " 代々木\u{3000}".into(),
+                "新宿".into(),
+                "\u{3000}".into(),
+                "代々木".into(),
+                "原宿".into(),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(args.validate(), Ok(()));
+        let (via, warnings) = args.clean_via();
+        assert_eq!(via, ["代々木", "原宿"]);
+        assert_eq!(
+            warnings,
+            [
+                ArgWarning::RedundantVia {
+                    index: 1,
+                    name: "新宿".into()
+                },
+                ArgWarning::BlankVia { index: 2 },
+                ArgWarning::RedundantVia {
+                    index: 3,
+                    name: "代々木".into()
+                },
+            ]
+        );
+        assert_eq!(args.warnings(), warnings);
+    }
+}
@@ -0,0 +1,209 @@
+//! Debounced place suggestions for search-as-you-type inputs.
+
+use std::{future::Future, pin::Pin, time::Duration};
+
+use futures_util::{Stream, StreamExt, stream};
+use tokio::time::{Instant, sleep_until};
+
+use crate::{client::TransitClient, yxhoo::YxhooSuggestResponse};
+
+/// Suggest places for each query typed into a search box.
+///
+/// A query is only sent once `debounce` has passed without a newer one
+/// arriving. A newer query also cancels a request still in flight, so each
+/// yielded response belongs to the latest query at the time it completed and
+/// responses never arrive out of order. Queries that are blank after
+/// trimming are not sent; they only cancel what came before.
+///
+/// The stream ends after `queries` ends and the last pending query has been
+/// answered.
+///
+/// ```no_run
+/// # async fn run(client: yxhoo_transit::TransitClient) {
+/// use std::time::Duration;
+///
+/// use futures_util::{StreamExt, stream};
+/// use yxhoo_transit::autocomplete::autocomplete;
+///
+/// let keystrokes = stream::iter(["s", "sh", "shin", "shinjuku"].map(String::from));
+/// let mut suggestions = Box::pin(autocomplete(client, keystrokes, Duration::from_millis(250)));
+/// while let Some((query, response)) = suggestions.next().await {
+///     println!("{query}: {} results", response.map_or(0, |r| r.results.len()));
+/// }
+/// # }
+/// ```
+pub fn autocomplete(
+    client: TransitClient,
+    queries: impl Stream<Item = String>,
+    debounce: Duration,
+) -> impl Stream<Item = (String, anyhow::Result<YxhooSuggestResponse>)> {
+    debounced(queries, debounce, move |query| {
+        let client = client.clone();
+        async move { client.suggest_places(&query).await }
+    })
+}
+
+type InFlight<T> = (String, Pin<Box<dyn Future<Output = T> + Send>>);
+
+struct State<Q, F, T> {
+    queries: Pin<Box<Q>>,
+    queries_done: bool,
+    fetch: F,
+    debounce: Duration,
+    /// Latest query and when its debounce ends.
+    pending: Option<(String, Instant)>,
+    in_flight: Option<InFlight<T>>,
+}
+
+/// [`autocomplete`] over any fetch function.
+fn debounced<Q, F, Fut, T>(
+    queries: Q,
+    debounce: Duration,
+    fetch: F,
+) -> impl Stream<Item = (String, T)>
+where
+    Q: Stream<Item = String>,
+    F: FnMut(String) -> Fut,
+    Fut: Future<Output = T> + Send + 'static,
+{
+    let state = State {
+        queries: Box::pin(queries),
+        queries_done: false,
+        fetch,
+        debounce,
+        pending: None,
+        in_flight: None,
+    };
+    stream::unfold(state, |mut state| async move {
+        let item = state.next().await?;
+        Some((item, state))
+    })
+}
+
+impl<Q, F, Fut, T> State<Q, F, T>
+where
+    Q: Stream<Item = String>,
+    F: FnMut(String) -> Fut,
+    Fut: Future<Output = T> + Send + 'static,
+{
+    async fn next(&mut self) -> Option<(String, T)> {
+        loop {
+            if self.queries_done && self.pending.is_none() && self.in_flight.is_none() {
+                return None;
+            }
+            let deadline = self.pending.as_ref().map(|(_, at)| *at);
+            tokio::select! {
+                query = self.queries.next(), if !self.queries_done => match query {
+                    Some(query) => {
+                        self.in_flight = None;
+                        self.pending = (!query.trim().is_empty())
+                            .then(|| (query, Instant::now() + self.debounce));
+                    }
+                    None => self.queries_done = true,
+                },
+                () = sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => {
+                    let (query, _) = self.pending.take().expect("deadline implies pending");
+                    let request = (self.fetch)(query.clone());
+                    self.in_flight = Some((query, Box::pin(request)));
+                }
+                result = answer(&mut self.in_flight), if self.in_flight.is_some() => {
+                    let (query, _) = self.in_flight.take().expect("answered request");
+                    return Some((query, result));
+                }
+            }
+        }
+    }
+}
+
+/// Wait for the in-flight request, if any.
+async fn answer<T>(in_flight: &mut Option<InFlight<T>>) -> T {
+    match in_flight {
+        Some((_, request)) => request.await,
+        None => std::future::pending().await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use tokio::sync::mpsc;
+
+    use super::*;
+
+    const DEBOUNCE: Duration = Duration::from_millis(200);
+    const LATENCY: Duration = Duration::from_millis(500);
+
+    /// Fetch that answers after [`LATENCY`] and records what was sent.
+    fn mock(
+        sent: Arc<Mutex<Vec<String>>>,
+    ) -> impl FnMut(String) -> Pin<Box<dyn Future<Output = String> + Send>> {
+        move |query| {
+            sent.lock().unwrap().push(query.clone());
+            Box::pin(async move {
+                tokio::time::sleep(LATENCY).await;
+                format!("results for {query}")
+            })
+        }
+    }
+
+    /// Feed `(delay, query)` pairs, each `delay` after the previous one.
+    fn typed(keys: &[(u64, &str)]) -> impl Stream<Item = String> + use<> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let keys: Vec<_> = keys.iter().map(|(ms, q)| (*ms, q.to_string())).collect();
+        tokio::spawn(async move {
+            for (ms, query) in keys {
+                tokio::time::sleep(Duration::from_millis(ms)).await;
+                tx.send(query).unwrap();
+            }
+        });
+        stream::unfold(rx, |mut rx| async move { Some((rx.recv().await?, rx)) })
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_debounce_sends_only_settled_query() {
+        let sent = Arc::default();
+        let keys = typed(&[(0, "s"), (50, "sh"), (50, "shin"), (50, "shinjuku")]);
+        let start = Instant::now();
+        let out: Vec<_> = debounced(keys, DEBOUNCE, mock(Arc::clone(&sent)))
+            .collect()
+            .await;
+
+        assert_eq!(*sent.lock().unwrap(), ["shinjuku"]);
+        assert_eq!(
+            out,
+            [("shinjuku".to_string(), "results for shinjuku".to_string())]
+        );
+        assert_eq!(
+            start.elapsed(),
+            Duration::from_millis(150) + DEBOUNCE + LATENCY
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_newer_query_drops_in_flight_request() {
+        let sent = Arc::default();
+        // "shib" is sent after the debounce but superseded before it answers.
+        let keys = typed(&[(0, "shib"), (300, "shibuya"), (1000, "ikebukuro")]);
+        let out: Vec<_> = debounced(keys, DEBOUNCE, mock(Arc::clone(&sent)))
+            .collect()
+            .await;
+
+        assert_eq!(*sent.lock().unwrap(), ["shib", "shibuya", "ikebukuro"]);
+        let queries: Vec<_> = out.iter().map(|(q, _)| q.as_str()).collect();
+        assert_eq!(queries, ["shibuya", "ikebukuro"]);
+        assert!(out.iter().all(|(q, r)| *r == format!("results for {q}")));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_blank_query_cancels_without_sending() {
+        let sent = Arc::default();
+        let keys = typed(&[(0, "ueno"), (100, "  ")]);
+        let out: Vec<_> = debounced(keys, DEBOUNCE, mock(Arc::clone(&sent)))
+            .collect()
+            .await;
+
+        assert!(sent.lock().unwrap().is_empty());
+        assert!(out.is_empty());
+    }
+}
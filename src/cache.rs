@@ -0,0 +1,128 @@
+//! Validator cache for conditional requests, shared by clones of a client.
+
+use std::{collections::HashMap, fmt, sync::Mutex};
+
+/// Response bodies keyed by URL, with the validators they were served with.
+///
+/// Only responses carrying an `ETag` or `Last-Modified` header are kept.
+/// When full, the least recently stored entry is evicted.
+pub(crate) struct ResponseCache {
+    max_entries: usize,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    entries: HashMap<String, Entry>,
+    /// Incremented on every store; orders entries for eviction.
+    clock: u64,
+}
+
+/// A cached body and its validators.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Entry {
+    pub(crate) etag: Option<String>,
+    pub(crate) last_modified: Option<String>,
+    pub(crate) body: String,
+    stored_at: u64,
+}
+
+impl ResponseCache {
+    /// An empty cache. `max_entries` is clamped to at least 1.
+    pub(crate) fn new(max_entries: usize) -> Self {
+        Self {
+            max_entries: max_entries.max(1),
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    /// The entry for `url`, if any.
+    pub(crate) fn get(&self, url: &str) -> Option<Entry> {
+        self.state.lock().unwrap().entries.get(url).cloned()
+    }
+
+    /// Record a full response for `url`.
+    ///
+    /// Without validators the response cannot be revalidated, so any
+    /// previous entry for `url` is dropped instead.
+    pub(crate) fn store(
+        &self,
+        url: &str,
+        etag: Option<String>,
+        last_modified: Option<String>,
+        body: &str,
+    ) {
+        let mut state = self.state.lock().unwrap();
+        if etag.is_none() && last_modified.is_none() {
+            state.entries.remove(url);
+            return;
+        }
+
+        if !state.entries.contains_key(url)
+            && state.entries.len() >= self.max_entries
+            && let Some(oldest) = state
+                .entries
+                .iter()
+                .min_by_key(|(_, e)| e.stored_at)
+                .map(|(url, _)| url.clone())
+        {
+            state.entries.remove(&oldest);
+        }
+
+        state.clock += 1;
+        let entry = Entry {
+            etag,
+            last_modified,
+            body: body.to_string(),
+            stored_at: state.clock,
+        };
+        state.entries.insert(url.to_string(), entry);
+    }
+
+    /// Number of cached responses.
+    pub(crate) fn len(&self) -> usize {
+        self.state.lock().unwrap().entries.len()
+    }
+}
+
+impl fmt::Debug for ResponseCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ResponseCache")
+            .field("max_entries", &self.max_entries)
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_store_and_evict() {
+        let cache = ResponseCache::new(2);
+        cache.store("/a", Some("\"1\"".into()), None, "a");
+        cache.store(
+            "/b",
+            None,
+            Some("Thu, 18 Dec 2025 00:00:00 GMT".into()),
+            "b",
+        );
+        assert_eq!(cache.get("/a").unwrap().body, "a");
+
+        // updating an entry does not evict another
+        cache.store("/a", Some("\"2\"".into()), None, "a2");
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get("/a").unwrap().etag.as_deref(), Some("\"2\""));
+
+        // "/b" is now the oldest
+        cache.store("/c", Some("\"3\"".into()), None, "c");
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get("/b").is_none());
+
+        // a response without validators drops the stale entry
+        cache.store("/a", None, None, "a3");
+        assert!(cache.get("/a").is_none());
+        assert_eq!(cache.len(), 1);
+    }
+}
@@ -0,0 +1,3033 @@
+#[cfg(feature = "http-reqwest")]
+use std::net::SocketAddr;
+use std::{
+    collections::{BTreeMap, HashMap},
+    net::IpAddr,
+    ops::RangeInclusive,
+    sync::{
+        Arc, LazyLock, Mutex, OnceLock, Weak,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+
+use chrono::{DateTime, FixedOffset};
+use futures_util::StreamExt;
+
+#[cfg(feature = "http-wreq")]
+use crate::http::Emulation;
+use crate::{
+    args::{
+        DEFAULT_DATE_HORIZON_DAYS, DateKind, PassengerType, SeatPreference, TransitArgs,
+        TransitCriteria, normalize_place,
+    },
+    cache::ResponseCache,
+    clock::{SharedClock, SystemClock},
+    error::{ArgWarning, TransitError, blocked_status, is_blocked, tls_error},
+    http::{
+        BACKEND, Certificate, HeaderMap, HttpClient, HttpConfig, ProxySetting, decode_body,
+        http_client,
+    },
+    limiter::RateLimiter,
+    maintenance::maintenance_page,
+    transit_dto::{
+        Budget, CriteriaComparison, CriteriaFailure, ParseOptions, RouteDto, RouteSummaryDto,
+        SeatFareComparison, TransitDto, avoid_operators, load_next_data,
+        next_data_to_transit_dto_with, only_operators,
+    },
+    verify::{DEFAULT_VERIFY_FROM, DEFAULT_VERIFY_TO, ParserVerification, verify_next_data},
+    yxhoo::{
+        BASE_URL, PlaceResolution, YxhooSuggestResponse, best_matches, build_transit_query,
+        encode_query, parse_suggest_response,
+    },
+};
+
+/// Retry behavior for [`TransitClient`].
+///
+/// Everything is disabled by default, so a request is sent exactly once.
+#[derive(Debug, Clone, Default)]
+pub struct RetryConfig {
+    /// Number of additional attempts when sending a request fails
+    /// (connection errors, timeouts). Defaults to 0. Responses, including
+    /// [`TransitError::Maintenance`], are never retried.
+    pub max_retries: u32,
+    /// Delay before the first retry; the n-th retry waits `n * backoff`.
+    pub backoff: Duration,
+    /// Retry `/api/suggest` once when it returns no results.
+    ///
+    /// The suggest endpoint occasionally answers a valid query with an empty
+    /// result set. When enabled, a single extra request is made if `results`
+    /// is empty and the trimmed query has at least 2 characters; shorter
+    /// queries legitimately match nothing and are never retried.
+    pub retry_empty_suggestions: bool,
+}
+
+/// Upper bound on the searches made by one [`TransitClient::transit_window`] call.
+pub const TRANSIT_WINDOW_MAX_REQUESTS: u32 = 20;
+
+/// Upper bound on the searches [`TransitClient::transit_routes_streaming`]
+/// runs at once, whatever the requested count.
+pub const STREAMING_MAX_CONCURRENT_REQUESTS: usize = 4;
+
+/// Ranks fetched by [`TransitClient::transit`] when operators are avoided
+/// or restricted.
+pub const AVOID_OPERATORS_RANKS: u32 = 5;
+
+/// Response headers returned by [`TransitClient::transit_with_headers`].
+pub const DEBUG_HEADERS: &[&str] = &[
+    "age",
+    "cache-control",
+    "content-location",
+    "content-type",
+    "date",
+    "etag",
+    "expires",
+    "last-modified",
+    "location",
+    "server",
+    "vary",
+    "via",
+    "x-cache",
+];
+
+/// Minimum trimmed query length (in characters) for an empty-result retry.
+const EMPTY_SUGGEST_RETRY_MIN_CHARS: usize = 2;
+
+/// Path of the result page.
+const TRANSIT_PATH: &str = "/search/print";
+
+/// Maximum number of `did_you_mean` suggestions attached to an error.
+const DID_YOU_MEAN_LIMIT: usize = 3;
+
+/// Reusable client for the Yxhoo! Transit endpoints.
+///
+/// Cloning is cheap: clones share the connection pool, the rate limiter and
+/// the active fallback profile, so a limit set with
+/// [`TransitClientBuilder::rate_limit`] holds across all of them. Build a
+/// separate client for independent state.
+#[derive(Clone)]
+pub struct TransitClient {
+    /// Primary HTTP client followed by fallbacks (one per emulation profile).
+    http: Arc<[HttpClient]>,
+    /// Index into `http` used for the next request.
+    active: Arc<AtomicUsize>,
+    limiter: Option<Arc<RateLimiter>>,
+    timeout: Duration,
+    base_url: String,
+    retry: RetryConfig,
+    did_you_mean: bool,
+    parse: ParseOptions,
+    decorator: Option<RequestDecorator>,
+    on_wait: Option<WaitHook>,
+    redact_queries: bool,
+    cache: Option<Arc<ResponseCache>>,
+    date_horizon_days: u32,
+    clock: SharedClock,
+}
+
+impl std::fmt::Debug for TransitClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TransitClient")
+            .field("backend", &BACKEND)
+            .field("base_url", &self.base_url)
+            .field("timeout", &self.timeout)
+            .field("profiles", &self.http.len())
+            .field("max_retries", &self.retry.max_retries)
+            .field("rate_limit", &self.limiter)
+            .field("did_you_mean", &self.did_you_mean)
+            .field("cache", &self.cache)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Why the client is about to sleep, passed to
+/// [`TransitClientBuilder::on_wait`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum WaitReason {
+    /// Waiting for the rate limit set with
+    /// [`TransitClientBuilder::rate_limit`].
+    RateLimit,
+    /// Backing off before retry number `attempt` (from 1) of a failed send.
+    RetryBackoff { attempt: u32 },
+}
+
+/// Hook told about every intentional sleep.
+#[derive(Clone)]
+struct WaitHook(Arc<dyn Fn(WaitReason, Duration) + Send + Sync>);
+
+impl std::fmt::Debug for WaitHook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("WaitHook")
+    }
+}
+
+/// Hook adding headers to every outgoing request.
+#[derive(Clone)]
+struct RequestDecorator(Arc<dyn Fn(&mut HeaderMap) + Send + Sync>);
+
+impl std::fmt::Debug for RequestDecorator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("RequestDecorator")
+    }
+}
+
+/// Builder for [`TransitClient`].
+#[derive(Debug, Clone)]
+pub struct TransitClientBuilder {
+    http: HttpConfig,
+    #[cfg(feature = "http-wreq")]
+    emulation_fallbacks: Vec<Emulation>,
+    base_url: String,
+    retry: RetryConfig,
+    did_you_mean: bool,
+    parse: ParseOptions,
+    decorator: Option<RequestDecorator>,
+    on_wait: Option<WaitHook>,
+    rate_limit: Option<(u32, Duration)>,
+    redact_queries: bool,
+    conditional_requests: Option<usize>,
+    date_horizon_days: u32,
+    clock: SharedClock,
+}
+
+impl Default for TransitClientBuilder {
+    fn default() -> Self {
+        Self {
+            http: HttpConfig::default(),
+            #[cfg(feature = "http-wreq")]
+            emulation_fallbacks: Vec::new(),
+            base_url: BASE_URL.clone(),
+            retry: RetryConfig::default(),
+            did_you_mean: false,
+            parse: ParseOptions::default(),
+            decorator: None,
+            on_wait: None,
+            rate_limit: None,
+            redact_queries: true,
+            conditional_requests: None,
+            date_horizon_days: DEFAULT_DATE_HORIZON_DAYS,
+            clock: Arc::new(SystemClock),
+        }
+    }
+}
+
+impl TransitClientBuilder {
+    /// Request timeout (default: 10 seconds).
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.http.timeout = timeout;
+        self
+    }
+
+    /// Ignore system proxy settings and connect directly.
+    pub fn no_proxy(mut self) -> Self {
+        self.http.proxy = ProxySetting::Disabled;
+        self
+    }
+
+    /// Send every request through the given proxy URL
+    /// (e.g. `http://proxy.example:8080`).
+    ///
+    /// By default the system proxy settings are used.
+    pub fn proxy(mut self, url: impl Into<String>) -> Self {
+        self.http.proxy = ProxySetting::Url(url.into());
+        self
+    }
+
+    /// Maximum idle connections kept per host (default: backend default).
+    pub fn pool_max_idle_per_host(mut self, max: usize) -> Self {
+        self.http.pool_max_idle_per_host = Some(max);
+        self
+    }
+
+    /// How long idle pooled connections are kept before being closed
+    /// (default: backend default).
+    ///
+    /// Keep this below the idle timeout of any NAT or proxy in between to
+    /// avoid reusing sockets that were silently dropped.
+    pub fn pool_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.http.pool_idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Enable TCP keepalive probes with the given interval
+    /// (default: backend default).
+    pub fn tcp_keepalive(mut self, interval: Duration) -> Self {
+        self.http.tcp_keepalive = Some(interval);
+        self
+    }
+
+    /// Bind outgoing connections to a local address (e.g. to pin egress to
+    /// one interface on a multi-homed host).
+    pub fn local_address(mut self, addr: IpAddr) -> Self {
+        self.http.local_address = Some(addr);
+        self
+    }
+
+    /// Resolve `host` to `addr` instead of using DNS.
+    ///
+    /// The port of `addr` is ignored; the port from the URL is used.
+    /// Only available with the `http-reqwest` backend.
+    #[cfg(feature = "http-reqwest")]
+    pub fn resolve(mut self, host: impl Into<String>, addr: SocketAddr) -> Self {
+        self.http.resolve.push((host.into(), addr));
+        self
+    }
+
+    /// Override the base URL (scheme and host, without trailing slash).
+    pub fn base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Retry behavior (default: no retries).
+    pub fn retry(mut self, retry: RetryConfig) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Browser profile to emulate (default: `Emulation::Chrome137`).
+    #[cfg(feature = "http-wreq")]
+    pub fn emulation(mut self, emulation: Emulation) -> Self {
+        self.http.emulation = emulation;
+        self
+    }
+
+    /// Profiles to rotate to, in order, when a request is classified as
+    /// [`TransitError::Blocked`](crate::TransitError::Blocked).
+    ///
+    /// The blocked request is re-sent with the next profile, and the client
+    /// keeps using the profile that succeeded. Empty by default.
+    #[cfg(feature = "http-wreq")]
+    pub fn emulation_fallbacks(mut self, fallbacks: Vec<Emulation>) -> Self {
+        self.emulation_fallbacks = fallbacks;
+        self
+    }
+
+    /// Fill `did_you_mean` on [`TransitError::PlaceNotFound`] and
+    /// [`TransitError::NoRouteFound`] (default: off).
+    ///
+    /// When enabled, a failing search triggers one extra `/api/suggest`
+    /// request per suspicious input, and the 3 closest names are attached.
+    pub fn did_you_mean(mut self, enabled: bool) -> Self {
+        self.did_you_mean = enabled;
+        self
+    }
+
+    /// How many days ahead a search date can be before a
+    /// [`ArgWarning::BeyondHorizon`](crate::ArgWarning::BeyondHorizon)
+    /// warning is added to the result (default:
+    /// [`DEFAULT_DATE_HORIZON_DAYS`]).
+    pub fn date_horizon_days(mut self, days: u32) -> Self {
+        self.date_horizon_days = days;
+        self
+    }
+
+    /// Maximum number of routes parsed from one result page
+    /// (default: [`ParseOptions::DEFAULT_MAX_ROUTES`]).
+    pub fn max_routes(mut self, max_routes: usize) -> Self {
+        self.parse.max_routes = max_routes;
+        self
+    }
+
+    /// Replace the searched `from` / `to` with `⟨redacted⟩` in response
+    /// excerpts attached to errors and tracing events (default: on).
+    ///
+    /// Result pages echo the search places, which may be personal
+    /// addresses; keep this on if errors are shipped to a third party.
+    pub fn redact_queries(mut self, enabled: bool) -> Self {
+        self.redact_queries = enabled;
+        self
+    }
+
+    /// Send at most `max_requests` requests per `per`, waiting as needed
+    /// (default: unlimited).
+    ///
+    /// Every attempt counts, including retries and fallback profiles. The
+    /// budget is shared by all clones of the built client.
+    pub fn rate_limit(mut self, max_requests: u32, per: Duration) -> Self {
+        self.rate_limit = Some((max_requests, per));
+        self
+    }
+
+    /// Revalidate repeated requests instead of downloading them again
+    /// (default: off).
+    ///
+    /// Responses that carry an `ETag` or `Last-Modified` header are kept,
+    /// up to `max_entries` URLs, and the next request for the same URL
+    /// sends `If-None-Match` / `If-Modified-Since`. On `304 Not Modified`
+    /// the kept body is parsed as if it had been sent again. Responses
+    /// without those headers are not kept, so servers that omit them see
+    /// no difference. The cache is shared by all clones of the built
+    /// client.
+    pub fn conditional_requests(mut self, max_entries: usize) -> Self {
+        self.conditional_requests = Some(max_entries);
+        self
+    }
+
+    /// Trust only the given root certificates instead of the built-in ones
+    /// (default: built-in roots).
+    ///
+    /// A server whose certificate does not chain to one of them fails with
+    /// [`TransitError::Tls`](crate::TransitError::Tls).
+    pub fn pin_certificates(mut self, certificates: Vec<Certificate>) -> Self {
+        self.http.pinned_certificates = certificates;
+        self
+    }
+
+    /// Accept any server certificate, including self-signed and expired
+    /// ones (default: off). Only with the `dangerous-config` feature.
+    ///
+    /// **Never use this in production.** With verification off, anyone on
+    /// the network path can impersonate the server. It exists so tests can
+    /// talk to a local HTTPS mock without a real CA; prefer
+    /// [`pin_certificates`](Self::pin_certificates) with the mock's
+    /// certificate where possible. Overrides `pin_certificates`.
+    #[cfg(feature = "dangerous-config")]
+    pub fn danger_accept_invalid_certs(mut self, accept: bool) -> Self {
+        self.http.accept_invalid_certs = accept;
+        self
+    }
+
+    /// Keep cookies the site sets and send them back on later requests
+    /// (default: off, no cookies are stored or sent).
+    ///
+    /// Some anti-bot checks expect cookie continuity, so this can make a
+    /// long-running client more reliable. The cookies stay in memory for the
+    /// life of the client and its clones, and let the site link all of its
+    /// requests into one session, which it cannot do otherwise. They are
+    /// never written to disk. Fallback profiles each keep their own cookies.
+    pub fn cookie_store(mut self, enabled: bool) -> Self {
+        self.http.cookie_store = enabled;
+        self
+    }
+
+    /// Add headers to every outgoing request (e.g. a correlation id).
+    ///
+    /// The hook runs once per request, including retries and
+    /// [`TransitClient::warm_up`]. It only sees the headers, so it cannot
+    /// change the URL or method.
+    pub fn request_decorator<F>(mut self, decorator: F) -> Self
+    where
+        F: Fn(&mut HeaderMap) + Send + Sync + 'static,
+    {
+        self.decorator = Some(RequestDecorator(Arc::new(decorator)));
+        self
+    }
+
+    /// Call `hook` whenever the client is about to sleep on purpose, with
+    /// the reason and the planned length, e.g. to show "waiting 3s for the
+    /// rate limit" instead of appearing hung (default: none).
+    ///
+    /// The hook runs before each sleep, on the task making the request, so
+    /// it should return quickly.
+    pub fn on_wait<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(WaitReason, Duration) + Send + Sync + 'static,
+    {
+        self.on_wait = Some(WaitHook(Arc::new(hook)));
+        self
+    }
+
+    /// Use `clock` for the current time and for sleeps: rate limiting,
+    /// retry backoff, maintenance end times and date checks
+    /// (default: [`SystemClock`](crate::clock::SystemClock)).
+    ///
+    /// With a [`MockClock`](crate::clock::MockClock), waits return at once,
+    /// so tests of time-dependent behavior need no real sleeps.
+    #[cfg(any(test, feature = "test-util"))]
+    pub fn clock(mut self, clock: impl crate::clock::Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Build the client.
+    ///
+    /// # Errors
+    /// Returns an error if the underlying HTTP client cannot be built.
+    pub fn build(self) -> anyhow::Result<TransitClient> {
+        #[allow(unused_mut)]
+        let mut http = vec![http_client(&self.http)?];
+        #[cfg(feature = "http-wreq")]
+        for emulation in &self.emulation_fallbacks {
+            let config = HttpConfig {
+                emulation: *emulation,
+                ..self.http.clone()
+            };
+            http.push(http_client(&config)?);
+        }
+
+        Ok(TransitClient {
+            http: http.into(),
+            active: Arc::new(AtomicUsize::new(0)),
+            limiter: self
+                .rate_limit
+                .map(|(max, per)| Arc::new(RateLimiter::new(max, per, self.clock.clone()))),
+            timeout: self.http.timeout,
+            base_url: self.base_url,
+            retry: self.retry,
+            did_you_mean: self.did_you_mean,
+            parse: self.parse,
+            decorator: self.decorator,
+            on_wait: self.on_wait,
+            redact_queries: self.redact_queries,
+            cache: self
+                .conditional_requests
+                .map(|max| Arc::new(ResponseCache::new(max))),
+            date_horizon_days: self.date_horizon_days,
+            clock: self.clock,
+        })
+    }
+}
+
+impl Default for TransitClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TransitClient {
+    /// Create a client with default settings.
+    ///
+    /// # Panics
+    /// Panics if the underlying HTTP client cannot be built.
+    /// Use [`TransitClient::builder`] to handle the error instead.
+    pub fn new() -> Self {
+        Self::builder().build().unwrap()
+    }
+
+    /// Create a builder to configure a client.
+    pub fn builder() -> TransitClientBuilder {
+        TransitClientBuilder::default()
+    }
+
+    /// Suggest places by a free-form query string.
+    ///
+    /// # Errors
+    /// Returns an error if the HTTP request fails or the response cannot be parsed.
+    pub async fn suggest_places(&self, query: &str) -> anyhow::Result<YxhooSuggestResponse> {
+        let response = self.fetch_suggest(query).await?;
+
+        if response.results.is_empty()
+            && self.retry.retry_empty_suggestions
+            && query.trim().chars().count() >= EMPTY_SUGGEST_RETRY_MIN_CHARS
+        {
+            return self.fetch_suggest(query).await;
+        }
+
+        Ok(response)
+    }
+
+    /// Suggest places and classify whether the query has one clear match.
+    ///
+    /// See [`PlaceResolution::from_places`] for the rule.
+    ///
+    /// # Errors
+    /// Returns an error if the HTTP request fails or the response cannot be parsed.
+    pub async fn resolve_place(&self, query: &str) -> anyhow::Result<PlaceResolution> {
+        let response = self.suggest_places(query).await?;
+        Ok(PlaceResolution::from_places(response.results))
+    }
+
+    /// Search transit routes using the given arguments.
+    ///
+    /// # Errors
+    /// Returns [`TransitError::InvalidArgs`] if `args` fail validation, or an
+    /// error if the HTTP request fails or the response cannot be parsed.
+    ///
+    /// With [`TransitOptions::avoid_operators`](crate::args::TransitOptions::avoid_operators)
+    /// or [`TransitOptions::only_operators`](crate::args::TransitOptions::only_operators)
+    /// set, the first [`AVOID_OPERATORS_RANKS`] ranks are fetched (one
+    /// request each) and filtered client-side, and `args.rank` is ignored;
+    /// this is logged at debug level.
+    pub async fn transit(&self, args: &TransitArgs) -> anyhow::Result<TransitDto> {
+        args.validate().map_err(TransitError::InvalidArgs)?;
+        let options = &args.options;
+        if options.avoid_operators.is_empty() && options.only_operators.is_empty() {
+            return self.fetch_transit(args).await;
+        }
+        tracing::debug!(
+            requests = AVOID_OPERATORS_RANKS,
+            rank = args.rank,
+            "operator filters fetch several ranks; rank is ignored"
+        );
+        let mut dto = self.fetch_ranks(args, AVOID_OPERATORS_RANKS).await?;
+        dto.routes = filter_operators(dto.routes, args);
+        Ok(dto)
+    }
+
+    /// Fetch and parse one result page.
+    async fn fetch_transit(&self, args: &TransitArgs) -> anyhow::Result<TransitDto> {
+        let (dto, _) = self.fetch_transit_with_headers(args).await?;
+        Ok(dto)
+    }
+
+    /// [`fetch_transit`](Self::fetch_transit), also returning the
+    /// [`DEBUG_HEADERS`] of the response.
+    async fn fetch_transit_with_headers(
+        &self,
+        args: &TransitArgs,
+    ) -> anyhow::Result<(TransitDto, HeaderMap)> {
+        let q = build_transit_query(args);
+        let (body, headers) = self.get_text_with_headers(TRANSIT_PATH, &q).await?;
+        match self.parse_transit_body(&body, args) {
+            Ok(dto) => Ok((dto, headers)),
+            Err(e) if self.did_you_mean => Err(self.attach_did_you_mean(e, args).await),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Parse a result page of a search for `args`: the client's
+    /// [`ParseOptions`], explained rejections, argument warnings and child
+    /// fares. Maintenance pages are caught before this.
+    fn parse_transit_body(&self, body: &str, args: &TransitArgs) -> anyhow::Result<TransitDto> {
+        let serde_value = load_next_data(body).map_err(|e| self.attach_excerpt(e, body, args))?;
+        let mut dto = next_data_to_transit_dto_with(&serde_value, &self.parse).map_err(|e| {
+            explain_invalid_search(e, args, self.clock.now_jst(), self.date_horizon_days)
+        })?;
+        let warnings = args
+            .warnings_at(self.clock.now_jst(), self.date_horizon_days)
+            .into_iter()
+            .map(|w| w.to_string());
+        dto.warnings.splice(0..0, warnings);
+        if args.options.passenger == Some(PassengerType::Child) {
+            dto.estimate_child_fares();
+        }
+        Ok(dto)
+    }
+
+    /// URL of the result page for `args`, as fetched by
+    /// [`transit`](Self::transit) (for a single rank).
+    pub fn transit_request_url(&self, args: &TransitArgs) -> String {
+        format!(
+            "{}{TRANSIT_PATH}?{}",
+            self.base_url,
+            encode_query(&build_transit_query(args))
+        )
+    }
+
+    /// Parse a result page fetched elsewhere (a gateway, a cache) without
+    /// making any request.
+    ///
+    /// This is the seam between network and parsing: the body goes through
+    /// the same parsing as a page fetched by
+    /// [`transit_with_headers`](Self::transit_with_headers), including the
+    /// client's [`ParseOptions`], argument warnings, child fares and the
+    /// operator filters. `did_you_mean` suggestions are never looked up, as
+    /// that needs requests. Callers are responsible for fetching
+    /// [`transit_request_url`](Self::transit_request_url); the body is not
+    /// checked against `args`. Accepts the HTML page or its bare
+    /// `__NEXT_DATA__` JSON.
+    ///
+    /// # Errors
+    /// Returns [`TransitError::Maintenance`] for the maintenance page, or an
+    /// error if the page cannot be parsed or has no route.
+    pub fn transit_from_body(&self, args: &TransitArgs, body: &str) -> anyhow::Result<TransitDto> {
+        if let Some(e) = maintenance_page(body, None, self.clock.now_jst()) {
+            return Err(e.into());
+        }
+        let mut dto = self.parse_transit_body(body, args)?;
+        dto.routes = filter_operators(dto.routes, args);
+        Ok(dto)
+    }
+
+    /// Like [`transit`](Self::transit), also returning headers of the
+    /// response that help explain odd results: caching (`cache-control`,
+    /// `age`, `etag`, ...), redirects (`location`) and the serving
+    /// infrastructure (`server`, `via`, `x-cache`). See [`DEBUG_HEADERS`].
+    ///
+    /// This is always a single request for `args.rank`; the operator
+    /// filters are applied to the routes of that page only. Which headers
+    /// are present can differ between the `http-reqwest` and `http-wreq`
+    /// backends, e.g. headers consumed by transparent decompression. With
+    /// [`conditional_requests`](TransitClientBuilder::conditional_requests),
+    /// a revalidated page comes with the headers of the 304 response.
+    ///
+    /// # Errors
+    /// Same as [`transit`](Self::transit).
+    pub async fn transit_with_headers(
+        &self,
+        args: &TransitArgs,
+    ) -> anyhow::Result<(TransitDto, HeaderMap)> {
+        args.validate().map_err(TransitError::InvalidArgs)?;
+        let (mut dto, headers) = self.fetch_transit_with_headers(args).await?;
+        dto.routes = filter_operators(dto.routes, args);
+        Ok((dto, headers))
+    }
+
+    /// Like [`transit`](Self::transit), but also checks the result for
+    /// signs that the parser no longer understands the page.
+    ///
+    /// When more than [`TransitDto::UNKNOWN_SEGMENT_WARN_RATIO`] of the
+    /// segments have an unknown mode, a warning is logged and added to
+    /// [`TransitDto::warnings`]. So is each route whose departure and arrival
+    /// times disagree with its duration by more than
+    /// [`RouteSummaryDto::DURATION_MISMATCH_WARN_MINUTES`].
+    ///
+    /// # Errors
+    /// Same as [`transit`](Self::transit).
+    pub async fn transit_with_diagnostics(&self, args: &TransitArgs) -> anyhow::Result<TransitDto> {
+        let mut dto = self.transit(args).await?;
+        let ratio = dto.unknown_segment_ratio();
+        if ratio > TransitDto::UNKNOWN_SEGMENT_WARN_RATIO {
+            tracing::warn!(
+                ratio,
+                "most segments have an unknown mode; markup may have changed"
+            );
+            dto.warnings.push(format!(
+                "{:.0}% of segments have an unknown mode; line names may no longer be parsed",
+                ratio * 100.0
+            ));
+        }
+        for route in &dto.routes {
+            let summary = &route.summary;
+            let Some(diff) = summary.duration_from_times() else {
+                continue;
+            };
+            if diff.abs() > RouteSummaryDto::DURATION_MISMATCH_WARN_MINUTES {
+                let reported = summary.duration_minutes.unwrap_or_default();
+                tracing::warn!(
+                    rank = route.rank,
+                    diff,
+                    "route duration disagrees with its times"
+                );
+                dto.warnings.push(format!(
+                    "route {}: times span {} min but duration is {reported} min",
+                    route.rank,
+                    i64::from(reported) + diff
+                ));
+            }
+        }
+        Ok(dto)
+    }
+
+    /// Search once per [`TransitCriteria`] concurrently and collect the top
+    /// route of each.
+    ///
+    /// `args.criteria` and `args.rank` are ignored. A route winning several
+    /// criteria appears once, listing all of them. A failing search is
+    /// recorded in [`CriteriaComparison::failures`] without affecting the
+    /// others.
+    ///
+    /// # Errors
+    /// Returns the first error if every search fails.
+    pub async fn transit_by_all_criteria(
+        &self,
+        args: &TransitArgs,
+    ) -> anyhow::Result<CriteriaComparison> {
+        const ALL: [TransitCriteria; 3] = [
+            TransitCriteria::EarliestArrival,
+            TransitCriteria::LowestCost,
+            TransitCriteria::FewestTransfers,
+        ];
+
+        let searches = ALL.map(|criteria| {
+            let args = TransitArgs {
+                criteria,
+                rank: 1,
+                ..args.clone()
+            };
+            async move { (criteria, self.transit(&args).await) }
+        });
+        let results = futures_util::future::join_all(searches).await;
+
+        let mut comparison = CriteriaComparison {
+            routes: Vec::new(),
+            failures: Vec::new(),
+        };
+        let mut first_error = None;
+        for (criteria, result) in results {
+            match result.and_then(top_route) {
+                Ok(route) => comparison.insert(criteria, route),
+                Err(e) => {
+                    comparison.failures.push(CriteriaFailure {
+                        criteria,
+                        error: format!("{e:#}"),
+                    });
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+
+        match first_error {
+            Some(e) if comparison.routes.is_empty() => Err(e),
+            _ => Ok(comparison),
+        }
+    }
+
+    /// Search the route at `args.rank` once per [`SeatPreference`] and
+    /// compare the fares.
+    ///
+    /// The non-reserved search runs first; the reserved and green-car
+    /// searches then run concurrently, and only if that route has a limited
+    /// express or Shinkansen leg. A class answered with a different journey
+    /// is flagged in [`SeatFare::same_route`](crate::transit_dto::SeatFare::same_route)
+    /// and gets no delta. `args.options.seat_preference` is ignored.
+    ///
+    /// # Errors
+    /// Returns the first error if any of the searches fails.
+    pub async fn compare_seat_fares(
+        &self,
+        args: &TransitArgs,
+    ) -> anyhow::Result<SeatFareComparison> {
+        let search = |seat_preference| {
+            let mut args = args.clone();
+            args.options.seat_preference = seat_preference;
+            async move { self.transit(&args).await.and_then(top_route) }
+        };
+
+        let base = search(SeatPreference::NonReserved).await?;
+        if !base.offers_seat_classes() {
+            return Ok(SeatFareComparison::new(base, Vec::new()));
+        }
+        let (reserved, green) = futures_util::future::join(
+            search(SeatPreference::Reserved),
+            search(SeatPreference::GreenCar),
+        )
+        .await;
+        Ok(SeatFareComparison::new(
+            base,
+            vec![
+                (SeatPreference::Reserved, reserved?),
+                (SeatPreference::GreenCar, green?),
+            ],
+        ))
+    }
+
+    /// Fetch routes ranked 1 to `count` concurrently and keep those within
+    /// `budget`, best first for `args.criteria`.
+    ///
+    /// `args.rank` is ignored. Each rank is a separate request, so this costs
+    /// `count` page fetches; keep `count` small. Ranks past the last route
+    /// the site offers are skipped, and a route returned for several ranks
+    /// appears once.
+    ///
+    /// ```no_run
+    /// # async fn run(client: yxhoo_transit::TransitClient) -> anyhow::Result<()> {
+    /// let args: yxhoo_transit::args::TransitArgs = "新宿>横浜".parse()?;
+    /// let routes = client.transit_under_budget(&args, 1500, 3).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    /// Returns the error of the rank-1 search if it fails.
+    pub async fn transit_under_budget(
+        &self,
+        args: &TransitArgs,
+        budget: impl Into<Budget>,
+        count: u32,
+    ) -> anyhow::Result<Vec<RouteDto>> {
+        args.validate().map_err(TransitError::InvalidArgs)?;
+        let routes = self.fetch_ranks(args, count).await?.routes;
+        let routes = filter_operators(routes, args);
+        Ok(budget.into().filter(routes, args.criteria))
+    }
+
+    /// Fetch the routes ranked `ranks`, keyed by rank.
+    ///
+    /// `args.rank` is ignored. Each rank is a separate request, so this costs
+    /// one page fetch per rank in the range (`start..=5` is `6 - start`
+    /// requests); they run concurrently and count against the client's rate
+    /// limit. Ranks past the last route the site offers are absent from the
+    /// map, as are ranks whose search returned no route of that rank or a
+    /// route already in the map. A start of 0 is treated as 1.
+    ///
+    /// No recorded result page has a paging structure, so `no=` is the only
+    /// way to reach later routes and there is no page-based API.
+    ///
+    /// ```no_run
+    /// # async fn run(client: yxhoo_transit::TransitClient) -> anyhow::Result<()> {
+    /// let args: yxhoo_transit::args::TransitArgs = "新宿>横浜".parse()?;
+    /// let alternatives = client.transit_rank_range(&args, 2..=5).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an error if `args` are invalid, or the first error of a
+    /// search other than a rank being out of range.
+    pub async fn transit_rank_range(
+        &self,
+        args: &TransitArgs,
+        ranks: RangeInclusive<u32>,
+    ) -> anyhow::Result<BTreeMap<u32, RouteDto>> {
+        args.validate().map_err(TransitError::InvalidArgs)?;
+        let searches = ranks.filter(|&rank| rank > 0).map(|rank| {
+            let args = TransitArgs {
+                rank,
+                ..args.clone()
+            };
+            async move { (rank, self.fetch_transit(&args).await) }
+        });
+        let results = futures_util::future::join_all(searches).await;
+
+        let mut routes = BTreeMap::new();
+        for (rank, result) in results {
+            match result {
+                Ok(dto) => {
+                    if let Some(route) = route_at_rank(dto, rank)
+                        && !routes.values().any(|r: &RouteDto| r.same_journey(&route))
+                    {
+                        routes.insert(rank, route);
+                    }
+                }
+                Err(e) if rank > 1 && rank_out_of_range(&e) => {
+                    tracing::debug!(rank, error = %e, "rank not offered");
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(routes)
+    }
+
+    /// Fetch ranks 1 to `count` and call `on_route` with each route as soon
+    /// as it and every better rank have arrived, for rendering routes
+    /// progressively. Returns the number of routes passed to `on_route`.
+    ///
+    /// `args.rank` is ignored. Up to [`STREAMING_MAX_CONCURRENT_REQUESTS`]
+    /// searches run at once and count against the client's rate limit, but
+    /// `on_route` always sees the
+    /// routes in rank order, so a slow rank holds back the ones after it.
+    /// Fetching stops at the first rank the site does not offer. A search
+    /// that returns no route of its rank, or a route already passed on, is
+    /// skipped.
+    ///
+    /// # Errors
+    /// Returns an error if `args` are invalid, or the first error of a
+    /// search other than a rank being out of range. Routes passed to
+    /// `on_route` before the error stay delivered.
+    pub async fn transit_routes_streaming(
+        &self,
+        args: &TransitArgs,
+        count: u32,
+        mut on_route: impl FnMut(RouteDto),
+    ) -> anyhow::Result<u32> {
+        args.validate().map_err(TransitError::InvalidArgs)?;
+        let searches = (1..=count).map(|rank| {
+            let args = TransitArgs {
+                rank,
+                ..args.clone()
+            };
+            async move { (rank, self.fetch_transit(&args).await) }
+        });
+        let mut results =
+            futures_util::stream::iter(searches).buffered(STREAMING_MAX_CONCURRENT_REQUESTS);
+
+        let mut delivered: Vec<RouteDto> = Vec::new();
+        while let Some((rank, result)) = results.next().await {
+            match result {
+                Ok(dto) => {
+                    if let Some(route) = route_at_rank(dto, rank)
+                        && !delivered.iter().any(|r| r.same_journey(&route))
+                    {
+                        on_route(route.clone());
+                        delivered.push(route);
+                    }
+                }
+                Err(e) if rank > 1 && rank_out_of_range(&e) => {
+                    tracing::debug!(rank, error = %e, "rank not offered");
+                    break;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(delivered.len() as u32)
+    }
+
+    /// Fetch ranks 1 to `count` (at least 1) concurrently.
+    ///
+    /// Returns the rank-1 result with the distinct routes of all ranks.
+    /// Later ranks that fail are skipped, as the site may offer fewer.
+    async fn fetch_ranks(&self, args: &TransitArgs, count: u32) -> anyhow::Result<TransitDto> {
+        let searches = (1..=count.max(1)).map(|rank| {
+            let args = TransitArgs {
+                rank,
+                ..args.clone()
+            };
+            async move { (rank, self.fetch_transit(&args).await) }
+        });
+        let results = futures_util::future::join_all(searches).await;
+
+        let mut first: Option<TransitDto> = None;
+        let mut routes: Vec<RouteDto> = Vec::new();
+        for (rank, result) in results {
+            match result {
+                Ok(mut dto) => {
+                    for route in dto.routes.drain(..) {
+                        if !routes.iter().any(|r| r.same_journey(&route)) {
+                            routes.push(route);
+                        }
+                    }
+                    first.get_or_insert(dto);
+                }
+                Err(e) if rank == 1 => return Err(e),
+                Err(e) => tracing::debug!(rank, error = %e, "skipping rank"),
+            }
+        }
+        let mut dto = first.ok_or_else(|| anyhow::anyhow!("no route returned"))?;
+        dto.routes = routes;
+        Ok(dto)
+    }
+
+    /// Collect the distinct routes departing within `window` after
+    /// `args.date`, earliest first, re-ranked from 1.
+    ///
+    /// The site has no window parameter, so this searches repeatedly: each
+    /// search departs one minute after the latest departure found so far, or
+    /// `step` after the previous search when given. It stops at the window
+    /// end or after [`TRANSIT_WINDOW_MAX_REQUESTS`] searches, so a long
+    /// window may be cut short. Searches run one at a time and count
+    /// against the client's rate limit. `args.date_kind` and `args.rank` are
+    /// ignored.
+    ///
+    /// # Errors
+    /// Returns an error if `window` or `step` is out of range, or the first
+    /// error of any search.
+    pub async fn transit_window(
+        &self,
+        args: &TransitArgs,
+        window: Duration,
+        step: Option<Duration>,
+    ) -> anyhow::Result<Vec<RouteDto>> {
+        let one_minute = chrono::Duration::minutes(1);
+        let start = args.date;
+        let end = start + chrono::Duration::from_std(window)?;
+        let step = step.map(chrono::Duration::from_std).transpose()?;
+
+        let mut cursor = start;
+        let mut routes: Vec<RouteDto> = Vec::new();
+        for _ in 0..TRANSIT_WINDOW_MAX_REQUESTS {
+            if cursor >= end {
+                break;
+            }
+            let search = TransitArgs {
+                date: cursor,
+                date_kind: DateKind::DepartureTime,
+                rank: 1,
+                ..args.clone()
+            };
+            let dto = self.transit(&search).await?;
+
+            let mut latest = None;
+            for route in dto.routes {
+                let Some(departure) = route.summary.departure_time else {
+                    continue;
+                };
+                latest = latest.max(Some(departure));
+                if (start..end).contains(&departure)
+                    && !routes.iter().any(|r| r.same_journey(&route))
+                {
+                    routes.push(route);
+                }
+            }
+
+            let next = match (step, latest) {
+                (Some(step), _) => cursor + step,
+                (None, Some(latest)) => latest + one_minute,
+                (None, None) => cursor,
+            };
+            cursor = next.max(cursor + one_minute);
+        }
+
+        routes.sort_by_key(|r| r.summary.departure_time);
+        Ok(routes
+            .into_iter()
+            .zip(1..)
+            .map(|(route, rank)| RouteDto { rank, ..route })
+            .collect())
+    }
+
+    /// Open a connection to the base host ahead of the first real request.
+    ///
+    /// Purely a latency optimization: it sends one `GET /` and drops the
+    /// body so the pooled connection (and TLS session) can be reused.
+    /// Failures are ignored.
+    pub async fn warm_up(&self) {
+        let http = &self.http[self.active.load(Ordering::Relaxed) % self.http.len()];
+        let url = format!("{}/", self.base_url);
+        self.throttle().await;
+        match http.get(&url).headers(self.headers()).send().await {
+            Ok(response) => {
+                let _ = response.bytes().await;
+            }
+            Err(e) => tracing::debug!(error = %e, "warm-up request failed"),
+        }
+    }
+
+    /// Run a live search between [`DEFAULT_VERIFY_FROM`] and
+    /// [`DEFAULT_VERIFY_TO`] and report which fields the parser could extract.
+    ///
+    /// This is a diagnostic for markup drift on the unofficial API; it only
+    /// runs when called explicitly.
+    ///
+    /// # Errors
+    /// Returns an error if the HTTP request fails or the body has no
+    /// `__NEXT_DATA__`. Parse failures are reported in the result instead.
+    pub async fn verify_parser(&self) -> anyhow::Result<ParserVerification> {
+        self.verify_parser_between(DEFAULT_VERIFY_FROM, DEFAULT_VERIFY_TO)
+            .await
+    }
+
+    /// Like [`TransitClient::verify_parser`], with a custom origin and destination.
+    ///
+    /// # Errors
+    /// See [`TransitClient::verify_parser`].
+    pub async fn verify_parser_between(
+        &self,
+        from: &str,
+        to: &str,
+    ) -> anyhow::Result<ParserVerification> {
+        let args = TransitArgs {
+            from: from.into(),
+            to: to.into(),
+            ..Default::default()
+        };
+        let body = self
+            .get_text("/search/print", &build_transit_query(&args))
+            .await?;
+        let root = load_next_data(&body).map_err(|e| self.attach_excerpt(e, &body, &args))?;
+        Ok(verify_next_data(&root, &self.parse))
+    }
+
+    /// Add the start of an unparsable `body` to `err` and log it, with the
+    /// search places redacted unless disabled on the client.
+    fn attach_excerpt(&self, err: anyhow::Error, body: &str, args: &TransitArgs) -> anyhow::Error {
+        let terms = if self.redact_queries {
+            redaction_terms(args)
+        } else {
+            Vec::new()
+        };
+        let excerpt = body_excerpt(body, &terms);
+        tracing::debug!(%excerpt, "unparsable response body");
+        err.context(format!("unparsable response body: {excerpt}"))
+    }
+
+    async fn attach_did_you_mean(&self, err: anyhow::Error, args: &TransitArgs) -> anyhow::Error {
+        let mut err = match err.downcast::<TransitError>() {
+            Ok(err) => err,
+            Err(other) => return other,
+        };
+
+        match &mut err {
+            TransitError::PlaceNotFound {
+                query,
+                did_you_mean,
+                ..
+            } => *did_you_mean = self.did_you_mean_for(query).await,
+            TransitError::NoRouteFound {
+                from,
+                to,
+                did_you_mean,
+            } => {
+                // only inputs that resolved to a different name look like typos;
+                // compare them as sent, not as typed
+                for (input, resolved) in [(&args.from, &*from), (&args.to, &*to)] {
+                    let input = normalize_place(input);
+                    if input != normalize_place(resolved) && did_you_mean.len() < DID_YOU_MEAN_LIMIT
+                    {
+                        let names = self.did_you_mean_for(&input).await;
+                        let room = DID_YOU_MEAN_LIMIT - did_you_mean.len();
+                        did_you_mean.extend(names.into_iter().take(room));
+                    }
+                }
+            }
+            _ => {}
+        }
+        err.into()
+    }
+
+    async fn did_you_mean_for(&self, query: &str) -> Vec<String> {
+        match self.suggest_places(query).await {
+            Ok(response) => best_matches(query, &response.results, DID_YOU_MEAN_LIMIT),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    async fn fetch_suggest(&self, query: &str) -> anyhow::Result<YxhooSuggestResponse> {
+        let q = [("value".to_string(), query.to_string())];
+        let body = self.get_text("/api/suggest", &q).await?;
+        parse_suggest_response(&body)
+    }
+
+    async fn get_text(&self, path: &str, query: &[(String, String)]) -> anyhow::Result<String> {
+        let (body, _) = self.get_text_with_headers(path, query).await?;
+        Ok(body)
+    }
+
+    /// [`get_text`](Self::get_text), also returning the [`DEBUG_HEADERS`]
+    /// of the response.
+    async fn get_text_with_headers(
+        &self,
+        path: &str,
+        query: &[(String, String)],
+    ) -> anyhow::Result<(String, HeaderMap)> {
+        // Encode the query ourselves so both backends send exactly what the
+        // site's own search form would.
+        let url = format!("{}{}?{}", self.base_url, path, encode_query(query));
+        let count = self.http.len();
+        let start = self.active.load(Ordering::Relaxed);
+
+        let mut result = Err(anyhow::anyhow!("no HTTP client configured"));
+        for i in 0..count {
+            let idx = (start + i) % count;
+            result = self.send_with_retry(&self.http[idx], &url).await;
+            match &result {
+                Err(e) if is_blocked(e) => self.active.store((idx + 1) % count, Ordering::Relaxed),
+                _ => break,
+            }
+        }
+        result
+    }
+
+    /// Extra headers from the request decorator, if any.
+    fn headers(&self) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        if let Some(RequestDecorator(decorate)) = &self.decorator {
+            decorate(&mut headers);
+        }
+        headers
+    }
+
+    /// Wait for the rate limiter, if any.
+    async fn throttle(&self) {
+        if let Some(limiter) = &self.limiter {
+            limiter
+                .acquire(|wait| self.notify_wait(WaitReason::RateLimit, wait))
+                .await;
+        }
+    }
+
+    /// Tell the wait hook, if any, about an upcoming sleep.
+    fn notify_wait(&self, reason: WaitReason, wait: Duration) {
+        if let Some(WaitHook(hook)) = &self.on_wait {
+            hook(reason, wait);
+        }
+    }
+
+    async fn send_with_retry(
+        &self,
+        http: &HttpClient,
+        url: &str,
+    ) -> anyhow::Result<(String, HeaderMap)> {
+        let mut attempt = 0;
+        loop {
+            self.throttle().await;
+            let cached = self.cache.as_ref().and_then(|cache| cache.get(url));
+            let mut request = http.get(url).headers(self.headers());
+            if let Some(cached) = &cached {
+                if let Some(etag) = &cached.etag {
+                    request = request.header("If-None-Match", etag.as_str());
+                }
+                if let Some(last_modified) = &cached.last_modified {
+                    request = request.header("If-Modified-Since", last_modified.as_str());
+                }
+            }
+            match request.send().await {
+                Ok(response) => {
+                    let status = response.status().as_u16();
+                    if let Some(e) = blocked_status(status) {
+                        return Err(e.into());
+                    }
+                    let headers = debug_headers(response.headers());
+                    if status == 304 {
+                        let body = cached.map(|c| c.body).ok_or_else(|| {
+                            anyhow::anyhow!("HTTP 304 for a request that was not conditional")
+                        })?;
+                        return Ok((body, headers));
+                    }
+                    let header = |name: &str| {
+                        response
+                            .headers()
+                            .get(name)
+                            .and_then(|v| v.to_str().ok())
+                            .map(str::to_string)
+                    };
+                    let content_type = header("content-type");
+                    let etag = header("etag");
+                    let last_modified = header("last-modified");
+                    let bytes = response.bytes().await?;
+                    let body = decode_body(&bytes, content_type.as_deref())?;
+                    if let Some(e) = maintenance_page(&body, Some(status), self.clock.now_jst()) {
+                        return Err(e.into());
+                    }
+                    if let Some(cache) = &self.cache
+                        && status == 200
+                    {
+                        cache.store(url, etag, last_modified, &body);
+                    }
+                    return Ok((body, headers));
+                }
+                Err(e) => {
+                    if let Some(tls) = tls_error(&e) {
+                        return Err(tls.into());
+                    }
+                    if attempt >= self.retry.max_retries {
+                        return Err(e.into());
+                    }
+                    attempt += 1;
+                    let wait = self.retry.backoff * attempt;
+                    self.notify_wait(WaitReason::RetryBackoff { attempt }, wait);
+                    self.clock.sleep(wait).await;
+                }
+            }
+        }
+    }
+}
+
+/// The [`DEBUG_HEADERS`] of `headers`, keeping repeated values.
+fn debug_headers(headers: &HeaderMap) -> HeaderMap {
+    let mut out = HeaderMap::new();
+    for (name, value) in headers {
+        if DEBUG_HEADERS.contains(&name.as_str()) {
+            out.append(name.clone(), value.clone());
+        }
+    }
+    out
+}
+
+/// Characters of a response body kept in an excerpt.
+const EXCERPT_CHARS: usize = 200;
+
+/// Replacement for redacted search places.
+const REDACTED: &str = "⟨redacted⟩";
+
+/// The strings under which a page may echo the search places: as typed,
+/// normalized, form-encoded, HTML-escaped and JSON-escaped. Longest first,
+/// so overlapping names are fully replaced.
+fn redaction_terms(args: &TransitArgs) -> Vec<String> {
+    let mut terms: Vec<String> = [&args.from, &args.to]
+        .into_iter()
+        .chain(&args.via)
+        .flat_map(|place| {
+            let normalized = normalize_place(place);
+            let encoded = encode_query(&[(String::new(), normalized.clone())]);
+            let mut terms = vec![encoded.trim_start_matches('=').to_string()];
+            for name in [place.clone(), normalized] {
+                terms.extend(html_escaped(&name));
+                terms.extend(json_escaped(&name));
+                terms.push(name);
+            }
+            terms
+        })
+        .filter(|t| !t.trim().is_empty())
+        .collect();
+    terms.sort_by_key(|t| std::cmp::Reverse(t.len()));
+    terms.dedup();
+    terms
+}
+
+/// `name` with the HTML special characters escaped, and additionally with
+/// every non-ASCII character as a decimal or hex character reference.
+fn html_escaped(name: &str) -> [String; 3] {
+    let escape = |reference: fn(char) -> String| {
+        name.chars()
+            .map(|c| match c {
+                '&' => "&amp;".to_string(),
+                '<' => "&lt;".to_string(),
+                '>' => "&gt;".to_string(),
+                '"' => "&quot;".to_string(),
+                '\'' => "&#39;".to_string(),
+                c if !c.is_ascii() => reference(c),
+                c => c.to_string(),
+            })
+            .collect()
+    };
+    [
+        escape(|c| c.to_string()),
+        escape(|c| format!("&#{};", u32::from(c))),
+        escape(|c| format!("&#x{:x};", u32::from(c))),
+    ]
+}
+
+/// `name` as a JSON string body with every non-ASCII character as
+/// `\uXXXX` (surrogate pairs beyond the BMP), in lower- and uppercase hex.
+fn json_escaped(name: &str) -> [String; 2] {
+    let escape = |upper: bool| {
+        let mut out = String::new();
+        for c in name.chars() {
+            match c {
+                '"' => out.push_str("\\\""),
+                '\\' => out.push_str("\\\\"),
+                c if c.is_ascii() => out.push(c),
+                c => {
+                    for unit in c.encode_utf16(&mut [0; 2]) {
+                        if upper {
+                            out.push_str(&format!("\\u{unit:04X}"));
+                        } else {
+                            out.push_str(&format!("\\u{unit:04x}"));
+                        }
+                    }
+                }
+            }
+        }
+        out
+    };
+    [escape(false), escape(true)]
+}
+
+/// The first [`EXCERPT_CHARS`] characters of `body`, trimmed, after replacing
+/// every `terms` occurrence.
+fn body_excerpt(body: &str, terms: &[String]) -> String {
+    let mut body = body.trim().to_string();
+    for term in terms {
+        body = body.replace(term.as_str(), REDACTED);
+    }
+    match body.char_indices().nth(EXCERPT_CHARS) {
+        Some((end, _)) => format!("{}…", &body[..end]),
+        None => body,
+    }
+}
+
+/// Whether a search for a rank > 1 failed because the site offers fewer
+/// routes: it rejects the rank, or the page has no route.
+fn rank_out_of_range(err: &anyhow::Error) -> bool {
+    matches!(
+        err.downcast_ref::<TransitError>(),
+        Some(TransitError::InvalidSearch { .. } | TransitError::NoRouteFound { .. })
+    )
+}
+
+/// Apply the operator filters of `args.options` to `routes`.
+fn filter_operators(routes: Vec<RouteDto>, args: &TransitArgs) -> Vec<RouteDto> {
+    let routes = avoid_operators(routes, &args.options.avoid_operators);
+    only_operators(routes, &args.options.only_operators)
+}
+
+/// The route numbered `rank` in the result of a search for `rank`.
+///
+/// `None` if no route carries that rank, e.g. because the site ignored
+/// `no=` or offers fewer routes; another route is never renamed to it.
+fn route_at_rank(dto: TransitDto, rank: u32) -> Option<RouteDto> {
+    dto.routes.into_iter().find(|r| r.rank == rank)
+}
+
+/// The first route of a search result.
+fn top_route(dto: TransitDto) -> anyhow::Result<RouteDto> {
+    dto.routes
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("no route returned"))
+}
+
+/// Settings of the client used by the free functions
+/// [`suggest_places`](crate::suggest_places) and [`transit`](crate::transit).
+static DEFAULT_BUILDER: OnceLock<TransitClientBuilder> = OnceLock::new();
+
+/// The default clients of live tokio runtimes (`None` outside a runtime).
+/// Pooled connections are driven by the runtime that opened them, so a
+/// client must not be shared between runtimes.
+static DEFAULT_CLIENTS: LazyLock<Mutex<HashMap<Option<tokio::runtime::Id>, DefaultClient>>> =
+    LazyLock::new(Default::default);
+
+/// A default client and, for one built in a runtime, whether that runtime
+/// is still running.
+struct DefaultClient {
+    client: TransitClient,
+    /// Upgradable until the runtime shuts down.
+    runtime_alive: Option<Weak<()>>,
+}
+
+impl DefaultClient {
+    fn is_stale(&self) -> bool {
+        self.runtime_alive
+            .as_ref()
+            .is_some_and(|alive| alive.strong_count() == 0)
+    }
+}
+
+/// Configure the client used by the free functions.
+///
+/// Must be called before the first free-function call; afterwards the
+/// settings are fixed. A client is built from `builder` for each tokio
+/// runtime the free functions run on, so state such as the rate limit and
+/// the response cache is per runtime. The client of a runtime that has shut
+/// down is dropped on the next free-function call.
+///
+/// # Errors
+/// Returns an error if the settings are already fixed (by an earlier call
+/// to this function or by a free-function call).
+pub fn init_default_client(builder: TransitClientBuilder) -> anyhow::Result<()> {
+    DEFAULT_BUILDER
+        .set(builder)
+        .map_err(|_| anyhow::anyhow!("default client is already initialized"))
+}
+
+/// The default client for the current tokio runtime, built on first use
+/// there.
+pub(crate) fn default_client() -> anyhow::Result<TransitClient> {
+    let handle = tokio::runtime::Handle::try_current().ok();
+    let runtime = handle.as_ref().map(|handle| handle.id());
+    let mut clients = DEFAULT_CLIENTS.lock().unwrap();
+    clients.retain(|_, c| !c.is_stale());
+    if let Some(c) = clients.get(&runtime) {
+        return Ok(c.client.clone());
+    }
+    let client = DEFAULT_BUILDER
+        .get_or_init(Default::default)
+        .clone()
+        .build()?;
+    // A shutting-down runtime drops its tasks, and with them the token.
+    let runtime_alive = handle.map(|handle| {
+        let token = Arc::new(());
+        let alive = Arc::downgrade(&token);
+        handle.spawn(async move {
+            let _token = token;
+            std::future::pending::<()>().await
+        });
+        alive
+    });
+    clients.insert(
+        runtime,
+        DefaultClient {
+            client: client.clone(),
+            runtime_alive,
+        },
+    );
+    Ok(client)
+}
+
+/// Name the arguments most likely rejected in a [`TransitError::InvalidSearch`].
+///
+/// Runs after local validation passed, so only arguments that are valid but
+/// suspicious are mentioned. Dates are suspect when they would be warned
+/// about with the client's `date_horizon_days`; see
+/// [`TransitArgs::warnings_at`].
+fn explain_invalid_search(
+    err: anyhow::Error,
+    args: &TransitArgs,
+    now: DateTime<FixedOffset>,
+    horizon_days: u32,
+) -> anyhow::Error {
+    let Some(TransitError::InvalidSearch { reason }) = err.downcast_ref::<TransitError>() else {
+        return err;
+    };
+
+    let mut suspects = Vec::new();
+    if args.rank > 1 {
+        suspects.push(format!(
+            "`rank` {} may exceed the number of routes",
+            args.rank
+        ));
+    }
+    for warning in args.warnings_at(now, horizon_days) {
+        match warning {
+            ArgWarning::PastDate { days_ago } => {
+                suspects.push(format!("`date` is {days_ago} days in the past"));
+            }
+            ArgWarning::BeyondHorizon {
+                days_ahead,
+                horizon_days,
+            } => suspects.push(format!(
+                "`date` is {days_ahead} days ahead, beyond the {horizon_days}-day timetable horizon"
+            )),
+            _ => {}
+        }
+    }
+    if suspects.is_empty() {
+        return err;
+    }
+
+    TransitError::InvalidSearch {
+        reason: format!("{reason}; likely cause: {}", suspects.join(", ")),
+    }
+    .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Datelike;
+
+    use crate::{
+        ArgError, TransitError,
+        args::jst_now,
+        clock::MockClock,
+        test_server::{Reply, TestServer},
+    };
+
+    const EMPTY: &str = r#"{"@query":"q","@totalResultsAvailable":0,"Result":[]}"#;
+    const SHINJUKU: &str = r#"{"@query":"q","@totalResultsAvailable":1,"Result":[{"Suggest":"新宿","Yomi":"しんじゅく","Lat":"35.690921","Lon":"139.700258","Address":"東京都新宿区","Id":"st"}]}"#;
+
+    async fn empty_then_found() -> TestServer {
+        let calls = AtomicUsize::new(0);
+        TestServer::start(move |_| {
+            if calls.fetch_add(1, Ordering::SeqCst) == 0 {
+                Reply::json(EMPTY)
+            } else {
+                Reply::json(SHINJUKU)
+            }
+        })
+        .await
+    }
+
+    fn client(server: &TestServer, retry_empty_suggestions: bool) -> TransitClient {
+        TransitClient::builder()
+            .base_url(server.url())
+            .retry(RetryConfig {
+                retry_empty_suggestions,
+                ..Default::default()
+            })
+            .build()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_suggest_retry_on_empty() {
+        let server = empty_then_found().await;
+        let result = client(&server, true).suggest_places("新宿").await.unwrap();
+        assert_eq!(result.results.len(), 1);
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(
+            requests
+                .iter()
+                .all(|r| r.target.starts_with("/api/suggest?value="))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_suggest_no_retry_by_default() {
+        let server = empty_then_found().await;
+        let result = client(&server, false).suggest_places("新宿").await.unwrap();
+        assert!(result.results.is_empty());
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_suggest_no_retry_on_short_query() {
+        let server = empty_then_found().await;
+        let result = client(&server, true).suggest_places(" 新 ").await.unwrap();
+        assert!(result.results.is_empty());
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    async fn blocked_then_found() -> TestServer {
+        let calls = AtomicUsize::new(0);
+        TestServer::start(move |_| {
+            if calls.fetch_add(1, Ordering::SeqCst) == 0 {
+                Reply::new(403, "text/html", "blocked")
+            } else {
+                Reply::json(SHINJUKU)
+            }
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_body_encoding() {
+        let server = TestServer::start(|req| {
+            if req.target.contains("sjis") {
+                Reply::new(
+                    200,
+                    "application/json; charset=Shift_JIS",
+                    b"\x90\x56\x8F\x68".to_vec(),
+                )
+            } else {
+                let mut body = b"\xEF\xBB\xBF".to_vec();
+                body.extend_from_slice(SHINJUKU.as_bytes());
+                Reply::new(200, "application/json", body)
+            }
+        })
+        .await;
+        let client = client(&server, false);
+
+        let result = client.suggest_places("新宿").await.unwrap();
+        assert_eq!(result.results[0].suggest, "新宿");
+
+        let err = client.suggest_places("sjis").await.unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<TransitError>(),
+            Some(&TransitError::Encoding {
+                charset: Some("Shift_JIS".into())
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rotates_to_fallback_when_blocked() {
+        let server = blocked_then_found().await;
+        let mut client = client(&server, false);
+        // stand-in for an emulation fallback profile
+        client.http = vec![client.http[0].clone(), client.http[0].clone()].into();
+
+        let result = client.suggest_places("新宿").await.unwrap();
+        assert_eq!(result.results.len(), 1);
+        assert_eq!(server.requests().len(), 2);
+        assert_eq!(client.active.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn test_blocked_without_fallback() {
+        let server = blocked_then_found().await;
+        let err = client(&server, false)
+            .suggest_places("新宿")
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<TransitError>(),
+            Some(&TransitError::Blocked { status: 403 })
+        );
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_maintenance_is_not_retried() {
+        let server = TestServer::start(|_| {
+            Reply::new(
+                503,
+                "text/html; charset=utf-8",
+                include_str!("../tests/fixtures/maintenance.html"),
+            )
+        })
+        .await;
+        let client = TransitClient::builder()
+            .base_url(server.url())
+            .retry(RetryConfig {
+                max_retries: 3,
+                ..Default::default()
+            })
+            .build()
+            .unwrap();
+
+        let err = client.suggest_places("新宿").await.unwrap_err();
+        let until = match err.downcast_ref::<TransitError>() {
+            Some(TransitError::Maintenance { until }) => *until,
+            _ => panic!("unexpected error: {err:?}"),
+        };
+        assert_eq!(
+            until.map(|t| t.to_rfc3339()).as_deref(),
+            Some("2025-12-18T06:00:00+09:00")
+        );
+
+        let err = client.transit(&search_args("渋谷")).await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<TransitError>(),
+            Some(TransitError::Maintenance { .. })
+        ));
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    async fn place_not_found_server() -> TestServer {
+        TestServer::start(|req| {
+            if req.target.starts_with("/api/suggest") {
+                Reply::json(
+                    r#"{"@query":"q","@totalResultsAvailable":4,"Result":[
+                        {"Suggest":"渋谷","Yomi":"しぶや","Lat":"35.6","Lon":"139.7","Address":"","Id":"st"},
+                        {"Suggest":"渋谷駅西口","Yomi":"","Lat":"35.6","Lon":"139.7","Address":"","Id":"bu"},
+                        {"Suggest":"渋谷ヒカリエ","Yomi":"","Lat":"35.6","Lon":"139.7","Address":"","Id":"la"},
+                        {"Suggest":"新宿御苑","Yomi":"","Lat":"35.6","Lon":"139.7","Address":"","Id":"la"}]}"#,
+                )
+            } else {
+                Reply::json(include_str!("../tests/fixtures/place_not_found.json"))
+            }
+        })
+        .await
+    }
+
+    fn search_args(to: &str) -> TransitArgs {
+        TransitArgs {
+            from: "新宿".into(),
+            to: to.into(),
+            ..Default::default()
+        }
+    }
+
+    /// The `no=` of a search request.
+    fn requested_rank(target: &str) -> u32 {
+        target
+            .split(['?', '&'])
+            .find_map(|p| p.strip_prefix("no="))
+            .unwrap()
+            .parse()
+            .unwrap()
+    }
+
+    /// A result page listing `count` distinct routes as ranks 1 to
+    /// `count`, departing 09:32, 09:33, ...
+    fn ranked_page(count: u32) -> String {
+        let mut root: serde_json::Value =
+            serde_json::from_str(include_str!("../tests/fixtures/basic.json")).unwrap();
+        let features = &mut root["props"]["pageProps"]["naviSearchParam"]["featureInfoList"];
+        let route = features[0].clone();
+        *features = (0..count)
+            .map(|i| {
+                let mut route = route.clone();
+                let time = format!("09:{}", 32 + i);
+                route["summaryInfo"]["departureTime"] = time.clone().into();
+                route["edgeInfoList"][0]["timeInfo"][0]["time"] = time.into();
+                route
+            })
+            .collect();
+        root.to_string()
+    }
+
+    #[tokio::test]
+    async fn test_did_you_mean_enabled() {
+        let server = place_not_found_server().await;
+        let client = TransitClient::builder()
+            .base_url(server.url())
+            .did_you_mean(true)
+            .build()
+            .unwrap();
+
+        let err = client.transit(&search_args("渋やx")).await.unwrap_err();
+        match err.downcast_ref::<TransitError>() {
+            Some(TransitError::PlaceNotFound {
+                query,
+                did_you_mean,
+                ..
+            }) => {
+                assert_eq!(query, "渋やx");
+                assert_eq!(did_you_mean, &["渋谷", "渋谷駅西口", "渋谷ヒカリエ"]);
+            }
+            other => panic!("unexpected error: {other:?}"),
+        }
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_did_you_mean_no_route_compares_normalized_names() {
+        let server = TestServer::start(|req| {
+            if req.target.starts_with("/api/suggest") {
+                return Reply::json(SHINJUKU);
+            }
+            let mut root: serde_json::Value =
+                serde_json::from_str(include_str!("../tests/fixtures/basic.json")).unwrap();
+            let navi = &mut root["props"]["pageProps"]["naviSearchParam"];
+            navi["featureInfoList"] = serde_json::json!([]);
+            navi["displayInfo"]["fromName"] = "新宿三丁目".into();
+            navi["displayInfo"]["toName"] = "渋谷 マークシティ".into();
+            Reply::json(&root.to_string())
+        })
+        .await;
+        let client = TransitClient::builder()
+            .base_url(server.url())
+            .did_you_mean(true)
+            .build()
+            .unwrap();
+
+        // "渋谷\u{3000} マークシティ" is sent as the resolved name, so only
+        // `from` is looked up
+        let args = TransitArgs {
+            from: "新宿3丁目".into(),
+            to: "渋谷\u{3000} マークシティ".into(),
+            ..Default::default()
+        };
+        let err = client.transit(&args).await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<TransitError>(),
+            Some(TransitError::NoRouteFound { did_you_mean, .. }) if did_you_mean == &["新宿"]
+        ));
+        let suggested: Vec<_> = server
+            .requests()
+            .iter()
+            .filter(|r| r.target.starts_with("/api/suggest"))
+            .map(|r| r.target.clone())
+            .collect();
+        assert_eq!(suggested.len(), 1, "{suggested:?}");
+    }
+
+    #[tokio::test]
+    async fn test_did_you_mean_disabled() {
+        let server = place_not_found_server().await;
+        let client = TransitClient::builder()
+            .base_url(server.url())
+            .build()
+            .unwrap();
+
+        let err = client.transit(&search_args("渋やx")).await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<TransitError>(),
+            Some(TransitError::PlaceNotFound { did_you_mean, .. }) if did_you_mean.is_empty()
+        ));
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_transit_invalid_search() {
+        let server = TestServer::start(|_| {
+            Reply::new(
+                200,
+                "text/html; charset=utf-8",
+                include_str!("../tests/fixtures/top_page.html"),
+            )
+        })
+        .await;
+        let client = TransitClient::builder()
+            .base_url(server.url())
+            .build()
+            .unwrap();
+
+        let args = TransitArgs {
+            rank: 9,
+            ..search_args("渋谷")
+        };
+        let err = client.transit(&args).await.unwrap_err();
+        let Some(TransitError::InvalidSearch { reason }) = err.downcast_ref::<TransitError>()
+        else {
+            panic!("unexpected error: {err:?}");
+        };
+        assert!(reason.contains("`rank` 9"), "{reason}");
+        assert!(!reason.contains("`date`"), "{reason}");
+
+        let args = TransitArgs {
+            date: jst_now() + chrono::TimeDelta::days(200),
+            ..search_args("渋谷")
+        };
+        let err = client.transit(&args).await.unwrap_err();
+        let Some(TransitError::InvalidSearch { reason }) = err.downcast_ref::<TransitError>()
+        else {
+            panic!("unexpected error: {err:?}");
+        };
+        assert!(reason.contains("beyond the 92-day"), "{reason}");
+
+        let same = client.transit(&search_args("新宿")).await.unwrap_err();
+        assert!(matches!(
+            same.downcast_ref::<TransitError>(),
+            Some(TransitError::InvalidArgs(_))
+        ));
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_transit_rejects_empty_places() {
+        let server = TestServer::start(|_| Reply::json("{}")).await;
+        let client = TransitClient::builder()
+            .base_url(server.url())
+            .build()
+            .unwrap();
+
+        assert!(client.transit(&search_args(" ")).await.is_err());
+        let err = client.transit(&TransitArgs::default()).await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<TransitError>(),
+            Some(TransitError::InvalidArgs(errors)) if errors.len() == 2
+        ));
+        assert!(server.requests().is_empty());
+    }
+
+    #[cfg(feature = "http-reqwest")]
+    #[tokio::test]
+    async fn test_resolve_and_local_address() {
+        let server = TestServer::start(|_| Reply::json(SHINJUKU)).await;
+        let port = server.url().rsplit(':').next().unwrap();
+        let client = TransitClient::builder()
+            .base_url(format!("http://transit.invalid:{port}"))
+            .resolve("transit.invalid", "127.0.0.1:0".parse().unwrap())
+            .local_address("127.0.0.1".parse().unwrap())
+            .build()
+            .unwrap();
+
+        client.suggest_places("新宿").await.unwrap();
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_verify_parser() {
+        let good =
+            TestServer::start(|_| Reply::json(include_str!("../tests/fixtures/basic.json"))).await;
+        let client = TransitClient::builder()
+            .base_url(good.url())
+            .build()
+            .unwrap();
+        let report = client.verify_parser().await.unwrap();
+        assert!(report.is_ok(), "{report:?}");
+        assert_eq!(report.build_id.as_deref(), Some("fixture-build"));
+        assert_eq!(report.route_count, 1);
+        assert!(good.requests()[0].target.starts_with("/search/print?"));
+
+        let degraded =
+            TestServer::start(|_| Reply::json(include_str!("../tests/fixtures/degraded.json")))
+                .await;
+        let client = TransitClient::builder()
+            .base_url(degraded.url())
+            .build()
+            .unwrap();
+        let report = client.verify_parser().await.unwrap();
+        assert!(!report.is_ok());
+        assert_eq!(
+            report.missing_fields(),
+            vec![
+                "routes[].summary.departureTime",
+                "routes[].summary.arrivalTime",
+                "routes[].summary.totalPriceYen",
+                "routes[].segments[].line",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_explicit_proxy() {
+        let proxy = TestServer::start(|_| Reply::json(SHINJUKU)).await;
+        let client = TransitClient::builder()
+            .base_url("http://transit.invalid")
+            .proxy(proxy.url())
+            .build()
+            .unwrap();
+
+        client.suggest_places("新宿").await.unwrap();
+        let requests = proxy.requests();
+        assert_eq!(requests.len(), 1);
+        assert!(
+            requests[0]
+                .target
+                .starts_with("http://transit.invalid/api/suggest?")
+        );
+    }
+
+    #[test]
+    fn test_builder_proxy_settings() {
+        let builder = TransitClient::builder();
+        assert!(matches!(builder.http.proxy, ProxySetting::System));
+        let builder = builder.proxy("http://127.0.0.1:8080");
+        assert!(
+            matches!(builder.http.proxy, ProxySetting::Url(ref u) if u == "http://127.0.0.1:8080")
+        );
+        let builder = builder.no_proxy();
+        assert!(matches!(builder.http.proxy, ProxySetting::Disabled));
+        builder.build().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_pool_recovers_from_server_closing_idle_sockets() {
+        // The server drops the pooled connection when the second request
+        // arrives on it, as if it had just timed the connection out.
+        let served = Arc::new(AtomicUsize::new(0));
+        let server = TestServer::start_keep_alive(Duration::from_secs(5), {
+            let served = served.clone();
+            move |_| match served.fetch_add(1, Ordering::SeqCst) {
+                1 => Reply::hang_up(),
+                _ => Reply::json(SHINJUKU),
+            }
+        })
+        .await;
+        let client = TransitClient::builder()
+            .base_url(server.url())
+            .pool_max_idle_per_host(2)
+            .tcp_keepalive(Duration::from_secs(15))
+            .retry(RetryConfig {
+                max_retries: 1,
+                backoff: Duration::ZERO,
+                ..Default::default()
+            })
+            .build()
+            .unwrap();
+
+        client.suggest_places("新宿").await.unwrap();
+        client.suggest_places("新宿").await.unwrap();
+        // the dropped request was sent again on a new connection
+        assert_eq!(server.requests().len(), 3);
+        assert_eq!(server.connections(), 2);
+
+        // the new connection is pooled in turn
+        client.suggest_places("新宿").await.unwrap();
+        assert_eq!(server.requests().len(), 4);
+        assert_eq!(server.connections(), 2);
+    }
+
+    /// The basic fixture with every time shifted by `minutes`.
+    fn shifted_basic(minutes: i32) -> String {
+        let mut root: serde_json::Value =
+            serde_json::from_str(include_str!("../tests/fixtures/basic.json")).unwrap();
+        let route = &mut root["props"]["pageProps"]["naviSearchParam"]["featureInfoList"][0];
+        let shift = |v: &mut serde_json::Value| {
+            let (h, m) = v.as_str().unwrap().split_once(':').unwrap();
+            let total = h.parse::<i32>().unwrap() * 60 + m.parse::<i32>().unwrap() + minutes;
+            *v = format!("{:02}:{:02}", total / 60, total % 60).into();
+        };
+        shift(&mut route["summaryInfo"]["departureTime"]);
+        shift(&mut route["summaryInfo"]["arrivalTime"]);
+        for edge in route["edgeInfoList"].as_array_mut().unwrap() {
+            for t in edge["timeInfo"].as_array_mut().unwrap() {
+                shift(&mut t["time"]);
+            }
+        }
+        // the page echoes a search time shifted along with the route
+        let query = &mut root["props"]["pageProps"]["pageQuery"];
+        let searched = 9 * 60 + 30 + minutes;
+        query["hh"] = (searched / 60).to_string().into();
+        query["m1"] = (searched % 60 / 10).to_string().into();
+        query["m2"] = (searched % 10).to_string().into();
+        root.to_string()
+    }
+
+    #[tokio::test]
+    async fn test_transit_by_all_criteria() {
+        let pages = [shifted_basic(0), shifted_basic(10), shifted_basic(0)];
+        let server = TestServer::start(move |req| {
+            match ["s=0", "s=1", "s=2"]
+                .iter()
+                .position(|s| req.target.contains(&format!("&{s}&")))
+            {
+                Some(i) => Reply::json(&pages[i]),
+                None => Reply::new(500, "text/plain", "unexpected"),
+            }
+        })
+        .await;
+        let client = client(&server, false);
+
+        let result = client
+            .transit_by_all_criteria(&search_args("渋谷"))
+            .await
+            .unwrap();
+        assert!(result.failures.is_empty());
+        assert_eq!(result.routes.len(), 2);
+        assert_eq!(
+            result.routes[0].criteria,
+            [
+                TransitCriteria::EarliestArrival,
+                TransitCriteria::FewestTransfers
+            ]
+        );
+        assert_eq!(result.routes[1].criteria, [TransitCriteria::LowestCost]);
+        let cheapest = result.best_for(TransitCriteria::LowestCost).unwrap();
+        assert_eq!(
+            cheapest.summary.departure_time.unwrap().to_rfc3339(),
+            "2025-12-18T09:42:00+09:00"
+        );
+        assert_eq!(server.requests().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_transit_by_all_criteria_partial_failure() {
+        let page = shifted_basic(0);
+        let server = TestServer::start(move |req| {
+            if req.target.contains("&s=1&") {
+                Reply::new(500, "text/html", "<html>error</html>")
+            } else {
+                Reply::json(&page)
+            }
+        })
+        .await;
+        let client = client(&server, false);
+
+        let result = client
+            .transit_by_all_criteria(&search_args("渋谷"))
+            .await
+            .unwrap();
+        assert_eq!(result.routes.len(), 1);
+        assert_eq!(result.routes[0].criteria.len(), 2);
+        assert_eq!(result.failures.len(), 1);
+        assert_eq!(result.failures[0].criteria, TransitCriteria::LowestCost);
+        assert!(result.best_for(TransitCriteria::LowestCost).is_none());
+
+        let failing = TestServer::start(|_| Reply::new(500, "text/html", "")).await;
+        let client = TransitClient::builder()
+            .base_url(failing.url())
+            .build()
+            .unwrap();
+        assert!(
+            client
+                .transit_by_all_criteria(&search_args("渋谷"))
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_query_encoding_on_the_wire() {
+        let server =
+            TestServer::start(|_| Reply::json(include_str!("../tests/fixtures/basic.json"))).await;
+        let client = client(&server, false);
+
+        let args = TransitArgs {
+            from: "名古屋港（ガーデンふ頭）".into(),
+            to: "三井アウトレットパーク　ジャズドリーム長島".into(),
+            ..Default::default()
+        };
+        client.transit(&args).await.unwrap();
+        let target = &server.requests()[0].target;
+        assert!(
+            target.starts_with(
+                "/search/print?from=%E5%90%8D%E5%8F%A4%E5%B1%8B%E6%B8%AF%EF%BC%88%E3%82%AC\
+                 %E3%83%BC%E3%83%87%E3%83%B3%E3%81%B5%E9%A0%AD%EF%BC%89&to=%E4%B8%89%E4%BA\
+                 %95%E3%82%A2%E3%82%A6%E3%83%88%E3%83%AC%E3%83%83%E3%83%88%E3%83%91%E3%83\
+                 %BC%E3%82%AF+%E3%82%B8%E3%83%A3%E3%82%BA%E3%83%89%E3%83%AA%E3%83%BC%E3%83\
+                 %A0%E9%95%B7%E5%B3%B6&y="
+            ),
+            "{target}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_request_decorator() {
+        let server = TestServer::start(|req| {
+            if req.target.starts_with("/api/suggest") {
+                Reply::json(SHINJUKU)
+            } else {
+                Reply::json(include_str!("../tests/fixtures/basic.json"))
+            }
+        })
+        .await;
+        let decorated = TransitClient::builder()
+            .base_url(server.url())
+            .request_decorator(|headers| {
+                headers.insert("x-request-id", "req-42".parse().unwrap());
+            })
+            .build()
+            .unwrap();
+
+        decorated.suggest_places("新宿").await.unwrap();
+        decorated.transit(&search_args("渋谷")).await.unwrap();
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(
+            requests
+                .iter()
+                .all(|r| r.header("x-request-id") == Some("req-42"))
+        );
+
+        let plain = TestServer::start(|_| Reply::json(SHINJUKU)).await;
+        client(&plain, false).suggest_places("新宿").await.unwrap();
+        assert_eq!(plain.requests()[0].header("x-request-id"), None);
+    }
+
+    #[test]
+    fn test_default_client_per_runtime() {
+        let first = default_client().unwrap();
+        assert!(Arc::ptr_eq(&first.http, &default_client().unwrap().http));
+        assert!(init_default_client(TransitClient::builder()).is_err());
+
+        let runtime = || {
+            tokio::runtime::Builder::new_current_thread()
+                .build()
+                .unwrap()
+        };
+        let (a, b) = (runtime(), runtime());
+        let on_a = a.block_on(async { default_client().unwrap() });
+        assert!(Arc::ptr_eq(
+            &on_a.http,
+            &a.block_on(async { default_client().unwrap() }).http
+        ));
+        let on_b = b.block_on(async { default_client().unwrap() });
+        assert!(!Arc::ptr_eq(&on_a.http, &on_b.http));
+        assert!(!Arc::ptr_eq(&first.http, &on_a.http));
+
+        // the client of a runtime that shut down is evicted
+        let id = Some(a.handle().id());
+        drop(a);
+        b.block_on(async { default_client().unwrap() });
+        assert!(!DEFAULT_CLIENTS.lock().unwrap().contains_key(&id));
+        drop(on_a);
+    }
+
+    #[tokio::test]
+    async fn test_warm_up_reuses_connection() {
+        let server =
+            TestServer::start_keep_alive(Duration::from_secs(5), |_| Reply::json(SHINJUKU)).await;
+        let client = client(&server, false);
+
+        client.warm_up().await;
+        client.suggest_places("新宿").await.unwrap();
+        let targets: Vec<_> = server.requests().into_iter().map(|r| r.target).collect();
+        assert_eq!(targets[0], "/");
+        assert_eq!(server.connections(), 1);
+
+        let unreachable = TransitClient::builder()
+            .base_url("http://127.0.0.1:1")
+            .build()
+            .unwrap();
+        unreachable.warm_up().await;
+    }
+
+    #[tokio::test]
+    async fn test_transit_reports_arg_warnings() {
+        let server =
+            TestServer::start(|_| Reply::json(include_str!("../tests/fixtures/basic.json"))).await;
+        let args = TransitArgs {
+            via: vec!["新宿".into(), "代々木\u{3000}".into()],
+            ..search_args("渋谷")
+        };
+        let dto = client(&server, false).transit(&args).await.unwrap();
+        assert_eq!(
+            dto.warnings,
+            ["`via[0]` (新宿) repeats `from`, `to` or an earlier via; ignored"]
+        );
+        let target = &server.requests()[0].target;
+        assert_eq!(target.matches("via=").count(), 1, "{target}");
+    }
+
+    #[test]
+    fn test_transit_from_body_matches_transit() {
+        let client = TransitClient::builder()
+            .base_url("http://gateway.test/transit/")
+            .max_routes(1)
+            .build()
+            .unwrap();
+        let mut args = TransitArgs {
+            via: vec!["新宿".into()],
+            ..search_args("渋谷")
+        };
+        args.options.passenger = Some(PassengerType::Child);
+
+        let url = client.transit_request_url(&args);
+        assert!(
+            url.starts_with("http://gateway.test/transit/search/print?from="),
+            "{url}"
+        );
+
+        let dto = client
+            .transit_from_body(&args, include_str!("../tests/fixtures/co2.json"))
+            .unwrap();
+        assert_eq!(dto.routes.len(), 1);
+        assert_eq!(
+            dto.routes[0].summary.fare_passenger_type,
+            PassengerType::Child
+        );
+        assert_eq!(
+            dto.warnings,
+            [
+                "`via[0]` (新宿) repeats `from`, `to` or an earlier via; ignored",
+                "featureInfoList truncated from 2 to 1 routes",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_operator_filters_log_extra_requests() {
+        let server =
+            TestServer::start(|_| Reply::json(include_str!("../tests/fixtures/basic.json"))).await;
+        let mut args = TransitArgs {
+            rank: 3,
+            ..search_args("渋谷")
+        };
+        args.options.avoid_operators = vec!["京王".into()];
+
+        let captured = Captured::default();
+        let writer = captured.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::DEBUG)
+            .with_writer(move || writer.clone())
+            .finish();
+        let guard = tracing::subscriber::set_default(subscriber);
+        client(&server, false).transit(&args).await.unwrap();
+        drop(guard);
+
+        assert_eq!(server.requests().len(), AVOID_OPERATORS_RANKS as usize);
+        let logs = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+        assert!(logs.contains("rank is ignored"), "{logs}");
+        assert!(logs.contains("rank=3"), "{logs}");
+    }
+
+    #[tokio::test]
+    async fn test_transit_reports_date_warnings() {
+        let server =
+            TestServer::start(|_| Reply::json(include_str!("../tests/fixtures/basic.json"))).await;
+        let now = jst_now();
+        let client = |horizon_days| {
+            TransitClient::builder()
+                .base_url(server.url())
+                .date_horizon_days(horizon_days)
+                .clock(MockClock::new(now))
+                .build()
+                .unwrap()
+        };
+        let args = |days| TransitArgs {
+            date: now + chrono::TimeDelta::days(days),
+            ..search_args("渋谷")
+        };
+
+        let dto = client(DEFAULT_DATE_HORIZON_DAYS)
+            .transit(&args(30))
+            .await
+            .unwrap();
+        assert!(dto.warnings.is_empty(), "{:?}", dto.warnings);
+        let dto = client(7).transit(&args(30)).await.unwrap();
+        assert_eq!(
+            dto.warnings,
+            [
+                "`date` is 30 days ahead, beyond the 7-day timetable horizon; results may use the current timetable"
+            ]
+        );
+        let dto = client(7).transit(&args(-3)).await.unwrap();
+        assert_eq!(
+            dto.warnings,
+            ["`date` is 3 days in the past; results may use the current timetable"]
+        );
+
+        let absurd = TransitArgs {
+            date: now.with_year(1999).unwrap(),
+            ..search_args("渋谷")
+        };
+        let err = client(7).transit(&absurd).await.unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<TransitError>(),
+            Some(&TransitError::InvalidArgs(vec![ArgError::DateOutOfRange {
+                year: 1999
+            }]))
+        );
+        assert_eq!(server.requests().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_transit_with_diagnostics() {
+        let server = TestServer::start(|_| {
+            Reply::json(include_str!("../tests/fixtures/unknown_lines.json"))
+        })
+        .await;
+        let dto = client(&server, false)
+            .transit_with_diagnostics(&search_args("渋谷"))
+            .await
+            .unwrap();
+        assert_eq!(dto.warnings.len(), 1, "{:?}", dto.warnings);
+        assert!(dto.warnings[0].starts_with("60%"));
+
+        let server =
+            TestServer::start(|_| Reply::json(include_str!("../tests/fixtures/basic.json"))).await;
+        let dto = client(&server, false)
+            .transit_with_diagnostics(&search_args("渋谷"))
+            .await
+            .unwrap();
+        assert!(dto.warnings.is_empty());
+
+        let server = TestServer::start(|_| {
+            Reply::json(include_str!("../tests/fixtures/duration_mismatch.json"))
+        })
+        .await;
+        let dto = client(&server, false)
+            .transit_with_diagnostics(&search_args("渋谷"))
+            .await
+            .unwrap();
+        assert_eq!(
+            dto.warnings,
+            ["route 1: times span 13 min but duration is 73 min"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_transit_rank_range() {
+        let server = TestServer::start(|req| {
+            let rank = requested_rank(&req.target);
+            if rank <= 3 {
+                // a page that ignores `no=` for rank 3
+                Reply::json(&ranked_page(rank.min(2)))
+            } else {
+                Reply::new(
+                    200,
+                    "text/html; charset=utf-8",
+                    include_str!("../tests/fixtures/top_page.html"),
+                )
+            }
+        })
+        .await;
+
+        let routes = client(&server, false)
+            .transit_rank_range(&search_args("渋谷"), 2..=6)
+            .await
+            .unwrap();
+        assert_eq!(routes.keys().copied().collect::<Vec<_>>(), [2]);
+        assert_eq!(routes[&2].rank, 2);
+        assert_eq!(
+            routes[&2]
+                .summary
+                .departure_time
+                .unwrap()
+                .format("%H:%M")
+                .to_string(),
+            "09:33"
+        );
+        assert_eq!(server.requests().len(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_transit_routes_streaming() {
+        let server =
+            TestServer::start(|req| Reply::json(&ranked_page(requested_rank(&req.target)))).await;
+
+        let mut routes = Vec::new();
+        let delivered = client(&server, false)
+            .transit_routes_streaming(&search_args("渋谷"), 6, |route| routes.push(route))
+            .await
+            .unwrap();
+        assert_eq!(delivered, 6);
+        let seen: Vec<_> = routes
+            .iter()
+            .map(|r| {
+                let departure = r.summary.departure_time.unwrap();
+                (r.rank, departure.format("%H:%M").to_string())
+            })
+            .collect();
+        assert_eq!(
+            seen,
+            (1..=6)
+                .map(|rank| (rank, format!("09:{}", 31 + rank)))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(server.requests().len(), 6);
+    }
+
+    #[tokio::test]
+    async fn test_compare_seat_fares() {
+        let page = |yen: u32, train: &str| {
+            let mut root: serde_json::Value =
+                serde_json::from_str(include_str!("../tests/fixtures/shinkansen.json")).unwrap();
+            let feature = &mut root["props"]["pageProps"]["naviSearchParam"]["featureInfoList"][0];
+            feature["summaryInfo"]["totalPrice"] = format!("{yen}円").into();
+            feature["edgeInfoList"][0]["railNameExcludingDestination"] =
+                format!("ＪＲ新幹線{train}").into();
+            root.to_string()
+        };
+        let pages = [
+            page(23390, "のぞみ２２５号"),
+            page(23920, "のぞみ２２５号"),
+            page(31480, "のぞみ２２７号"),
+        ];
+        let server = TestServer::start(move |req| {
+            let seat = ["expkind=1", "expkind=2", "expkind=3"]
+                .iter()
+                .position(|p| req.target.contains(p))
+                .unwrap();
+            Reply::json(&pages[seat])
+        })
+        .await;
+
+        let comparison = client(&server, false)
+            .compare_seat_fares(&search_args("博多"))
+            .await
+            .unwrap();
+        let seats: Vec<_> = comparison.fares.iter().map(|f| f.seat).collect();
+        assert_eq!(
+            seats,
+            [
+                SeatPreference::NonReserved,
+                SeatPreference::Reserved,
+                SeatPreference::GreenCar
+            ]
+        );
+        let reserved = comparison.get(SeatPreference::Reserved).unwrap();
+        assert_eq!(reserved.total_price_yen, Some(23920));
+        assert_eq!(reserved.delta_yen, Some(530));
+        let green = comparison.get(SeatPreference::GreenCar).unwrap();
+        assert!(!green.same_route);
+        assert_eq!(green.delta_yen, None);
+
+        let local =
+            TestServer::start(|_| Reply::json(include_str!("../tests/fixtures/basic.json"))).await;
+        let comparison = client(&local, false)
+            .compare_seat_fares(&search_args("渋谷"))
+            .await
+            .unwrap();
+        assert_eq!(comparison.fares.len(), 1);
+        assert_eq!(local.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_clones_share_rate_limit() {
+        let server = TestServer::start(|_| Reply::json(SHINJUKU)).await;
+        let builder = TransitClient::builder()
+            .base_url(server.url())
+            .rate_limit(1, Duration::from_secs(3600));
+        let first = builder.clone().build().unwrap();
+        let second = first.clone();
+
+        first.suggest_places("新宿").await.unwrap();
+        let waited =
+            tokio::time::timeout(Duration::from_millis(100), second.suggest_places("新宿")).await;
+        assert!(waited.is_err(), "clone did not share the limiter");
+
+        let independent = builder.build().unwrap();
+        independent.suggest_places("新宿").await.unwrap();
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_transit_with_headers() {
+        let server = TestServer::start(|_| {
+            let mut reply = Reply::json(include_str!("../tests/fixtures/basic.json"));
+            reply.headers.extend([
+                ("Cache-Control".into(), "max-age=60".into()),
+                ("Age".into(), "42".into()),
+                ("Via".into(), "1.1 edge-a".into()),
+                ("Via".into(), "1.1 edge-b".into()),
+                ("Set-Cookie".into(), "session=secret".into()),
+            ]);
+            reply
+        })
+        .await;
+
+        let (dto, headers) = client(&server, false)
+            .transit_with_headers(&search_args("渋谷"))
+            .await
+            .unwrap();
+        assert_eq!(dto.routes.len(), 1);
+        assert_eq!(headers["cache-control"], "max-age=60");
+        assert_eq!(headers["age"], "42");
+        let via: Vec<_> = headers.get_all("via").iter().collect();
+        assert_eq!(via, ["1.1 edge-a", "1.1 edge-b"]);
+        assert!(headers.get("set-cookie").is_none());
+        assert!(
+            headers
+                .keys()
+                .all(|name| DEBUG_HEADERS.contains(&name.as_str()))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cookie_store() {
+        let server = TestServer::start(|_| {
+            let mut reply = Reply::json(SHINJUKU);
+            reply
+                .headers
+                .push(("Set-Cookie".into(), "B=abc; Path=/".into()));
+            reply
+        })
+        .await;
+
+        for enabled in [false, true] {
+            let client = TransitClient::builder()
+                .base_url(server.url())
+                .cookie_store(enabled)
+                .build()
+                .unwrap();
+            client.suggest_places("新宿").await.unwrap();
+            client.clone().suggest_places("新宿").await.unwrap();
+        }
+        let cookies: Vec<_> = server
+            .requests()
+            .iter()
+            .map(|req| req.header("cookie").map(str::to_string))
+            .collect();
+        assert_eq!(cookies, [None, None, None, Some("B=abc".into())]);
+    }
+
+    #[tokio::test]
+    async fn test_on_wait() {
+        type Waits = Arc<std::sync::Mutex<Vec<(WaitReason, Duration)>>>;
+        let recorder = || {
+            let waits = Waits::default();
+            let hook = {
+                let waits = waits.clone();
+                move |reason, wait| waits.lock().unwrap().push((reason, wait))
+            };
+            (waits, hook)
+        };
+
+        let server = TestServer::start(|_| Reply::json(SHINJUKU)).await;
+        let (waits, hook) = recorder();
+        let clock = MockClock::new(jst_now());
+        let client = TransitClient::builder()
+            .base_url(server.url())
+            .rate_limit(1, Duration::from_secs(60))
+            .on_wait(hook)
+            .clock(clock.clone())
+            .build()
+            .unwrap();
+        client.suggest_places("新宿").await.unwrap();
+        assert!(waits.lock().unwrap().is_empty());
+        client.suggest_places("新宿").await.unwrap();
+        assert_eq!(
+            *waits.lock().unwrap(),
+            [(WaitReason::RateLimit, Duration::from_secs(60))]
+        );
+        assert_eq!(clock.sleeps(), [Duration::from_secs(60)]);
+
+        // nothing listens on a port just released
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let (waits, hook) = recorder();
+        let clock = MockClock::new(jst_now());
+        let client = TransitClient::builder()
+            .base_url(format!("http://127.0.0.1:{port}"))
+            .retry(RetryConfig {
+                max_retries: 2,
+                backoff: Duration::from_secs(30),
+                ..Default::default()
+            })
+            .on_wait(hook)
+            .clock(clock.clone())
+            .build()
+            .unwrap();
+        let started = std::time::Instant::now();
+        client.suggest_places("新宿").await.unwrap_err();
+        assert!(started.elapsed() < Duration::from_secs(30));
+        assert_eq!(
+            *waits.lock().unwrap(),
+            [
+                (
+                    WaitReason::RetryBackoff { attempt: 1 },
+                    Duration::from_secs(30)
+                ),
+                (
+                    WaitReason::RetryBackoff { attempt: 2 },
+                    Duration::from_secs(60)
+                ),
+            ]
+        );
+        assert_eq!(
+            clock.sleeps(),
+            [Duration::from_secs(30), Duration::from_secs(60)]
+        );
+    }
+
+    #[test]
+    fn test_debug_is_concise() {
+        let client = TransitClient::builder()
+            .request_decorator(|headers| {
+                headers.insert("x-secret", "token".parse().unwrap());
+            })
+            .rate_limit(10, Duration::from_secs(1))
+            .build()
+            .unwrap();
+        let debug = format!("{client:?}");
+        assert!(debug.len() < 300, "{debug}");
+        assert!(debug.contains("base_url"));
+        assert!(debug.contains("10/1s"));
+        assert!(!debug.contains("token"));
+    }
+
+    /// Serves basic.json departing at the first of 08:00 / 08:12 / 08:24 /
+    /// 09:32 that is not before the requested time.
+    async fn departures_server() -> TestServer {
+        let pages: Vec<(i32, String)> = [-92, -80, -68, 0]
+            .into_iter()
+            .map(|shift| (9 * 60 + 32 + shift, shifted_basic(shift)))
+            .collect();
+        TestServer::start(move |req| {
+            let param = |name: &str| -> i32 {
+                req.target
+                    .split(['?', '&'])
+                    .find_map(|p| p.strip_prefix(&format!("{name}=")))
+                    .unwrap()
+                    .parse()
+                    .unwrap()
+            };
+            let requested = param("hh") * 60 + param("m1") * 10 + param("m2");
+            let (_, page) = pages
+                .iter()
+                .find(|(departure, _)| *departure >= requested)
+                .unwrap_or(pages.last().unwrap());
+            Reply::json(page)
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_transit_window() {
+        let server = departures_server().await;
+        let args = TransitArgs {
+            date: "2025-12-18T08:00:00+09:00".parse().unwrap(),
+            ..search_args("渋谷")
+        };
+        let routes = client(&server, false)
+            .transit_window(&args, Duration::from_secs(3600), None)
+            .await
+            .unwrap();
+        let departures: Vec<_> = routes
+            .iter()
+            .map(|r| {
+                r.summary
+                    .departure_time
+                    .unwrap()
+                    .format("%H:%M")
+                    .to_string()
+            })
+            .collect();
+        assert_eq!(departures, ["08:00", "08:12", "08:24"]);
+        assert_eq!(routes.iter().map(|r| r.rank).collect::<Vec<_>>(), [1, 2, 3]);
+        // 08:00, 08:01, 08:13, 08:25 (answered with 09:32)
+        assert_eq!(server.requests().len(), 4);
+
+        let server = departures_server().await;
+        let routes = client(&server, false)
+            .transit_window(
+                &args,
+                Duration::from_secs(3600),
+                Some(Duration::from_secs(60)),
+            )
+            .await
+            .unwrap();
+        assert_eq!(routes.len(), 3);
+        assert_eq!(
+            server.requests().len(),
+            TRANSIT_WINDOW_MAX_REQUESTS as usize
+        );
+    }
+
+    /// Collects formatted tracing output.
+    #[derive(Clone, Default)]
+    struct Captured(Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_excerpt_redacts_escaped_queries() {
+        let args = TransitArgs {
+            from: "A&B 芝公園".into(),
+            ..search_args("渋谷\u{1F683}")
+        };
+        let terms = redaction_terms(&args);
+        let body = concat!(
+            "<title>A&amp;B 芝公園</title>",
+            "<p>A&amp;B &#33437;&#20844;&#22290; / &#x6e0b;&#x8c37;&#x1f683;</p>",
+            r#"{"from":"A&B \u829d\u516c\u5712","to":"\u6E0B\u8C37\uD83D\uDE83"}"#,
+        );
+        let excerpt = body_excerpt(body, &terms);
+        assert_eq!(
+            excerpt,
+            concat!(
+                "<title>⟨redacted⟩</title>",
+                "<p>⟨redacted⟩ / ⟨redacted⟩</p>",
+                r#"{"from":"⟨redacted⟩","to":"⟨redacted⟩"}"#,
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_excerpt_redacts_queries() {
+        let server = TestServer::start(|_| {
+            Reply::new(
+                200,
+                "text/html; charset=utf-8",
+                "<html><title>東京都港区芝公園4-2-8 → 渋谷</title><p>東京都港区芝公園4-2-8</p></html>",
+            )
+        })
+        .await;
+        let args = TransitArgs {
+            from: "東京都港区芝公園4-2-8".into(),
+            ..search_args("渋谷")
+        };
+
+        let captured = Captured::default();
+        let writer = captured.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::DEBUG)
+            .with_writer(move || writer.clone())
+            .finish();
+        let guard = tracing::subscriber::set_default(subscriber);
+        let err = client(&server, false).transit(&args).await.unwrap_err();
+        drop(guard);
+
+        let message = err.to_string();
+        assert!(
+            message.contains("<title>⟨redacted⟩ → ⟨redacted⟩</title>"),
+            "{message}"
+        );
+        assert!(!message.contains("芝公園"));
+        let logs = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+        assert!(logs.contains("⟨redacted⟩ → ⟨redacted⟩"), "{logs}");
+        assert!(!logs.contains("芝公園"));
+
+        let unredacted = TransitClient::builder()
+            .base_url(server.url())
+            .redact_queries(false)
+            .build()
+            .unwrap();
+        let err = unredacted.transit(&args).await.unwrap_err();
+        assert!(err.to_string().contains("芝公園"));
+    }
+
+    #[test]
+    fn test_body_excerpt() {
+        let terms = redaction_terms(&TransitArgs {
+            from: " 新宿 三丁目 ".into(),
+            to: "新宿".into(),
+            via: vec!["代々木".into()],
+            ..Default::default()
+        });
+        assert_eq!(body_excerpt("代々木", &terms), "⟨redacted⟩");
+        assert_eq!(
+            body_excerpt(
+                "新宿 三丁目 / 新宿 / %E6%96%B0%E5%AE%BF+%E4%B8%89%E4%B8%81%E7%9B%AE",
+                &terms
+            ),
+            "⟨redacted⟩ / ⟨redacted⟩ / ⟨redacted⟩"
+        );
+
+        let long = "あ".repeat(EXCERPT_CHARS + 10);
+        let excerpt = body_excerpt(&long, &[]);
+        assert_eq!(excerpt.chars().count(), EXCERPT_CHARS + 1);
+        assert!(excerpt.ends_with('…'));
+    }
+
+    #[tokio::test]
+    async fn test_pinned_certificates() {
+        let server = TestServer::start_tls(
+            include_bytes!("../tests/fixtures/tls/localhost.pem"),
+            include_bytes!("../tests/fixtures/tls/localhost.key"),
+            |_| Reply::json(SHINJUKU),
+        )
+        .await;
+        let pinned = |pem: &[u8]| {
+            TransitClient::builder()
+                .base_url(server.url())
+                .pin_certificates(vec![Certificate::from_pem(pem)])
+                .retry(RetryConfig {
+                    max_retries: 2,
+                    ..Default::default()
+                })
+                .build()
+                .unwrap()
+        };
+
+        pinned(include_bytes!("../tests/fixtures/tls/localhost.pem"))
+            .suggest_places("新宿")
+            .await
+            .unwrap();
+        assert_eq!(server.connections(), 1);
+
+        let err = pinned(include_bytes!("../tests/fixtures/tls/other.pem"))
+            .suggest_places("新宿")
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err.downcast_ref(), Some(TransitError::Tls { .. })),
+            "{err:?}"
+        );
+        // Verification failures are not retried.
+        assert_eq!(server.connections(), 2);
+
+        let err = TransitClient::builder()
+            .base_url(server.url())
+            .build()
+            .unwrap()
+            .suggest_places("新宿")
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err.downcast_ref(), Some(TransitError::Tls { .. })),
+            "{err:?}"
+        );
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    /// Serves `bodies[version(n)]` for the n-th request, tagged with the
+    /// version as its ETag, and answers 304 when the request already has
+    /// that tag.
+    async fn etag_server(
+        bodies: &'static [&'static str],
+        version: fn(usize) -> usize,
+    ) -> TestServer {
+        let served = Arc::new(AtomicUsize::new(0));
+        TestServer::start(move |req| {
+            let current = version(served.fetch_add(1, Ordering::SeqCst));
+            let etag = format!("\"v{current}\"");
+            if req.header("if-none-match") == Some(etag.as_str()) {
+                return Reply::new(304, "application/json", "");
+            }
+            let mut reply = Reply::json(bodies[current]);
+            reply.headers.push(("ETag".into(), etag));
+            reply
+        })
+        .await
+    }
+
+    fn caching_client(server: &TestServer) -> TransitClient {
+        TransitClient::builder()
+            .base_url(server.url())
+            .conditional_requests(16)
+            .build()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_conditional_request_serves_cached_body_on_304() {
+        let server = etag_server(&[SHINJUKU], |_| 0).await;
+        let client = caching_client(&server);
+        for _ in 0..2 {
+            let response = client.suggest_places("新宿").await.unwrap();
+            assert_eq!(response.results[0].suggest, "新宿");
+        }
+        let requests = server.requests();
+        assert_eq!(requests[0].header("if-none-match"), None);
+        assert_eq!(requests[1].header("if-none-match"), Some("\"v0\""));
+    }
+
+    #[tokio::test]
+    async fn test_conditional_request_changed_etag_replaces_entry() {
+        let server = etag_server(&[SHINJUKU, EMPTY], |n| n.min(1)).await;
+        let client = caching_client(&server);
+        assert_eq!(
+            client.suggest_places("新宿").await.unwrap().results.len(),
+            1
+        );
+        // v0 is stale; the server sends v1 in full
+        assert!(
+            client
+                .suggest_places("新宿")
+                .await
+                .unwrap()
+                .results
+                .is_empty()
+        );
+        // v1 is current; the cached v1 body is reused
+        assert!(
+            client
+                .suggest_places("新宿")
+                .await
+                .unwrap()
+                .results
+                .is_empty()
+        );
+        let tags: Vec<_> = server
+            .requests()
+            .iter()
+            .map(|r| r.header("if-none-match").map(str::to_string))
+            .collect();
+        assert_eq!(tags, [None, Some("\"v0\"".into()), Some("\"v1\"".into())]);
+        assert_eq!(client.cache.as_ref().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_conditional_requests_without_validators() {
+        let server = TestServer::start(|_| Reply::json(SHINJUKU)).await;
+        let client = caching_client(&server);
+        client.suggest_places("新宿").await.unwrap();
+        client.suggest_places("新宿").await.unwrap();
+        assert!(server.requests().iter().all(
+            |r| r.header("if-none-match").is_none() && r.header("if-modified-since").is_none()
+        ));
+        assert_eq!(client.cache.as_ref().unwrap().len(), 0);
+    }
+
+    #[cfg(feature = "dangerous-config")]
+    #[tokio::test]
+    async fn test_danger_accept_invalid_certs() {
+        let server = TestServer::start_tls(
+            include_bytes!("../tests/fixtures/tls/localhost.pem"),
+            include_bytes!("../tests/fixtures/tls/localhost.key"),
+            |_| Reply::json(SHINJUKU),
+        )
+        .await;
+        let client = |accept: bool| {
+            TransitClient::builder()
+                .base_url(server.url())
+                .danger_accept_invalid_certs(accept)
+                .build()
+                .unwrap()
+        };
+
+        let err = client(false).suggest_places("新宿").await.unwrap_err();
+        assert!(
+            matches!(err.downcast_ref(), Some(TransitError::Tls { .. })),
+            "{err:?}"
+        );
+        client(true).suggest_places("新宿").await.unwrap();
+    }
+
+    #[cfg(feature = "http-wreq")]
+    #[test]
+    fn test_builder_plumbs_emulation() {
+        let builder = TransitClient::builder()
+            .emulation(Emulation::Firefox136)
+            .emulation_fallbacks(vec![Emulation::Safari18, Emulation::Chrome136]);
+        assert_eq!(
+            format!("{:?}", builder.http.emulation),
+            format!("{:?}", Emulation::Firefox136)
+        );
+        assert_eq!(builder.emulation_fallbacks.len(), 2);
+        assert_eq!(builder.build().unwrap().http.len(), 3);
+    }
+}
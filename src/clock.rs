@@ -0,0 +1,127 @@
+//! The client's source of "now" and of sleeps.
+//!
+//! Rate limiting, retry backoff and the date checks of a
+//! [`TransitClient`](crate::TransitClient) go through a [`Clock`], so tests
+//! can swap in a [`MockClock`] whose sleeps return at once.
+
+use std::{fmt, sync::Arc, time::Duration};
+
+use chrono::{DateTime, FixedOffset};
+use futures_util::future::BoxFuture;
+use tokio::time::Instant;
+
+use crate::args::jst_now;
+
+/// Current time and sleeping, as used by the client.
+pub trait Clock: fmt::Debug + Send + Sync {
+    /// The current time in JST, for dates. May jump when the system clock
+    /// is adjusted; use [`now`](Self::now) to measure elapsed time.
+    fn now_jst(&self) -> DateTime<FixedOffset>;
+
+    /// A monotonic instant for measuring elapsed time.
+    fn now(&self) -> Instant;
+
+    /// Wait for `duration`.
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()>;
+}
+
+/// The system clock and tokio's timer (and monotonic clock). The default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_jst(&self) -> DateTime<FixedOffset> {
+        jst_now()
+    }
+
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        Box::pin(tokio::time::sleep(duration))
+    }
+}
+
+/// A clock that only moves when told to.
+///
+/// Sleeping advances it by the requested duration and returns immediately;
+/// the durations are recorded for [`sleeps`](Self::sleeps). Clones share
+/// the same time.
+#[cfg(any(test, feature = "test-util"))]
+#[derive(Debug, Clone)]
+pub struct MockClock {
+    state: Arc<std::sync::Mutex<MockState>>,
+}
+
+#[cfg(any(test, feature = "test-util"))]
+#[derive(Debug)]
+struct MockState {
+    now: DateTime<FixedOffset>,
+    instant: Instant,
+    sleeps: Vec<Duration>,
+}
+
+#[cfg(any(test, feature = "test-util"))]
+impl MockClock {
+    /// A clock stopped at `now`.
+    pub fn new(now: DateTime<FixedOffset>) -> Self {
+        Self {
+            state: Arc::new(std::sync::Mutex::new(MockState {
+                now,
+                instant: Instant::now(),
+                sleeps: Vec::new(),
+            })),
+        }
+    }
+
+    /// Move the clock forward by `duration`.
+    pub fn advance(&self, duration: Duration) {
+        let mut state = self.state.lock().unwrap();
+        state.now += chrono::Duration::from_std(duration).unwrap_or(chrono::Duration::MAX);
+        state.instant += duration;
+    }
+
+    /// Every sleep requested so far, in order.
+    pub fn sleeps(&self) -> Vec<Duration> {
+        self.state.lock().unwrap().sleeps.clone()
+    }
+}
+
+#[cfg(any(test, feature = "test-util"))]
+impl Clock for MockClock {
+    fn now_jst(&self) -> DateTime<FixedOffset> {
+        self.state.lock().unwrap().now
+    }
+
+    fn now(&self) -> Instant {
+        self.state.lock().unwrap().instant
+    }
+
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        self.advance(duration);
+        self.state.lock().unwrap().sleeps.push(duration);
+        Box::pin(std::future::ready(()))
+    }
+}
+
+/// The clock a client uses.
+pub(crate) type SharedClock = Arc<dyn Clock>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_mock_clock() {
+        let start = jst_now();
+        let clock = MockClock::new(start);
+        let instant = clock.now();
+        let shared = clock.clone();
+        shared.sleep(Duration::from_secs(90)).await;
+        clock.advance(Duration::from_secs(30));
+        assert_eq!(shared.now_jst() - start, chrono::Duration::minutes(2));
+        assert_eq!(shared.now() - instant, Duration::from_secs(120));
+        assert_eq!(clock.sleeps(), [Duration::from_secs(90)]);
+    }
+}
@@ -0,0 +1,44 @@
+//! Fullwidth numerals in page text.
+
+use std::borrow::Cow;
+
+/// Map fullwidth digits (`０`–`９`) and the punctuation around numbers
+/// (`，` `．` `：` `￥`) to ASCII, so numeric parsers only see `0`–`9`.
+///
+/// Borrows `s` when there is nothing to map.
+pub(crate) fn normalize_digits(s: &str) -> Cow<'_, str> {
+    if !s.chars().any(|c| map(c) != c) {
+        return Cow::Borrowed(s);
+    }
+    Cow::Owned(s.chars().map(map).collect())
+}
+
+fn map(c: char) -> char {
+    match c {
+        // fullwidth forms sit 0xFEE0 above ASCII
+        '０'..='９' | '，' | '．' | '：' => char::from_u32(c as u32 - 0xFEE0).unwrap_or(c),
+        '￥' => '¥',
+        _ => c,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_digits() {
+        let cases = [
+            ("１，２３４円", "1,234円"),
+            ("￥１２３", "¥123"),
+            ("１２．５km", "12.5km"),
+            ("０９：３２", "09:32"),
+            ("ＪＲ山手線", "ＪＲ山手線"),
+            ("", ""),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(normalize_digits(input), expected, "input: {input}");
+        }
+        assert!(matches!(normalize_digits("1,234円"), Cow::Borrowed(_)));
+    }
+}
@@ -1,16 +1,49 @@
+use std::cell::Cell;
+
 use chrono::{DateTime, FixedOffset, Timelike};
 use serde::{self, Deserialize, Deserializer, Serializer};
 
 const FMT_MIN_TZ: &str = "%Y-%m-%dT%H:%M%:z";
 const FMT_SEC_TZ: &str = "%Y-%m-%dT%H:%M:%S%:z";
+const FMT_MIN_NAIVE: &str = "%Y-%m-%dT%H:%M";
+const JST_OFFSET_SECONDS: i32 = 9 * 3600;
+
+thread_local! {
+    /// Set while serializing inside [`with_naive_local`].
+    static NAIVE_LOCAL: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Run `f` with every datetime serialized as JST wall-clock time without
+/// an offset ("2025-12-18T09:30").
+pub(crate) fn with_naive_local<R>(f: impl FnOnce() -> R) -> R {
+    struct Reset(bool);
+    impl Drop for Reset {
+        fn drop(&mut self) {
+            NAIVE_LOCAL.with(|naive| naive.set(self.0));
+        }
+    }
+
+    let _reset = Reset(NAIVE_LOCAL.with(|naive| naive.replace(true)));
+    f()
+}
+
+/// `dt` truncated to the minute, in the current serialization mode.
+fn format_minute(dt: &DateTime<FixedOffset>) -> String {
+    let dt = dt.with_second(0).unwrap().with_nanosecond(0).unwrap();
+    if NAIVE_LOCAL.with(Cell::get) {
+        let jst = FixedOffset::east_opt(JST_OFFSET_SECONDS).expect("valid JST offset");
+        dt.with_timezone(&jst).format(FMT_MIN_NAIVE).to_string()
+    } else {
+        dt.format(FMT_MIN_TZ).to_string()
+    }
+}
 
 #[allow(dead_code)]
 pub fn serialize<S>(dt: &DateTime<FixedOffset>, ser: S) -> Result<S::Ok, S::Error>
 where
     S: Serializer,
 {
-    let dt = dt.with_second(0).unwrap().with_nanosecond(0).unwrap();
-    ser.serialize_str(&dt.format(FMT_MIN_TZ).to_string())
+    ser.serialize_str(&format_minute(dt))
 }
 
 pub fn deserialize<'de, D>(de: D) -> Result<DateTime<FixedOffset>, D::Error>
@@ -21,7 +54,7 @@ where
     parse_str(&s).map_err(serde::de::Error::custom)
 }
 
-fn parse_str(s: &str) -> Result<DateTime<FixedOffset>, String> {
+pub(crate) fn parse_str(s: &str) -> Result<DateTime<FixedOffset>, String> {
     let mut s = s.to_string();
 
     // Normalize 'Z' to +00:00
@@ -82,6 +115,31 @@ pub mod option {
     }
 }
 
+pub mod vec {
+    use super::*;
+    use serde::{Serializer, ser::SerializeSeq};
+
+    pub fn serialize<S>(dts: &[DateTime<FixedOffset>], ser: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut seq = ser.serialize_seq(Some(dts.len()))?;
+        for dt in dts {
+            seq.serialize_element(&format_minute(dt))?;
+        }
+        seq.end()
+    }
+
+    #[cfg(feature = "schemars")]
+    pub fn schema(generator: &mut schemars::generate::SchemaGenerator) -> schemars::Schema {
+        let item = super::schema(generator);
+        schemars::json_schema!({
+            "type": "array",
+            "items": item
+        })
+    }
+}
+
 #[cfg(feature = "schemars")]
 pub fn schema(_gen: &mut schemars::generate::SchemaGenerator) -> schemars::Schema {
     // YYYY-MM-DD
@@ -170,4 +228,39 @@ mod tests {
             assert!(result.is_err(), "Expected failure on input: {}", input);
         }
     }
+
+    #[test]
+    fn test_naive_local_mode() {
+        let utc = FixedOffset::east_opt(0)
+            .unwrap()
+            .with_ymd_and_hms(2025, 12, 18, 0, 30, 45)
+            .unwrap();
+        let wrapped = Wrap { date: utc };
+        let naive = with_naive_local(|| serde_json::to_string(&wrapped).unwrap());
+        assert_eq!(naive, r#"{"date":"2025-12-18T09:30"}"#);
+        // offset-bearing again outside the scope
+        assert_eq!(
+            serde_json::to_string(&wrapped).unwrap(),
+            r#"{"date":"2025-12-18T00:30+00:00"}"#
+        );
+        // the naive form cannot be read back
+        assert!(serde_json::from_str::<Wrap>(&naive).is_err());
+    }
+
+    #[cfg(feature = "schemars")]
+    #[test]
+    fn test_schema_examples_match_pattern() {
+        let schema = schema(&mut schemars::generate::SchemaGenerator::default());
+        let pattern = regex::Regex::new(schema.get("pattern").unwrap().as_str().unwrap()).unwrap();
+        let examples = schema.get("examples").unwrap().as_array().unwrap();
+        assert!(!examples.is_empty());
+        for example in examples {
+            let example = example.as_str().unwrap();
+            assert!(pattern.is_match(example), "pattern rejects {example}");
+            let _: Wrap = serde_json::from_str(&format!(r#"{{"date":"{example}"}}"#)).unwrap();
+        }
+        for input in ["2025-12-18", "2025-12-18T09:30", "2025/12/18+09:00"] {
+            assert!(!pattern.is_match(input), "pattern accepts {input}");
+        }
+    }
 }
@@ -0,0 +1,138 @@
+//! Frozen v1 serialization view of [`TransitDto`].
+//!
+//! These structs mirror the field set and names of the DTOs as of v1 and must
+//! never change. New fields on the DTOs are intentionally not copied here.
+
+use chrono::{DateTime, FixedOffset};
+use serde::Serialize;
+
+use crate::transit_dto::{RouteDto, RouteSummaryDto, SegmentDto, TransitDto};
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransitV1<'a> {
+    from: &'a str,
+    to: &'a str,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        with = "crate::dt_minute_tz::option"
+    )]
+    search_date_time: Option<DateTime<FixedOffset>>,
+    routes: Vec<RouteV1<'a>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RouteV1<'a> {
+    rank: u32,
+    summary: SummaryV1,
+    segments: Vec<SegmentV1<'a>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SummaryV1 {
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        with = "crate::dt_minute_tz::option"
+    )]
+    departure_time: Option<DateTime<FixedOffset>>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        with = "crate::dt_minute_tz::option"
+    )]
+    arrival_time: Option<DateTime<FixedOffset>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    duration_minutes: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    transfer_count: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    total_price_yen: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    distance_km: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    is_fast: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    is_easy: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    is_cheap: Option<bool>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SegmentV1<'a> {
+    mode: &'a str,
+    from: &'a str,
+    to: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    line: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    destination: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    duration_minutes: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fare_yen: Option<u32>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        with = "crate::dt_minute_tz::option"
+    )]
+    departure_time: Option<DateTime<FixedOffset>>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        with = "crate::dt_minute_tz::option"
+    )]
+    arrival_time: Option<DateTime<FixedOffset>>,
+}
+
+impl<'a> From<&'a TransitDto> for TransitV1<'a> {
+    fn from(dto: &'a TransitDto) -> Self {
+        Self {
+            from: &dto.from,
+            to: &dto.to,
+            search_date_time: dto.search_date_time,
+            routes: dto.routes.iter().map(RouteV1::from).collect(),
+        }
+    }
+}
+
+impl<'a> From<&'a RouteDto> for RouteV1<'a> {
+    fn from(route: &'a RouteDto) -> Self {
+        Self {
+            rank: route.rank,
+            summary: SummaryV1::from(&route.summary),
+            segments: route.segments.iter().map(SegmentV1::from).collect(),
+        }
+    }
+}
+
+impl From<&RouteSummaryDto> for SummaryV1 {
+    fn from(s: &RouteSummaryDto) -> Self {
+        Self {
+            departure_time: s.departure_time,
+            arrival_time: s.arrival_time,
+            duration_minutes: s.duration_minutes,
+            transfer_count: s.transfer_count,
+            total_price_yen: s.total_price_yen,
+            distance_km: s.distance_km,
+            is_fast: s.is_fast,
+            is_easy: s.is_easy,
+            is_cheap: s.is_cheap,
+        }
+    }
+}
+
+impl<'a> From<&'a SegmentDto> for SegmentV1<'a> {
+    fn from(s: &'a SegmentDto) -> Self {
+        Self {
+            mode: &s.mode,
+            from: &s.from,
+            to: &s.to,
+            line: s.line.as_deref(),
+            destination: s.destination.as_deref(),
+            duration_minutes: s.duration_minutes,
+            fare_yen: s.fare_yen,
+            departure_time: s.departure_time,
+            arrival_time: s.arrival_time,
+        }
+    }
+}
@@ -0,0 +1,189 @@
+//! Self-describing envelope for storing results long-term.
+//!
+//! [`TransitDto::to_versioned_json`] wraps a result with the crate version
+//! and [`PARSER_REVISION`] that produced it, so archived results can be
+//! told apart from ones a newer parser would read differently.
+
+use std::fmt;
+
+use chrono::{DateTime, FixedOffset};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    args::jst_now,
+    transit_dto::{PARSER_REVISION, TransitDto},
+};
+
+/// A result read back by [`TransitDto::from_versioned_json`].
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VersionedTransit {
+    /// Version of this crate that wrote the envelope.
+    pub crate_version: String,
+    /// [`PARSER_REVISION`] of the parser that produced `data`.
+    pub parser_revision: u32,
+    /// When the envelope was written.
+    pub generated_at: DateTime<FixedOffset>,
+    /// The serialized [`TransitDto`].
+    pub data: Value,
+}
+
+impl VersionedTransit {
+    /// Whether the current parser would read the raw page differently, so
+    /// re-parsing an archived page may give a better result.
+    pub fn is_stale(&self) -> bool {
+        self.parser_revision < PARSER_REVISION
+    }
+}
+
+/// Why [`TransitDto::from_versioned_json`] rejected its input.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum EnvelopeError {
+    /// The input is not a versioned envelope.
+    Malformed(serde_json::Error),
+    /// The envelope comes from a newer parser than this one.
+    UnsupportedRevision {
+        /// Revision in the envelope.
+        found: u32,
+        /// Newest revision this build reads ([`PARSER_REVISION`]).
+        supported: u32,
+    },
+}
+
+impl fmt::Display for EnvelopeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Malformed(e) => write!(f, "malformed versioned envelope: {e}"),
+            Self::UnsupportedRevision { found, supported } => write!(
+                f,
+                "parser revision {found} is newer than the supported revision {supported}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for EnvelopeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Malformed(e) => Some(e),
+            Self::UnsupportedRevision { .. } => None,
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Envelope<'a> {
+    crate_version: &'static str,
+    parser_revision: u32,
+    generated_at: DateTime<FixedOffset>,
+    data: &'a TransitDto,
+}
+
+impl TransitDto {
+    /// Serialize wrapped in an envelope recording the crate version,
+    /// [`PARSER_REVISION`] and the current time:
+    /// `{"crateVersion", "parserRevision", "generatedAt", "data"}`.
+    ///
+    /// # Errors
+    /// Returns an error if serialization fails.
+    pub fn to_versioned_json(&self) -> serde_json::Result<String> {
+        self.to_versioned_json_at(jst_now())
+    }
+
+    fn to_versioned_json_at(
+        &self,
+        generated_at: DateTime<FixedOffset>,
+    ) -> serde_json::Result<String> {
+        serde_json::to_string(&Envelope {
+            crate_version: env!("CARGO_PKG_VERSION"),
+            parser_revision: PARSER_REVISION,
+            generated_at,
+            data: self,
+        })
+    }
+
+    /// Read an envelope written by [`to_versioned_json`](Self::to_versioned_json).
+    ///
+    /// The DTOs are output-only, so `data` is returned as JSON. Envelopes
+    /// from older revisions are accepted; check
+    /// [`VersionedTransit::is_stale`] to decide whether to re-parse.
+    ///
+    /// # Errors
+    /// Returns [`EnvelopeError::UnsupportedRevision`] for a revision newer
+    /// than [`PARSER_REVISION`], or [`EnvelopeError::Malformed`] if `json`
+    /// is not an envelope.
+    pub fn from_versioned_json(json: &str) -> Result<VersionedTransit, EnvelopeError> {
+        let versioned: VersionedTransit =
+            serde_json::from_str(json).map_err(EnvelopeError::Malformed)?;
+        if versioned.parser_revision > PARSER_REVISION {
+            return Err(EnvelopeError::UnsupportedRevision {
+                found: versioned.parser_revision,
+                supported: PARSER_REVISION,
+            });
+        }
+        Ok(versioned)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transit_dto::{load_next_data, next_data_to_transit_dto};
+
+    fn dto() -> TransitDto {
+        next_data_to_transit_dto(
+            &load_next_data(include_str!("../tests/fixtures/basic.json")).unwrap(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let dto = dto();
+        let now = jst_now();
+        let json = dto.to_versioned_json_at(now).unwrap();
+        let versioned = TransitDto::from_versioned_json(&json).unwrap();
+        assert_eq!(versioned.crate_version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(versioned.parser_revision, PARSER_REVISION);
+        assert_eq!(versioned.generated_at, now);
+        assert_eq!(versioned.data, serde_json::to_value(&dto).unwrap());
+        assert!(!versioned.is_stale());
+    }
+
+    #[test]
+    fn test_revisions() {
+        let mut envelope: Value =
+            serde_json::from_str(&dto().to_versioned_json().unwrap()).unwrap();
+
+        envelope["parserRevision"] = (PARSER_REVISION + 1).into();
+        let err = TransitDto::from_versioned_json(&envelope.to_string()).unwrap_err();
+        assert!(matches!(
+            err,
+            EnvelopeError::UnsupportedRevision { found, supported }
+                if found == PARSER_REVISION + 1 && supported == PARSER_REVISION
+        ));
+
+        envelope["parserRevision"] = 0.into();
+        let versioned = TransitDto::from_versioned_json(&envelope.to_string()).unwrap();
+        assert!(versioned.is_stale());
+
+        let err = TransitDto::from_versioned_json(r#"{"from":"新宿"}"#).unwrap_err();
+        assert!(matches!(err, EnvelopeError::Malformed(_)));
+    }
+
+    #[test]
+    fn test_parser_reads_revision() {
+        // The constant sits next to the parser; keep it referenced there so
+        // parser changes are reviewed alongside it.
+        let source = include_str!("transit_dto.rs");
+        let parser = source
+            .split("pub fn next_data_to_transit_dto_with(")
+            .nth(1)
+            .and_then(|rest| rest.split("\n}\n").next())
+            .unwrap();
+        assert!(parser.contains("PARSER_REVISION"));
+    }
+}
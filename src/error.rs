@@ -0,0 +1,273 @@
+use std::fmt;
+
+use chrono::{DateTime, FixedOffset};
+
+use crate::http::{HttpError, certificate_error};
+
+/// Errors with a specific meaning that callers may want to handle.
+///
+/// Public functions return [`anyhow::Result`]; use
+/// `err.downcast_ref::<TransitError>()` to inspect these.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TransitError {
+    /// The upstream refused to serve the request (HTTP 403 / 429),
+    /// typically because of anti-bot filtering.
+    Blocked { status: u16 },
+    /// The origin or destination could not be resolved to a place.
+    PlaceNotFound {
+        field: PlaceField,
+        /// The unresolved input.
+        query: String,
+        /// Up to 3 suggested names, when enabled on the client.
+        did_you_mean: Vec<String>,
+    },
+    /// Both places were resolved but no route connects them.
+    NoRouteFound {
+        /// Resolved origin name.
+        from: String,
+        /// Resolved destination name.
+        to: String,
+        /// Up to 3 suggested names for inputs that resolved to a different
+        /// name, when enabled on the client.
+        did_you_mean: Vec<String>,
+    },
+    /// The search arguments failed [`TransitArgs::validate`](crate::args::TransitArgs::validate).
+    InvalidArgs(Vec<ArgError>),
+    /// The site rejected the search and redirected to its top page.
+    InvalidSearch {
+        /// What was rejected and, if known, which argument likely caused it.
+        reason: String,
+    },
+    /// The response body is not valid UTF-8.
+    Encoding {
+        /// Charset declared in the `Content-Type` header, if any.
+        charset: Option<String>,
+    },
+    /// The server certificate failed verification, e.g. because it is not
+    /// signed by a root given to
+    /// [`TransitClientBuilder::pin_certificates`](crate::TransitClientBuilder::pin_certificates).
+    /// Never retried.
+    Tls {
+        /// The verifier's description of the failure.
+        message: String,
+    },
+    /// The site served its maintenance page instead of a result. Never
+    /// retried within the call; wait until `until` before trying again.
+    Maintenance {
+        /// End of the maintenance window, if the page announces one.
+        until: Option<DateTime<FixedOffset>>,
+    },
+    /// The origin or destination lies outside the area the site covers, so
+    /// no search was made. Unlike [`NoRouteFound`](Self::NoRouteFound),
+    /// trying other times or options will not help.
+    ///
+    /// Unverified: the message keys and phrases this is detected by are
+    /// assumed and only seen in a hand-written fixture, so such searches may
+    /// still be reported as `NoRouteFound`.
+    OutOfServiceArea {
+        /// Resolved origin name.
+        from: String,
+        /// Resolved destination name.
+        to: String,
+        /// The site's explanation.
+        message: String,
+    },
+}
+
+/// A single problem found by [`TransitArgs::validate`](crate::args::TransitArgs::validate).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ArgError {
+    /// `from` or `to` is empty or whitespace only.
+    EmptyPlace(PlaceField),
+    /// `rank` is 0; ranks start at 1.
+    ZeroRank,
+    /// `options.available_means` is empty.
+    NoAvailableMeans,
+    /// `from` and `to` are the same place.
+    SamePlace,
+    /// The year of `date` is before 2000 or after 2100, which no timetable
+    /// covers.
+    DateOutOfRange { year: i32 },
+}
+
+/// A problem in [`TransitArgs`](crate::args::TransitArgs) that is fixed up
+/// rather than rejected, from
+/// [`TransitArgs::warnings`](crate::args::TransitArgs::warnings).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ArgWarning {
+    /// `via[index]` is empty or whitespace only and is left out.
+    BlankVia { index: usize },
+    /// `via[index]` names `from`, `to` or an earlier via entry and is left
+    /// out, so the query does not contradict itself.
+    RedundantVia {
+        index: usize,
+        /// The normalized name.
+        name: String,
+    },
+    /// `date` is more than a day before today (JST). The site may answer
+    /// with the current timetable instead.
+    PastDate { days_ago: i64 },
+    /// `date` is further ahead than the timetable horizon, so the timetable
+    /// for that day may not be published yet and the site may use the
+    /// current one.
+    BeyondHorizon { days_ahead: i64, horizon_days: u32 },
+}
+
+impl fmt::Display for ArgWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BlankVia { index } => write!(f, "`via[{index}]` is empty; ignored"),
+            Self::RedundantVia { index, name } => write!(
+                f,
+                "`via[{index}]` ({name}) repeats `from`, `to` or an earlier via; ignored"
+            ),
+            Self::PastDate { days_ago } => write!(
+                f,
+                "`date` is {days_ago} days in the past; results may use the current timetable"
+            ),
+            Self::BeyondHorizon {
+                days_ahead,
+                horizon_days,
+            } => write!(
+                f,
+                "`date` is {days_ahead} days ahead, beyond the {horizon_days}-day timetable horizon; results may use the current timetable"
+            ),
+        }
+    }
+}
+
+impl fmt::Display for ArgError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::EmptyPlace(field) => write!(f, "`{field}` must not be empty"),
+            Self::ZeroRank => f.write_str("`rank` must be at least 1"),
+            Self::NoAvailableMeans => f.write_str("`availableMeans` must not be empty"),
+            Self::SamePlace => f.write_str("`from` and `to` must differ"),
+            Self::DateOutOfRange { year } => {
+                write!(f, "`date` year {year} must be between 2000 and 2100")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ArgError {}
+
+/// Which endpoint of a search an error refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PlaceField {
+    From,
+    To,
+}
+
+impl fmt::Display for PlaceField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::From => "from",
+            Self::To => "to",
+        })
+    }
+}
+
+impl fmt::Display for TransitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Blocked { status } => write!(f, "request blocked by upstream (HTTP {status})"),
+            Self::PlaceNotFound {
+                field,
+                query,
+                did_you_mean,
+            } => {
+                write!(f, "place not found for `{field}`: {query}")?;
+                write_did_you_mean(f, did_you_mean)
+            }
+            Self::NoRouteFound {
+                from,
+                to,
+                did_you_mean,
+            } => {
+                write!(f, "no route found from {from} to {to}")?;
+                write_did_you_mean(f, did_you_mean)
+            }
+            Self::InvalidArgs(errors) => {
+                f.write_str("invalid arguments: ")?;
+                for (i, e) in errors.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str("; ")?;
+                    }
+                    write!(f, "{e}")?;
+                }
+                Ok(())
+            }
+            Self::InvalidSearch { reason } => write!(f, "invalid search: {reason}"),
+            Self::Encoding { charset } => match charset {
+                Some(charset) => write!(
+                    f,
+                    "response body is not UTF-8 (declared charset: {charset})"
+                ),
+                None => f.write_str("response body is not UTF-8 (no charset declared)"),
+            },
+            Self::Tls { message } => {
+                write!(f, "TLS certificate verification failed: {message}")
+            }
+            Self::Maintenance { until: Some(until) } => {
+                write!(
+                    f,
+                    "upstream is under maintenance until {}",
+                    until.format("%Y-%m-%d %H:%M %:z")
+                )
+            }
+            Self::Maintenance { until: None } => f.write_str("upstream is under maintenance"),
+            Self::OutOfServiceArea { from, to, message } => {
+                write!(f, "{from} to {to} is outside the service area: {message}")
+            }
+        }
+    }
+}
+
+fn write_did_you_mean(f: &mut fmt::Formatter<'_>, names: &[String]) -> fmt::Result {
+    if names.is_empty() {
+        Ok(())
+    } else {
+        write!(f, " (did you mean: {}?)", names.join(", "))
+    }
+}
+
+impl std::error::Error for TransitError {}
+
+/// Classify an HTTP status code as a blocked request.
+pub(crate) fn blocked_status(status: u16) -> Option<TransitError> {
+    matches!(status, 403 | 429).then_some(TransitError::Blocked { status })
+}
+
+/// Classify a send error caused by certificate verification as
+/// [`TransitError::Tls`].
+///
+/// The source chain is searched for the TLS library's own error first. When
+/// the backend hides it, a connect error whose chain mentions a certificate
+/// is taken as one; errors after the connection was made never are.
+pub(crate) fn tls_error(err: &HttpError) -> Option<TransitError> {
+    let chain = || {
+        std::iter::successors(Some(err as &(dyn std::error::Error + 'static)), |e| {
+            e.source()
+        })
+    };
+    let message = chain().find_map(certificate_error).or_else(|| {
+        if !err.is_connect() {
+            return None;
+        }
+        chain()
+            .map(|e| e.to_string())
+            .find(|message| message.to_ascii_lowercase().contains("certificate"))
+    })?;
+    Some(TransitError::Tls { message })
+}
+
+pub(crate) fn is_blocked(err: &anyhow::Error) -> bool {
+    matches!(
+        err.downcast_ref::<TransitError>(),
+        Some(TransitError::Blocked { .. })
+    )
+}
@@ -0,0 +1,77 @@
+//! Cargo features compiled into this build, for hosts that load the crate
+//! dynamically.
+
+use std::sync::LazyLock;
+
+/// Every optional feature with whether it is enabled.
+const ALL: [(&str, bool); 9] = [
+    ("http-reqwest", cfg!(feature = "http-reqwest")),
+    ("http-wreq", cfg!(feature = "http-wreq")),
+    ("schemars", cfg!(feature = "schemars")),
+    ("raw-fields", cfg!(feature = "raw-fields")),
+    ("autocomplete", cfg!(feature = "autocomplete")),
+    ("geo", cfg!(feature = "geo")),
+    ("anonymize", cfg!(feature = "anonymize")),
+    ("dangerous-config", cfg!(feature = "dangerous-config")),
+    ("test-util", cfg!(feature = "test-util")),
+];
+
+static ENABLED: LazyLock<Vec<&'static str>> = LazyLock::new(|| {
+    ALL.iter()
+        .filter(|(_, enabled)| *enabled)
+        .map(|(name, _)| *name)
+        .collect()
+});
+
+/// Names of the Cargo features this build was compiled with, as written in
+/// `Cargo.toml` (e.g. `["http-reqwest", "schemars"]`).
+pub fn features() -> &'static [&'static str] {
+    &ENABLED
+}
+
+/// Whether the Cargo feature `name` was compiled in.
+pub fn has_feature(name: &str) -> bool {
+    features().contains(&name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lists_every_cargo_feature() {
+        let manifest = include_str!("../Cargo.toml");
+        let mut declared: Vec<&str> = manifest
+            .split("[features]")
+            .nth(1)
+            .unwrap()
+            .lines()
+            .skip(1)
+            .take_while(|line| !line.starts_with('['))
+            .filter_map(|line| line.split_once('=').map(|(name, _)| name.trim()))
+            .filter(|name| !name.is_empty() && *name != "default")
+            .collect();
+        let mut listed: Vec<&str> = ALL.iter().map(|(name, _)| *name).collect();
+        declared.sort_unstable();
+        listed.sort_unstable();
+        assert_eq!(declared, listed, "update `ALL` to match Cargo.toml");
+    }
+
+    #[test]
+    fn test_enabled_features() {
+        // exactly one HTTP backend
+        assert_eq!(
+            ["http-reqwest", "http-wreq"]
+                .iter()
+                .filter(|name| has_feature(name))
+                .count(),
+            1
+        );
+        assert_eq!(has_feature("http-reqwest"), cfg!(feature = "http-reqwest"));
+        assert_eq!(has_feature("http-wreq"), cfg!(feature = "http-wreq"));
+        assert_eq!(has_feature("schemars"), cfg!(feature = "schemars"));
+        assert!(!has_feature("default"));
+        assert!(!has_feature("ical"));
+        assert!(features().iter().all(|name| has_feature(name)));
+    }
+}
@@ -0,0 +1,154 @@
+//! Fare, duration and label rendering shared by the human-readable
+//! formatters ([`RouteDto::to_mermaid_with`], [`RouteSummaryDto::format`]).
+//!
+//! [`RouteDto::to_mermaid_with`]: crate::transit_dto::RouteDto::to_mermaid_with
+//! [`RouteSummaryDto::format`]: crate::transit_dto::RouteSummaryDto::format
+
+use serde::{Deserialize, Serialize};
+
+/// How formatters render numbers and labels. Defaults to Japanese:
+/// "1,234円", "1時間5分", "乗換1回".
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct FormatOptions {
+    /// How fares are written.
+    pub currency_style: CurrencyStyle,
+    /// How durations are written.
+    pub duration_style: DurationStyle,
+    /// Language of words such as "walk" and "transfers".
+    pub locale: Locale,
+}
+
+/// Fare rendering.
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[non_exhaustive]
+pub enum CurrencyStyle {
+    /// "1,234円"
+    #[default]
+    Kanji,
+    /// "¥1,234"
+    Symbol,
+    /// "1 234 JPY"
+    Code,
+}
+
+/// Duration rendering.
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[non_exhaustive]
+pub enum DurationStyle {
+    /// "1時間5分", "45分"
+    #[default]
+    Japanese,
+    /// "1h05", "45min"
+    Compact,
+}
+
+/// Language of the words formatters add around the data.
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[non_exhaustive]
+pub enum Locale {
+    #[default]
+    Ja,
+    En,
+}
+
+impl FormatOptions {
+    /// A fare in yen.
+    pub fn fare(&self, yen: u32) -> String {
+        match self.currency_style {
+            CurrencyStyle::Kanji => format!("{}円", group_digits(yen, ',')),
+            CurrencyStyle::Symbol => format!("¥{}", group_digits(yen, ',')),
+            CurrencyStyle::Code => format!("{} JPY", group_digits(yen, ' ')),
+        }
+    }
+
+    /// A duration in minutes.
+    pub fn duration(&self, minutes: u32) -> String {
+        let (h, m) = (minutes / 60, minutes % 60);
+        match (self.duration_style, h) {
+            (DurationStyle::Japanese, 0) => format!("{m}分"),
+            (DurationStyle::Japanese, _) if m == 0 => format!("{h}時間"),
+            (DurationStyle::Japanese, _) => format!("{h}時間{m}分"),
+            (DurationStyle::Compact, 0) => format!("{m}min"),
+            (DurationStyle::Compact, _) => format!("{h}h{m:02}"),
+        }
+    }
+
+    /// A transfer count.
+    pub fn transfers(&self, count: u32) -> String {
+        match (self.locale, count) {
+            (Locale::Ja, _) => format!("乗換{count}回"),
+            (Locale::En, 1) => "1 transfer".to_string(),
+            (Locale::En, _) => format!("{count} transfers"),
+        }
+    }
+
+    /// Label of a walking segment.
+    pub fn walk(&self) -> &'static str {
+        match self.locale {
+            Locale::Ja => "徒歩",
+            Locale::En => "Walk",
+        }
+    }
+}
+
+/// `n` with `sep` between groups of three digits.
+fn group_digits(n: u32, sep: char) -> String {
+    let digits = n.to_string();
+    let mut out = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(sep);
+        }
+        out.push(c);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fare_and_duration_styles() {
+        let options = |currency_style, duration_style| FormatOptions {
+            currency_style,
+            duration_style,
+            locale: Locale::Ja,
+        };
+        let ja = options(CurrencyStyle::Kanji, DurationStyle::Japanese);
+        assert_eq!(ja, FormatOptions::default());
+        assert_eq!(ja.fare(1234), "1,234円");
+        assert_eq!(ja.fare(200), "200円");
+        assert_eq!(ja.duration(65), "1時間5分");
+        assert_eq!(ja.duration(120), "2時間");
+        assert_eq!(ja.duration(45), "45分");
+
+        let other = options(CurrencyStyle::Symbol, DurationStyle::Compact);
+        assert_eq!(other.fare(1234), "¥1,234");
+        assert_eq!(other.fare(1_234_567), "¥1,234,567");
+        assert_eq!(other.duration(65), "1h05");
+        assert_eq!(other.duration(45), "45min");
+
+        let code = options(CurrencyStyle::Code, DurationStyle::Compact);
+        assert_eq!(code.fare(1234), "1 234 JPY");
+        assert_eq!(code.fare(0), "0 JPY");
+
+        let json = serde_json::to_value(FormatOptions {
+            locale: Locale::En,
+            ..code
+        })
+        .unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({"currencyStyle": "code", "durationStyle": "compact", "locale": "en"})
+        );
+    }
+}
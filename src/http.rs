@@ -4,24 +4,310 @@ compile_error!("Enable only one of the features: `http-reqwest` or `http-wreq`."
 #[cfg(not(any(feature = "http-reqwest", feature = "http-wreq")))]
 compile_error!("Enable one HTTP client feature: `http-reqwest` or `http-wreq`.");
 
+use std::{net::IpAddr, time::Duration};
+
+use crate::error::TransitError;
+
+#[cfg(feature = "http-reqwest")]
+use std::net::SocketAddr;
+
 #[cfg(feature = "http-wreq")]
-use wreq_util::Emulation;
+pub use wreq_util::Emulation;
 
 #[cfg(feature = "http-reqwest")]
-/// Build a reqwest HTTP client with sensible defaults.
-pub fn http_client() -> reqwest::Client {
-    reqwest::ClientBuilder::new()
-        .timeout(std::time::Duration::from_secs(10))
-        .build()
-        .unwrap()
+pub type HttpClient = reqwest::Client;
+
+#[cfg(feature = "http-wreq")]
+pub type HttpClient = wreq::Client;
+
+/// Name of the enabled HTTP backend.
+#[cfg(feature = "http-reqwest")]
+pub(crate) const BACKEND: &str = "reqwest";
+
+#[cfg(feature = "http-wreq")]
+pub(crate) const BACKEND: &str = "wreq";
+
+#[cfg(feature = "http-reqwest")]
+pub use reqwest::header::HeaderMap;
+
+/// Error returned by the enabled HTTP backend.
+#[cfg(feature = "http-reqwest")]
+pub(crate) type HttpError = reqwest::Error;
+
+#[cfg(feature = "http-wreq")]
+pub(crate) type HttpError = wreq::Error;
+
+#[cfg(feature = "http-wreq")]
+pub use wreq::header::HeaderMap;
+
+/// How outgoing requests pick a proxy.
+#[derive(Debug, Clone, Default)]
+pub enum ProxySetting {
+    /// Use the system proxy settings (`HTTP_PROXY`, `HTTPS_PROXY`, ...).
+    #[default]
+    System,
+    /// Connect directly, ignoring any system proxy.
+    Disabled,
+    /// Send every request through the given proxy URL.
+    Url(String),
+}
+
+/// A root certificate to trust in place of the built-in roots.
+///
+/// The bytes are handed to the backend when the client is built.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Certificate(CertificateData);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum CertificateData {
+    Der(Vec<u8>),
+    Pem(Vec<u8>),
+}
+
+impl Certificate {
+    /// A DER-encoded X.509 certificate.
+    pub fn from_der(der: impl Into<Vec<u8>>) -> Self {
+        Self(CertificateData::Der(der.into()))
+    }
+
+    /// A PEM-encoded X.509 certificate.
+    pub fn from_pem(pem: impl Into<Vec<u8>>) -> Self {
+        Self(CertificateData::Pem(pem.into()))
+    }
+}
+
+/// Backend-agnostic settings applied when building the HTTP client.
+#[derive(Debug, Clone)]
+pub struct HttpConfig {
+    pub timeout: Duration,
+    pub proxy: ProxySetting,
+    /// Maximum idle connections kept per host (`None`: backend default).
+    pub pool_max_idle_per_host: Option<usize>,
+    /// How long idle pooled connections are kept (`None`: backend default).
+    pub pool_idle_timeout: Option<Duration>,
+    /// TCP keepalive interval (`None`: backend default).
+    pub tcp_keepalive: Option<Duration>,
+    /// Local address to bind outgoing connections to.
+    pub local_address: Option<IpAddr>,
+    /// Roots that replace the built-in ones when non-empty.
+    pub pinned_certificates: Vec<Certificate>,
+    /// Keep cookies set by responses and send them on later requests.
+    pub cookie_store: bool,
+    /// Skip certificate verification entirely.
+    #[cfg(feature = "dangerous-config")]
+    pub accept_invalid_certs: bool,
+    /// Static DNS overrides (host, address).
+    #[cfg(feature = "http-reqwest")]
+    pub resolve: Vec<(String, SocketAddr)>,
+    /// Browser profile to emulate.
+    #[cfg(feature = "http-wreq")]
+    pub emulation: Emulation,
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(10),
+            proxy: ProxySetting::default(),
+            pool_max_idle_per_host: None,
+            pool_idle_timeout: None,
+            tcp_keepalive: None,
+            local_address: None,
+            pinned_certificates: Vec::new(),
+            cookie_store: false,
+            #[cfg(feature = "dangerous-config")]
+            accept_invalid_certs: false,
+            #[cfg(feature = "http-reqwest")]
+            resolve: Vec::new(),
+            #[cfg(feature = "http-wreq")]
+            emulation: Emulation::Chrome137,
+        }
+    }
+}
+
+/// Decode a response body as UTF-8, stripping a leading byte-order mark.
+///
+/// The declared charset is only used for the error message: a mislabeled
+/// body that is valid UTF-8 is accepted.
+pub(crate) fn decode_body(
+    bytes: &[u8],
+    content_type: Option<&str>,
+) -> Result<String, TransitError> {
+    let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes);
+    String::from_utf8(bytes.to_vec()).map_err(|_| TransitError::Encoding {
+        charset: content_type.and_then(charset_of),
+    })
+}
+
+fn charset_of(content_type: &str) -> Option<String> {
+    content_type.split(';').skip(1).find_map(|param| {
+        let (key, value) = param.split_once('=')?;
+        key.trim()
+            .eq_ignore_ascii_case("charset")
+            .then(|| value.trim().trim_matches('"').to_string())
+    })
+}
+
+/// Describe `err` if it is rustls rejecting the server certificate.
+///
+/// rustls errors reach the backend wrapped in (possibly nested)
+/// [`std::io::Error`]s, whose `source` skips the wrapped error, so the
+/// wrappers are unpacked here.
+#[cfg(feature = "http-reqwest")]
+pub(crate) fn certificate_error(err: &(dyn std::error::Error + 'static)) -> Option<String> {
+    let mut err = err;
+    while let Some(inner) = err
+        .downcast_ref::<std::io::Error>()
+        .and_then(|io| io.get_ref())
+    {
+        err = inner;
+    }
+    let tls = err.downcast_ref::<rustls::Error>()?;
+    matches!(
+        tls,
+        rustls::Error::InvalidCertificate(_) | rustls::Error::NoCertificatesPresented
+    )
+    .then(|| tls.to_string())
+}
+
+/// BoringSSL errors are not exposed by wreq, so nothing is recognized here.
+#[cfg(feature = "http-wreq")]
+pub(crate) fn certificate_error(_err: &(dyn std::error::Error + 'static)) -> Option<String> {
+    None
+}
+
+#[cfg(feature = "http-reqwest")]
+/// Build a reqwest HTTP client from the given settings.
+pub fn http_client(config: &HttpConfig) -> anyhow::Result<HttpClient> {
+    let mut builder = reqwest::ClientBuilder::new().timeout(config.timeout);
+    match &config.proxy {
+        ProxySetting::System => {}
+        ProxySetting::Disabled => builder = builder.no_proxy(),
+        ProxySetting::Url(url) => builder = builder.proxy(reqwest::Proxy::all(url)?),
+    }
+    if let Some(max) = config.pool_max_idle_per_host {
+        builder = builder.pool_max_idle_per_host(max);
+    }
+    if let Some(timeout) = config.pool_idle_timeout {
+        builder = builder.pool_idle_timeout(timeout);
+    }
+    if let Some(interval) = config.tcp_keepalive {
+        builder = builder.tcp_keepalive(interval);
+    }
+    if let Some(addr) = config.local_address {
+        builder = builder.local_address(addr);
+    }
+    for (host, addr) in &config.resolve {
+        builder = builder.resolve(host, *addr);
+    }
+    if !config.pinned_certificates.is_empty() {
+        let certs = config
+            .pinned_certificates
+            .iter()
+            .map(|Certificate(data)| match data {
+                CertificateData::Der(der) => reqwest::Certificate::from_der(der),
+                CertificateData::Pem(pem) => reqwest::Certificate::from_pem(pem),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        builder = builder.tls_certs_only(certs);
+    }
+    #[cfg(feature = "dangerous-config")]
+    if config.accept_invalid_certs {
+        builder = builder.danger_accept_invalid_certs(true);
+    }
+    if config.cookie_store {
+        builder = builder.cookie_store(true);
+    }
+    Ok(builder.build()?)
 }
 
 #[cfg(feature = "http-wreq")]
-/// Build a wreq HTTP client with sensible defaults.
-pub fn http_client() -> wreq::Client {
-    wreq::ClientBuilder::new()
-        .emulation(Emulation::Chrome137)
-        .timeout(std::time::Duration::from_secs(10))
-        .build()
-        .unwrap()
+/// Build a wreq HTTP client from the given settings.
+pub fn http_client(config: &HttpConfig) -> anyhow::Result<HttpClient> {
+    let mut builder = wreq::ClientBuilder::new()
+        .emulation(config.emulation)
+        .timeout(config.timeout);
+    match &config.proxy {
+        ProxySetting::System => {}
+        ProxySetting::Disabled => builder = builder.no_proxy(),
+        ProxySetting::Url(url) => builder = builder.proxy(wreq::Proxy::all(url)?),
+    }
+    if let Some(max) = config.pool_max_idle_per_host {
+        builder = builder.pool_max_idle_per_host(max);
+    }
+    if let Some(timeout) = config.pool_idle_timeout {
+        builder = builder.pool_idle_timeout(timeout);
+    }
+    if let Some(interval) = config.tcp_keepalive {
+        builder = builder.tcp_keepalive(interval);
+    }
+    if let Some(addr) = config.local_address {
+        builder = builder.local_address(addr);
+    }
+    if !config.pinned_certificates.is_empty() {
+        let mut store = wreq::tls::CertStore::builder();
+        for Certificate(data) in &config.pinned_certificates {
+            store = match data {
+                CertificateData::Der(der) => store.add_der_cert(der),
+                CertificateData::Pem(pem) => store.add_pem_cert(pem),
+            };
+        }
+        builder = builder.cert_store(store.build()?);
+    }
+    #[cfg(feature = "dangerous-config")]
+    if config.accept_invalid_certs {
+        builder = builder.cert_verification(false);
+    }
+    if config.cookie_store {
+        builder = builder.cookie_store(true);
+    }
+    Ok(builder.build()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_body() {
+        let bom = b"\xEF\xBB\xBF{\"a\":1}";
+        assert_eq!(decode_body(bom, None).unwrap(), r#"{"a":1}"#);
+
+        let mislabeled = "新宿".as_bytes();
+        assert_eq!(
+            decode_body(mislabeled, Some("text/html; charset=Shift_JIS")).unwrap(),
+            "新宿"
+        );
+
+        // "新宿" in Shift_JIS
+        let sjis = b"\x90\x56\x8F\x68";
+        assert_eq!(
+            decode_body(sjis, Some("text/html; charset=\"Shift_JIS\"")),
+            Err(TransitError::Encoding {
+                charset: Some("Shift_JIS".into())
+            })
+        );
+        assert_eq!(
+            decode_body(sjis, Some("text/html")),
+            Err(TransitError::Encoding { charset: None })
+        );
+    }
+
+    #[cfg(feature = "http-reqwest")]
+    #[test]
+    fn test_certificate_error() {
+        let rejected = std::io::Error::other(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            rustls::Error::InvalidCertificate(rustls::CertificateError::UnknownIssuer),
+        ));
+        assert_eq!(
+            certificate_error(&rejected).as_deref(),
+            Some("invalid peer certificate: UnknownIssuer")
+        );
+
+        let alert = std::io::Error::other(rustls::Error::DecryptError);
+        assert_eq!(certificate_error(&alert), None);
+        let mentioned = std::io::Error::other("certificate not found in cache");
+        assert_eq!(certificate_error(&mentioned), None);
+    }
 }
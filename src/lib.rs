@@ -7,7 +7,20 @@
 //!
 //! - `http-reqwest` (default)
 //! - `http-wreq`
-//! - `schemars`: Enable `JsonSchema` derives for public types.
+//! - `schemars`: Enable `JsonSchema` derives for public types and
+//!   [`schema::schemas`].
+//! - `raw-fields`: Keep the untouched suggest result object on each place.
+//! - `geo`: Convert places to and from `geo_types::Point`.
+//! - `anonymize`: Rewrite recorded pages into shareable fixtures
+//!   ([`anonymize::anonymize_next_data`], `examples/anonymize.rs`).
+//! - `autocomplete`: Debounced suggestions for search-as-you-type inputs
+//!   ([`autocomplete::autocomplete`]).
+//! - `dangerous-config`: Allow turning off certificate verification for
+//!   local HTTPS mocks. Testing only.
+//! - `test-util`: A mock [`clock`](crate::clock) for the client, so rate
+//!   limiting and retry backoff can be tested without real sleeps.
+//!
+//! [`features`] lists the features a build was compiled with.
 //!
 //! ```bash
 //! # default (reqwest)
@@ -42,10 +55,48 @@
 //!
 //! ## Notes
 //! This crate uses an unofficial API and may break without notice.
+#[cfg(feature = "anonymize")]
+pub mod anonymize;
 pub mod args;
+#[cfg(feature = "autocomplete")]
+pub mod autocomplete;
+mod cache;
+pub mod client;
+#[cfg(feature = "test-util")]
+pub mod clock;
+#[cfg(not(feature = "test-util"))]
+mod clock;
+mod digits;
 mod dt_minute_tz;
+mod dto_v1;
+pub mod envelope;
+mod error;
+mod features;
+pub mod format;
 mod http;
+mod limiter;
+mod maintenance;
+pub mod protocol;
+#[cfg(feature = "schemars")]
+pub mod schema;
+#[cfg(test)]
+mod test_server;
 pub mod transit_dto;
+pub mod verify;
+mod yen;
 mod yxhoo;
 
-pub use yxhoo::{YxhooPlace, YxhooPlaceKind, YxhooSuggestResponse, suggest_places, transit};
+pub use client::{
+    RetryConfig, TransitClient, TransitClientBuilder, WaitReason, init_default_client,
+};
+pub use error::{ArgError, ArgWarning, PlaceField, TransitError};
+pub use features::{features, has_feature};
+pub use format::FormatOptions;
+#[cfg(feature = "http-wreq")]
+pub use http::Emulation;
+pub use http::{Certificate, HeaderMap};
+pub use yen::Yen;
+pub use yxhoo::{
+    LatLon, PlaceResolution, YxhooPlace, YxhooPlaceKind, YxhooSuggestResponse, suggest_places,
+    transit, transit_from_body, transit_request_url,
+};
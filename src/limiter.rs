@@ -0,0 +1,110 @@
+//! Token-bucket rate limiter shared by clones of a client.
+
+use std::{fmt, sync::Mutex, time::Duration};
+
+use tokio::time::Instant;
+
+use crate::clock::SharedClock;
+
+/// Allows `max_requests` per `per`, refilling continuously.
+pub(crate) struct RateLimiter {
+    max_requests: u32,
+    per: Duration,
+    clock: SharedClock,
+    state: Mutex<Bucket>,
+}
+
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl RateLimiter {
+    /// A full bucket. `max_requests` is clamped to at least 1.
+    pub(crate) fn new(max_requests: u32, per: Duration, clock: SharedClock) -> Self {
+        let max_requests = max_requests.max(1);
+        Self {
+            max_requests,
+            per,
+            state: Mutex::new(Bucket {
+                tokens: f64::from(max_requests),
+                refilled_at: clock.now(),
+            }),
+            clock,
+        }
+    }
+
+    /// Wait until a token is available and take it, calling `on_wait` with
+    /// the length of each sleep before it starts.
+    pub(crate) async fn acquire(&self, mut on_wait: impl FnMut(Duration)) {
+        loop {
+            let wait = {
+                let mut bucket = self.state.lock().unwrap();
+                let now = self.clock.now();
+                let capacity = f64::from(self.max_requests);
+                let rate = capacity / self.per.as_secs_f64().max(f64::EPSILON);
+                let elapsed = now
+                    .saturating_duration_since(bucket.refilled_at)
+                    .as_secs_f64();
+                bucket.tokens = (bucket.tokens + elapsed * rate).min(capacity);
+                bucket.refilled_at = now;
+                if bucket.tokens >= 1.0 {
+                    bucket.tokens -= 1.0;
+                    return;
+                }
+                Duration::from_secs_f64((1.0 - bucket.tokens) / rate)
+            };
+            on_wait(wait);
+            self.clock.sleep(wait).await;
+        }
+    }
+}
+
+impl fmt::Debug for RateLimiter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{:?}", self.max_requests, self.per)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::clock::{Clock, MockClock, SystemClock};
+
+    #[tokio::test]
+    async fn test_acquire_waits_for_refill() {
+        let start = crate::args::jst_now();
+        let clock = MockClock::new(start);
+        let limiter = RateLimiter::new(2, Duration::from_secs(10), Arc::new(clock.clone()));
+        let mut waits = Vec::new();
+        limiter.acquire(|wait| waits.push(wait)).await;
+        limiter.acquire(|wait| waits.push(wait)).await;
+        assert_eq!(clock.now_jst(), start);
+        assert!(waits.is_empty());
+
+        limiter.acquire(|wait| waits.push(wait)).await;
+        assert_eq!(clock.now_jst() - start, chrono::Duration::seconds(5));
+        assert_eq!(waits, [Duration::from_secs(5)]);
+        assert_eq!(clock.sleeps(), waits);
+
+        // refilled by time passing without a sleep
+        clock.advance(Duration::from_secs(5));
+        limiter.acquire(|wait| waits.push(wait)).await;
+        assert_eq!(waits.len(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_system_clock_is_monotonic() {
+        // the wall clock moves on while tokio's time is paused; only
+        // elapsed monotonic time refills the bucket
+        let limiter = RateLimiter::new(1, Duration::from_secs(10), Arc::new(SystemClock));
+        let started = Instant::now();
+        limiter.acquire(|_| {}).await;
+        let mut waits = Vec::new();
+        limiter.acquire(|wait| waits.push(wait)).await;
+        assert_eq!(waits, [Duration::from_secs(10)]);
+        assert_eq!(started.elapsed(), Duration::from_secs(10));
+    }
+}
@@ -0,0 +1,199 @@
+//! Detection of the site's maintenance page.
+
+use chrono::{DateTime, Datelike, FixedOffset, NaiveDate, TimeZone};
+use scraper::{Html, Selector};
+
+use crate::{digits::normalize_digits, error::TransitError};
+
+/// Text every maintenance page carries.
+const MARKER: &str = "メンテナンス";
+
+/// Element wrapping the message on the maintenance page.
+const CONTAINER: &str = "div.maintenance";
+
+/// Classify `body` as the maintenance page, reading the announced end time
+/// relative to `now` (JST).
+///
+/// Mentioning maintenance is not enough: the page must also be served with
+/// HTTP 503 (`status`, when known), be titled as maintenance, or carry the
+/// maintenance container. Result pages and suggest JSON are never
+/// classified, even if a banner mentions maintenance.
+pub(crate) fn maintenance_page(
+    body: &str,
+    status: Option<u16>,
+    now: DateTime<FixedOffset>,
+) -> Option<TransitError> {
+    let trimmed = body.trim_start_matches('\u{feff}').trim_start();
+    if trimmed.starts_with('{') || body.contains("__NEXT_DATA__") || !body.contains(MARKER) {
+        return None;
+    }
+    let document = Html::parse_document(body);
+    let title = Selector::parse("title").unwrap();
+    let titled = document
+        .select(&title)
+        .any(|title| title.text().any(|text| text.contains(MARKER)));
+    let container = Selector::parse(CONTAINER).unwrap();
+    if status != Some(503) && !titled && document.select(&container).next().is_none() {
+        return None;
+    }
+    let text: String = document.root_element().text().collect();
+    Some(TransitError::Maintenance {
+        until: announced_end(&text, now),
+    })
+}
+
+/// Date and time fields read from a phrase such as "12月18日 6:00".
+#[derive(Debug, Default, Clone, Copy)]
+struct Stamp {
+    year: Option<i32>,
+    month: Option<u32>,
+    day: Option<u32>,
+    hour: Option<u32>,
+    minute: Option<u32>,
+}
+
+/// The end of the window in the first "…まで" phrase that names a time.
+///
+/// For a range ("2:00～6:00まで") the part after the separator is used, with
+/// the date taken from the start when only the end time is given. A missing
+/// year is the one that puts the end closest to `now`.
+fn announced_end(text: &str, now: DateTime<FixedOffset>) -> Option<DateTime<FixedOffset>> {
+    let text = normalize_digits(text);
+    text.split("まで").find_map(|before| {
+        let phrase = before.rsplit(['。', '\n']).next().unwrap_or(before);
+        let (start, end) = match phrase.rsplit_once(['～', '〜', '~']) {
+            Some((start, end)) => (read_stamp(start), read_stamp(end)),
+            None => (Stamp::default(), read_stamp(phrase)),
+        };
+        let month = end.month.or(start.month)?;
+        let day = end.day.or(start.day)?;
+        let hour = end.hour?;
+        let minute = end.minute.unwrap_or(0);
+        let at = |year| {
+            let date = NaiveDate::from_ymd_opt(year, month, day)?;
+            // "24:00" is the end of the day
+            let (date, hour) = if hour == 24 {
+                (date.succ_opt()?, 0)
+            } else {
+                (date, hour)
+            };
+            let naive = date.and_hms_opt(hour, minute, 0)?;
+            now.timezone().from_local_datetime(&naive).single()
+        };
+        match end.year.or(start.year) {
+            Some(year) => at(year),
+            None => (now.year() - 1..=now.year() + 1)
+                .filter_map(at)
+                .min_by_key(|t| (*t - now).num_minutes().abs()),
+        }
+    })
+}
+
+/// Read year/month/day and hour:minute (or "6時30分") from a phrase.
+fn read_stamp(phrase: &str) -> Stamp {
+    let mut stamp = Stamp::default();
+    let mut chars = phrase.chars().peekable();
+    while let Some(c) = chars.next() {
+        let Some(first) = c.to_digit(10) else {
+            continue;
+        };
+        let mut n = first;
+        while let Some(d) = chars.peek().and_then(|c| c.to_digit(10)) {
+            n = n.saturating_mul(10).saturating_add(d);
+            chars.next();
+        }
+        match chars.peek() {
+            Some('年') => stamp.year = i32::try_from(n).ok(),
+            Some('月') => stamp.month = Some(n),
+            Some('日') => stamp.day = Some(n),
+            Some(':' | '時') => stamp.hour = Some(n),
+            Some('分') => stamp.minute = Some(n),
+            // minutes after ':'
+            _ if stamp.hour.is_some() && stamp.minute.is_none() => stamp.minute = Some(n),
+            _ => {}
+        }
+    }
+    stamp
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn jst(y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<FixedOffset> {
+        FixedOffset::east_opt(9 * 3600)
+            .unwrap()
+            .with_ymd_and_hms(y, m, d, h, min, 0)
+            .unwrap()
+    }
+
+    fn until(body: &str, now: DateTime<FixedOffset>) -> Option<DateTime<FixedOffset>> {
+        match maintenance_page(body, Some(200), now) {
+            Some(TransitError::Maintenance { until }) => until,
+            other => panic!("not classified as maintenance: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_maintenance_page_with_end_time() {
+        let body = include_str!("../tests/fixtures/maintenance.html");
+        let now = jst(2025, 12, 18, 3, 0);
+        assert_eq!(until(body, now), Some(jst(2025, 12, 18, 6, 0)));
+    }
+
+    #[test]
+    fn test_maintenance_page_without_end_time() {
+        let body = include_str!("../tests/fixtures/maintenance_unannounced.html");
+        assert_eq!(until(body, jst(2025, 12, 18, 3, 0)), None);
+    }
+
+    #[test]
+    fn test_announced_end_formats() {
+        let now = jst(2025, 12, 31, 22, 0);
+        let end = |text| announced_end(text, now);
+        assert_eq!(
+            end("１月１日（木）５時まで停止します"),
+            Some(jst(2026, 1, 1, 5, 0))
+        );
+        assert_eq!(
+            end("12月31日 23:00～24:00まで"),
+            Some(jst(2026, 1, 1, 0, 0))
+        );
+        assert_eq!(
+            end("2025年12月31日 21:00～12月31日 23:30まで"),
+            Some(jst(2025, 12, 31, 23, 30))
+        );
+        assert_eq!(end("終了までしばらくお待ちください"), None);
+        assert_eq!(end("6:00まで"), None);
+    }
+
+    #[test]
+    fn test_result_pages_are_not_maintenance() {
+        let now = jst(2025, 12, 18, 3, 0);
+        let page = r#"<p>メンテナンスのお知らせ</p><script id="__NEXT_DATA__">{}</script>"#;
+        assert!(maintenance_page(page, Some(200), now).is_none());
+        let json = r#"{"Result":"メンテナンス"}"#;
+        assert!(maintenance_page(json, Some(503), now).is_none());
+        let top = include_str!("../tests/fixtures/top_page.html");
+        assert!(maintenance_page(top, Some(200), now).is_none());
+    }
+
+    #[test]
+    fn test_maintenance_needs_a_strong_signal() {
+        let now = jst(2025, 12, 18, 3, 0);
+        let notice = "<title>お知らせ</title><p>12月18日 6:00までメンテナンスを行います</p>";
+        assert!(maintenance_page(notice, Some(200), now).is_none());
+        assert!(maintenance_page(notice, None, now).is_none());
+        assert_eq!(
+            maintenance_page(notice, Some(503), now),
+            Some(TransitError::Maintenance {
+                until: Some(jst(2025, 12, 18, 6, 0))
+            })
+        );
+
+        let titled = "<title>メンテナンス中</title><p>しばらくお待ちください</p>";
+        assert!(maintenance_page(titled, None, now).is_some());
+        let contained = r#"<div class="maintenance">メンテナンス中です</div>"#;
+        assert!(maintenance_page(contained, None, now).is_some());
+    }
+}
@@ -0,0 +1,225 @@
+//! Query parameter names and codes of the upstream search endpoint.
+//!
+//! The codes come from the enums' `as_str` / `as_u32` methods; the tables
+//! below list every variant with its code and back the enums' `FromStr`
+//! impls and the query builder.
+
+use crate::args::{
+    AvailableMeans, DateKind, SeatPreference, TransitCriteria, TransitTicketPreference,
+    WalkingSpeed,
+};
+
+/// Origin name.
+pub const FROM: &str = "from";
+/// Destination name.
+pub const TO: &str = "to";
+/// Year of the search date.
+pub const YEAR: &str = "y";
+/// Month of the search date (1-12, no padding).
+pub const MONTH: &str = "m";
+/// Day of the search date (1-31, no padding).
+pub const DAY: &str = "d";
+/// Hour of the search date (0-23, no padding).
+pub const HOUR: &str = "hh";
+/// Tens digit of the minute.
+pub const MINUTE_TENS: &str = "m1";
+/// Units digit of the minute.
+pub const MINUTE_UNITS: &str = "m2";
+/// [`DateKind`] code.
+pub const DATE_KIND: &str = "type";
+/// Via place; repeated once per place, in order.
+pub const VIA: &str = "via";
+/// [`TransitCriteria`] code.
+pub const CRITERIA: &str = "s";
+/// 1-based route rank.
+pub const RANK: &str = "no";
+/// [`TransitTicketPreference`] code.
+pub const TICKET: &str = "ticket";
+/// [`SeatPreference`] code.
+pub const SEAT: &str = "expkind";
+/// [`WalkingSpeed`] code.
+pub const WALKING_SPEED: &str = "ws";
+
+/// Value of a means parameter when the means is allowed.
+pub const MEANS_ON: &str = "1";
+/// Value of a means parameter when the means is excluded.
+pub const MEANS_OFF: &str = "0";
+
+/// Each [`AvailableMeans`] with its parameter name, in query order.
+pub const MEANS: [(AvailableMeans, &str); 6] = [
+    (AvailableMeans::Airlane, AvailableMeans::Airlane.as_str()),
+    (
+        AvailableMeans::Shinkansen,
+        AvailableMeans::Shinkansen.as_str(),
+    ),
+    (
+        AvailableMeans::PaidExpress,
+        AvailableMeans::PaidExpress.as_str(),
+    ),
+    (
+        AvailableMeans::HighwayBus,
+        AvailableMeans::HighwayBus.as_str(),
+    ),
+    (AvailableMeans::Bus, AvailableMeans::Bus.as_str()),
+    (AvailableMeans::Ferry, AvailableMeans::Ferry.as_str()),
+];
+
+/// Each [`DateKind`] with its `type` code.
+pub const DATE_KINDS: [(DateKind, u32); 5] = [
+    (DateKind::DepartureTime, DateKind::DepartureTime.as_u32()),
+    (DateKind::LastTrain, DateKind::LastTrain.as_u32()),
+    (DateKind::FirstTrain, DateKind::FirstTrain.as_u32()),
+    (DateKind::ArrivalTime, DateKind::ArrivalTime.as_u32()),
+    (DateKind::NotSpecified, DateKind::NotSpecified.as_u32()),
+];
+
+/// Each [`TransitCriteria`] with its `s` code.
+pub const CRITERIAS: [(TransitCriteria, u32); 3] = [
+    (
+        TransitCriteria::EarliestArrival,
+        TransitCriteria::EarliestArrival.as_u32(),
+    ),
+    (
+        TransitCriteria::LowestCost,
+        TransitCriteria::LowestCost.as_u32(),
+    ),
+    (
+        TransitCriteria::FewestTransfers,
+        TransitCriteria::FewestTransfers.as_u32(),
+    ),
+];
+
+/// Each [`SeatPreference`] with its `expkind` code.
+pub const SEATS: [(SeatPreference, u32); 3] = [
+    (
+        SeatPreference::NonReserved,
+        SeatPreference::NonReserved.as_u32(),
+    ),
+    (SeatPreference::Reserved, SeatPreference::Reserved.as_u32()),
+    (SeatPreference::GreenCar, SeatPreference::GreenCar.as_u32()),
+];
+
+/// Each [`WalkingSpeed`] with its `ws` code.
+pub const WALKING_SPEEDS: [(WalkingSpeed, u32); 4] = [
+    (WalkingSpeed::Fast, WalkingSpeed::Fast.as_u32()),
+    (WalkingSpeed::Brisk, WalkingSpeed::Brisk.as_u32()),
+    (WalkingSpeed::Leisurely, WalkingSpeed::Leisurely.as_u32()),
+    (WalkingSpeed::Slow, WalkingSpeed::Slow.as_u32()),
+];
+
+/// Each [`TransitTicketPreference`] with its `ticket` value.
+pub const TICKETS: [(TransitTicketPreference, &str); 2] = [
+    (
+        TransitTicketPreference::IC,
+        TransitTicketPreference::IC.as_str(),
+    ),
+    (
+        TransitTicketPreference::Normal,
+        TransitTicketPreference::Normal.as_str(),
+    ),
+];
+
+/// Look up the variant for `code` in one of the tables above.
+pub(crate) fn from_code<T: Clone, C: PartialEq + ?Sized>(table: &[(T, &C)], code: &C) -> Option<T> {
+    table
+        .iter()
+        .find(|(_, c)| *c == code)
+        .map(|(v, _)| v.clone())
+}
+
+/// Like [`from_code`] for numeric codes given as text.
+pub(crate) fn from_numeric_code<T: Clone>(table: &[(T, u32)], code: &str) -> Option<T> {
+    let code: u32 = code.trim().parse().ok()?;
+    table
+        .iter()
+        .find(|(_, c)| *c == code)
+        .map(|(v, _)| v.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    /// Fails to compile when a variant is added, as a reminder to extend
+    /// the table; the runtime test below checks the entry exists.
+    #[allow(dead_code)]
+    fn exhaustive(
+        means: AvailableMeans,
+        date_kind: DateKind,
+        criteria: TransitCriteria,
+        seat: SeatPreference,
+        walking: WalkingSpeed,
+        ticket: TransitTicketPreference,
+    ) {
+        match means {
+            AvailableMeans::Airlane
+            | AvailableMeans::Shinkansen
+            | AvailableMeans::PaidExpress
+            | AvailableMeans::HighwayBus
+            | AvailableMeans::Bus
+            | AvailableMeans::Ferry => {}
+        }
+        match date_kind {
+            DateKind::DepartureTime
+            | DateKind::LastTrain
+            | DateKind::FirstTrain
+            | DateKind::ArrivalTime
+            | DateKind::NotSpecified => {}
+        }
+        match criteria {
+            TransitCriteria::EarliestArrival
+            | TransitCriteria::LowestCost
+            | TransitCriteria::FewestTransfers => {}
+        }
+        match seat {
+            SeatPreference::NonReserved | SeatPreference::Reserved | SeatPreference::GreenCar => {}
+        }
+        match walking {
+            WalkingSpeed::Fast
+            | WalkingSpeed::Brisk
+            | WalkingSpeed::Leisurely
+            | WalkingSpeed::Slow => {}
+        }
+        match ticket {
+            TransitTicketPreference::IC | TransitTicketPreference::Normal => {}
+        }
+    }
+
+    fn assert_round_trip<T, C>(table: &[(T, C)])
+    where
+        T: FromStr + PartialEq + std::fmt::Debug,
+        <T as FromStr>::Err: std::fmt::Debug,
+        C: ToString,
+    {
+        for (i, (variant, code)) in table.iter().enumerate() {
+            assert_eq!(&code.to_string().parse::<T>().unwrap(), variant);
+            assert!(
+                table[..i]
+                    .iter()
+                    .all(|(_, c)| c.to_string() != code.to_string()),
+                "duplicate code {}",
+                code.to_string()
+            );
+        }
+        assert!("bogus".parse::<T>().is_err());
+    }
+
+    #[test]
+    fn test_tables_round_trip() {
+        assert_round_trip(&MEANS);
+        assert_round_trip(&DATE_KINDS);
+        assert_round_trip(&CRITERIAS);
+        assert_round_trip(&SEATS);
+        assert_round_trip(&WALKING_SPEEDS);
+        assert_round_trip(&TICKETS);
+
+        assert_eq!(
+            MEANS.map(|(_, key)| key),
+            ["al", "shin", "ex", "hb", "lb", "sr"]
+        );
+        assert_eq!(DATE_KINDS.map(|(_, code)| code), [1, 2, 3, 4, 5]);
+        assert_eq!(CRITERIAS.map(|(_, code)| code), [0, 1, 2]);
+    }
+}
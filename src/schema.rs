@@ -0,0 +1,99 @@
+//! JSON Schemas of the request and response types.
+
+use std::collections::BTreeMap;
+
+use schemars::{JsonSchema, generate::SchemaSettings};
+use serde_json::{Map, Value};
+
+use crate::{args::TransitArgs, transit_dto::TransitDto};
+
+/// JSON Schemas of [`TransitArgs`] and [`TransitDto`], keyed by type name.
+///
+/// Generated with fixed settings (JSON Schema draft 2020-12) and with
+/// object keys sorted at every level, so the output only changes when the
+/// types do, not with the `schemars` version or its `preserve_order`
+/// feature.
+pub fn schemas() -> BTreeMap<&'static str, Value> {
+    BTreeMap::from([
+        ("TransitArgs", schema_of::<TransitArgs>()),
+        ("TransitDto", schema_of::<TransitDto>()),
+    ])
+}
+
+fn schema_of<T: JsonSchema>() -> Value {
+    let schema = SchemaSettings::draft2020_12()
+        .into_generator()
+        .into_root_schema_for::<T>();
+    sort_keys(schema.to_value())
+}
+
+/// Rebuild every object with its keys in sorted order.
+fn sort_keys(value: Value) -> Value {
+    match value {
+        Value::Object(map) => {
+            let mut entries: Vec<_> = map.into_iter().collect();
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+            Value::Object(
+                entries
+                    .into_iter()
+                    .map(|(k, v)| (k, sort_keys(v)))
+                    .collect::<Map<_, _>>(),
+            )
+        }
+        Value::Array(items) => Value::Array(items.into_iter().map(sort_keys).collect()),
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Compare with `tests/fixtures/schemas/<name>.json`; run with
+    /// `UPDATE_SCHEMAS=1` to rewrite the files after a deliberate change.
+    fn assert_golden(name: &str, golden: &str) {
+        let actual = serde_json::to_string_pretty(&schemas()[name]).unwrap() + "\n";
+        if std::env::var_os("UPDATE_SCHEMAS").is_some() {
+            let path = format!(
+                "{}/tests/fixtures/schemas/{name}.json",
+                env!("CARGO_MANIFEST_DIR")
+            );
+            std::fs::write(path, &actual).unwrap();
+            return;
+        }
+        assert!(
+            actual == golden,
+            "{name} schema changed; rerun with UPDATE_SCHEMAS=1 and review the diff"
+        );
+    }
+
+    #[test]
+    fn test_transit_args_schema_golden() {
+        assert_golden(
+            "TransitArgs",
+            include_str!("../tests/fixtures/schemas/TransitArgs.json"),
+        );
+    }
+
+    #[test]
+    fn test_transit_dto_schema_golden() {
+        assert_golden(
+            "TransitDto",
+            include_str!("../tests/fixtures/schemas/TransitDto.json"),
+        );
+    }
+
+    #[test]
+    fn test_schemas_are_sorted_and_pinned() {
+        for schema in schemas().values() {
+            assert_eq!(
+                schema["$schema"],
+                "https://json-schema.org/draft/2020-12/schema"
+            );
+            let keys: Vec<_> = schema.as_object().unwrap().keys().collect();
+            let mut sorted = keys.clone();
+            sorted.sort();
+            assert_eq!(keys, sorted);
+        }
+    }
+}
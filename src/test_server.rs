@@ -0,0 +1,243 @@
+//! Minimal HTTP/1.1 server used by the client tests.
+
+use std::{
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::TcpListener,
+};
+use tokio_rustls::{
+    TlsAcceptor,
+    rustls::{
+        ServerConfig,
+        crypto::aws_lc_rs,
+        pki_types::{CertificateDer, PrivateKeyDer, pem::PemObject},
+    },
+};
+
+/// A request received by [`TestServer`].
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+    /// Path and query string, as sent on the request line.
+    pub target: String,
+    /// Header fields with lowercased names.
+    pub headers: Vec<(String, String)>,
+}
+
+impl RecordedRequest {
+    /// Value of the first header named `name` (case-insensitive).
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// A canned response.
+#[derive(Debug, Clone)]
+pub struct Reply {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Reply {
+    pub fn new(status: u16, content_type: &str, body: impl Into<Vec<u8>>) -> Self {
+        Self {
+            status,
+            headers: vec![("Content-Type".into(), content_type.into())],
+            body: body.into(),
+        }
+    }
+
+    pub fn json(body: &str) -> Self {
+        Self::new(200, "application/json; charset=utf-8", body)
+    }
+
+    /// Close the connection after reading the request, without answering,
+    /// like a server dropping a connection it considered idle.
+    pub fn hang_up() -> Self {
+        Self::new(0, "", "")
+    }
+}
+
+type Handler = dyn Fn(&RecordedRequest) -> Reply + Send + Sync;
+
+/// Serves replies from a handler on a random local port and records requests.
+pub struct TestServer {
+    url: String,
+    requests: Arc<Mutex<Vec<RecordedRequest>>>,
+    connections: Arc<AtomicUsize>,
+}
+
+impl TestServer {
+    /// Serve one request per connection.
+    pub async fn start<F>(handler: F) -> Self
+    where
+        F: Fn(&RecordedRequest) -> Reply + Send + Sync + 'static,
+    {
+        Self::spawn(None, None, Arc::new(handler)).await
+    }
+
+    /// Serve one request per connection over TLS with the given PEM
+    /// certificate and private key.
+    pub async fn start_tls<F>(cert_pem: &[u8], key_pem: &[u8], handler: F) -> Self
+    where
+        F: Fn(&RecordedRequest) -> Reply + Send + Sync + 'static,
+    {
+        let cert = CertificateDer::from_pem_slice(cert_pem).unwrap();
+        let key = PrivateKeyDer::from_pem_slice(key_pem).unwrap();
+        let config = ServerConfig::builder_with_provider(Arc::new(aws_lc_rs::default_provider()))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_no_client_auth()
+            .with_single_cert(vec![cert], key)
+            .unwrap();
+        let acceptor = TlsAcceptor::from(Arc::new(config));
+        Self::spawn(Some(acceptor), None, Arc::new(handler)).await
+    }
+
+    /// Keep connections alive, closing them after `idle` without a request.
+    pub async fn start_keep_alive<F>(idle: Duration, handler: F) -> Self
+    where
+        F: Fn(&RecordedRequest) -> Reply + Send + Sync + 'static,
+    {
+        Self::spawn(None, Some(idle), Arc::new(handler)).await
+    }
+
+    async fn spawn(
+        tls: Option<TlsAcceptor>,
+        idle: Option<Duration>,
+        handler: Arc<Handler>,
+    ) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let scheme = if tls.is_some() { "https" } else { "http" };
+        let url = format!("{scheme}://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let connections = Arc::new(AtomicUsize::new(0));
+
+        let recorded = requests.clone();
+        let accepted = connections.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                accepted.fetch_add(1, Ordering::SeqCst);
+                let recorded = recorded.clone();
+                let handler = handler.clone();
+                let tls = tls.clone();
+                tokio::spawn(async move {
+                    match tls {
+                        // A failed handshake just drops the connection.
+                        Some(tls) => {
+                            if let Ok(stream) = tls.accept(stream).await {
+                                serve(stream, idle, &recorded, handler.as_ref()).await;
+                            }
+                        }
+                        None => serve(stream, idle, &recorded, handler.as_ref()).await,
+                    }
+                });
+            }
+        });
+
+        Self {
+            url,
+            requests,
+            connections,
+        }
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// Number of accepted TCP connections.
+    pub fn connections(&self) -> usize {
+        self.connections.load(Ordering::SeqCst)
+    }
+}
+
+/// Answer requests on one connection until it is closed.
+async fn serve<S>(
+    mut stream: S,
+    idle: Option<Duration>,
+    recorded: &Mutex<Vec<RecordedRequest>>,
+    handler: &Handler,
+) where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    loop {
+        let request = match idle {
+            Some(idle) => tokio::time::timeout(idle, read_request(&mut stream))
+                .await
+                .ok()
+                .flatten(),
+            None => read_request(&mut stream).await,
+        };
+        let Some(request) = request else {
+            break;
+        };
+        recorded.lock().unwrap().push(request.clone());
+        let reply = handler(&request);
+        if reply.status == 0 {
+            break;
+        }
+        let keep_alive = idle.is_some();
+        if stream
+            .write_all(&encode_reply(&reply, keep_alive))
+            .await
+            .is_err()
+            || !keep_alive
+        {
+            break;
+        }
+    }
+    let _ = stream.shutdown().await;
+}
+
+async fn read_request<S: AsyncRead + Unpin>(stream: &mut S) -> Option<RecordedRequest> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 1024];
+    while !buf.windows(4).any(|w| w == b"\r\n\r\n") {
+        let n = stream.read(&mut chunk).await.ok()?;
+        if n == 0 {
+            return None;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+
+    let head = String::from_utf8_lossy(&buf);
+    let mut lines = head.split("\r\n");
+    let target = lines.next()?.split(' ').nth(1)?.to_string();
+    let headers = lines
+        .take_while(|line| !line.is_empty())
+        .filter_map(|line| line.split_once(':'))
+        .map(|(k, v)| (k.trim().to_ascii_lowercase(), v.trim().to_string()))
+        .collect();
+
+    Some(RecordedRequest { target, headers })
+}
+
+fn encode_reply(reply: &Reply, keep_alive: bool) -> Vec<u8> {
+    let mut out = format!("HTTP/1.1 {} Test\r\n", reply.status);
+    for (k, v) in &reply.headers {
+        out.push_str(&format!("{k}: {v}\r\n"));
+    }
+    let connection = if keep_alive { "keep-alive" } else { "close" };
+    out.push_str(&format!(
+        "Content-Length: {}\r\nConnection: {connection}\r\n\r\n",
+        reply.body.len()
+    ));
+    let mut out = out.into_bytes();
+    out.extend_from_slice(&reply.body);
+    out
+}
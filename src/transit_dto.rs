@@ -1,17 +1,31 @@
-use anyhow::{Result, anyhow};
-use chrono::{DateTime, FixedOffset, NaiveDate, NaiveTime, TimeZone};
+use anyhow::{Result, anyhow, bail};
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveTime, TimeDelta, TimeZone};
 use scraper::{Html, Selector};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
+use crate::{
+    args::{PassengerType, SeatPreference, TransitArgs, TransitCriteria},
+    digits::normalize_digits,
+    error::{PlaceField, TransitError},
+    format::FormatOptions,
+    yen::Yen,
+    yxhoo::{BASE_URL, build_transit_query, encode_query},
+};
+
 /// Parsed transit search result.
 #[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[derive(Debug, Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct TransitDto {
     /// Origin display name.
+    ///
+    /// Taken from the first of these that is not blank: the name the site
+    /// resolved the input to (`displayInfo.fromName`), the first station of
+    /// the top route, the query as typed.
     pub from: String,
-    /// Destination display name.
+    /// Destination display name, chosen like `from` but from
+    /// the last station of the top route.
     pub to: String,
     /// Search date/time with timezone, if available.
     #[serde(
@@ -25,6 +39,10 @@ pub struct TransitDto {
     pub search_date_time: Option<DateTime<FixedOffset>>,
     /// Routes returned by the search (usually 1 entry).
     pub routes: Vec<RouteDto>,
+    /// Non-fatal issues noticed while parsing (e.g. truncated routes, a
+    /// ticket preference that could not be honored).
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
 }
 
 /// A single route in the search result.
@@ -65,18 +83,66 @@ pub struct RouteSummaryDto {
         schemars(schema_with = "crate::dt_minute_tz::schema")
     )]
     pub arrival_time: Option<DateTime<FixedOffset>>,
+    /// Whether the route leaves on the day after the search date, e.g. the
+    /// 00:10 train found for a 23:55 search (the site's "翌日" badge).
+    /// Only set for departure-time searches with a departure time; a
+    /// departure more than an hour before the search time counts.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub departs_next_day: Option<bool>,
+    /// Whether the route arrives on a later calendar day than it departs.
+    /// `None` if either time is missing.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub spans_midnight: Option<bool>,
+    /// Number of calendar days the route touches, counting the departure
+    /// day (1 for a same-day route, 2 for an overnight bus). `None` if
+    /// either time is missing.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub calendar_days: Option<u32>,
     /// Total duration in minutes, if present.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub duration_minutes: Option<u32>,
+    /// Time spent on board (乗車時間), excluding walking and waiting.
+    ///
+    /// Read from the summary (`timeOnBoard`) when the page lists it;
+    /// otherwise the sum of the rail, bus, flight and ferry segment
+    /// durations. `None` if a segment's mode or one of those durations is
+    /// unknown.
+    ///
+    /// Unverified: no recorded page has a summary `timeOnBoard` yet (only
+    /// segments carry one), so in practice this is the derived sum.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub onboard_minutes: Option<u32>,
     /// Transfer count, if present.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub transfer_count: Option<u32>,
+    /// Shortest time between arriving and departing at a transfer, walking
+    /// included. `None` if the route has no transfer whose times are known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_transfer_minutes: Option<u32>,
     /// Total price in JPY, if present.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub total_price_yen: Option<u32>,
+    /// Discount already taken off `total_price_yen` (e.g. 乗継割引), if the
+    /// page states one. Explains a total below the sum of the segment fares.
+    ///
+    /// Unverified: the `discountPrice` key is assumed (only a hand-made
+    /// fixture has it); otherwise the amount comes from a discount note.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fare_discount: Option<Yen>,
     /// Distance in kilometers, if present.
+    ///
+    /// This is the single distance the page shows (`distance`). No recorded
+    /// page lists a separate fare-calculation distance (営業キロ), so only
+    /// this one is exposed; it may differ from the distance fares are
+    /// computed on.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub distance_km: Option<f64>,
+    /// CO2 emitted per passenger in grams (`co2`), when the page shows it.
+    ///
+    /// Unverified: the key and its format come from a hand-made fixture,
+    /// not a recorded page, so expect `None`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub co2_grams: Option<u32>,
     /// Fastest route flag, if present.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub is_fast: Option<bool>,
@@ -86,6 +152,232 @@ pub struct RouteSummaryDto {
     /// Cheapest route flag, if present.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub is_cheap: Option<bool>,
+    /// Convenient route flag (`isConvenient`), if present.
+    ///
+    /// Unverified: the key is assumed and no recorded page has it yet, so
+    /// this is usually `None`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_convenient: Option<bool>,
+    /// First/last service of the day marker, if present.
+    ///
+    /// Set on rank 1 of a first/last-train search. The `isFirstTrain`,
+    /// `isLastTrain` and `serviceLabel` keys are also read, but they are
+    /// unverified: only hand-made fixtures carry them.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub service_position: Option<ServicePosition>,
+    /// Caveats attached to the route (e.g. "運賃は目安です").
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub notes: Vec<String>,
+    /// Whether the fare is an estimate: the site marks it so (e.g.
+    /// "運賃は概算"), or it was computed for a child passenger.
+    ///
+    /// The `isFareEstimate` flag also read here is unverified, as only a
+    /// hand-made fixture has it; the price and note wording are used
+    /// otherwise.
+    pub fare_is_estimate: bool,
+    /// Passenger type the fares are for.
+    pub fare_passenger_type: PassengerType,
+    /// Fare basis of the total price, derived from the segment fares.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fare_basis: Option<FareBasis>,
+    /// Whether any segment has `fare_caveats`, so the total may need a
+    /// closer look.
+    pub has_fare_caveats: bool,
+}
+
+/// Which kind of fare a price is based on.
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum FareBasis {
+    /// IC card fare.
+    Ic,
+    /// Ticket (cash) fare.
+    Ticket,
+    /// A total combining IC and ticket fares.
+    Mixed,
+}
+
+/// Marks a route as the first or last feasible service of the day.
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ServicePosition {
+    /// 始発
+    FirstOfDay,
+    /// 終電
+    LastOfDay,
+    /// Another service label shown by the site.
+    Other(String),
+}
+
+/// Top route under each [`TransitCriteria`], from
+/// [`TransitClient::transit_by_all_criteria`](crate::TransitClient::transit_by_all_criteria).
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CriteriaComparison {
+    /// Distinct winning routes, in the order they first won.
+    pub routes: Vec<CriteriaRoute>,
+    /// Criteria whose search failed.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub failures: Vec<CriteriaFailure>,
+}
+
+/// A route together with every criteria it ranked first under.
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CriteriaRoute {
+    pub criteria: Vec<TransitCriteria>,
+    pub route: RouteDto,
+}
+
+/// A criteria whose search failed, with the error message.
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CriteriaFailure {
+    pub criteria: TransitCriteria,
+    pub error: String,
+}
+
+impl CriteriaComparison {
+    /// The top route for `criteria`, if its search succeeded.
+    pub fn best_for(&self, criteria: TransitCriteria) -> Option<&RouteDto> {
+        self.routes
+            .iter()
+            .find(|r| r.criteria.contains(&criteria))
+            .map(|r| &r.route)
+    }
+
+    /// Record `route` as the winner for `criteria`, merging with an
+    /// identical journey won under another criteria.
+    pub(crate) fn insert(&mut self, criteria: TransitCriteria, route: RouteDto) {
+        match self
+            .routes
+            .iter_mut()
+            .find(|r| r.route.same_journey(&route))
+        {
+            Some(existing) => existing.criteria.push(criteria),
+            None => self.routes.push(CriteriaRoute {
+                criteria: vec![criteria],
+                route,
+            }),
+        }
+    }
+}
+
+/// Fares of one route under each seat class, from
+/// [`TransitClient::compare_seat_fares`](crate::TransitClient::compare_seat_fares).
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SeatFareComparison {
+    /// One entry per searched class, [`SeatPreference::NonReserved`] first.
+    /// Reserved and green-car classes are only searched when the
+    /// non-reserved route has a limited express or Shinkansen leg.
+    pub fares: Vec<SeatFare>,
+}
+
+/// The route found for one seat class.
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SeatFare {
+    pub seat: SeatPreference,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_price_yen: Option<u32>,
+    /// `false` when this class was answered with a different journey than
+    /// the non-reserved search, so the fares are not comparable.
+    pub same_route: bool,
+    /// `total_price_yen` minus the non-reserved total; `None` when either
+    /// total is unknown or `same_route` is `false`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delta_yen: Option<i64>,
+    pub route: RouteDto,
+}
+
+impl SeatFareComparison {
+    /// Compare `others` against the non-reserved route `base`.
+    pub(crate) fn new(base: RouteDto, others: Vec<(SeatPreference, RouteDto)>) -> Self {
+        let base_yen = base.summary.total_price_yen;
+        let mut fares = vec![SeatFare {
+            seat: SeatPreference::NonReserved,
+            total_price_yen: base_yen,
+            same_route: true,
+            delta_yen: base_yen.map(|_| 0),
+            route: base.clone(),
+        }];
+        for (seat, route) in others {
+            let total_price_yen = route.summary.total_price_yen;
+            let same_route = route.same_journey(&base);
+            let delta_yen = match (same_route, total_price_yen, base_yen) {
+                (true, Some(yen), Some(base_yen)) => Some(i64::from(yen) - i64::from(base_yen)),
+                _ => None,
+            };
+            fares.push(SeatFare {
+                seat,
+                total_price_yen,
+                same_route,
+                delta_yen,
+                route,
+            });
+        }
+        Self { fares }
+    }
+
+    /// The entry for `seat`, if that class was searched.
+    pub fn get(&self, seat: SeatPreference) -> Option<&SeatFare> {
+        self.fares.iter().find(|f| f.seat == seat)
+    }
+}
+
+/// Minimal route digest for push notifications.
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NotificationSummary {
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        with = "crate::dt_minute_tz::option"
+    )]
+    #[cfg_attr(
+        feature = "schemars",
+        schemars(schema_with = "crate::dt_minute_tz::schema")
+    )]
+    pub departure_time: Option<DateTime<FixedOffset>>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        with = "crate::dt_minute_tz::option"
+    )]
+    #[cfg_attr(
+        feature = "schemars",
+        schemars(schema_with = "crate::dt_minute_tz::schema")
+    )]
+    pub arrival_time: Option<DateTime<FixedOffset>>,
+    /// Line of the first non-walking segment.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub first_line: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transfer_count: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_price_yen: Option<u32>,
+}
+
+/// Reserved seat availability as shown by the site.
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SeatAvailability {
+    /// ○
+    Available,
+    /// △
+    Few,
+    /// ×
+    Full,
+    /// Any other marker (e.g. "－").
+    Unknown,
 }
 
 /// A segment within a route (rail, walk, bus, etc.).
@@ -104,9 +396,21 @@ pub struct SegmentDto {
     /// Line name, if present.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub line: Option<String>,
+    /// Operating company (`companyName`), if present.
+    ///
+    /// Unverified: the key is assumed and no recorded page has it, so
+    /// operator filters usually match the line name instead.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub operator: Option<String>,
     /// Destination/terminus name, if present.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub destination: Option<String>,
+    /// Named train service (e.g. "のぞみ"), split out of `line`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub train_name: Option<String>,
+    /// Train number in ASCII digits (e.g. "225"), split out of `line`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub train_number: Option<String>,
 
     /// Segment duration in minutes, if present.
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -114,6 +418,26 @@ pub struct SegmentDto {
     /// Segment fare in JPY, if present.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub fare_yen: Option<u32>,
+    /// Fare basis of `fare_yen` (`Ic` or `Ticket`), if the page says.
+    ///
+    /// Unverified: read from `priceType` / `isIcPrice`, which only
+    /// hand-made fixtures carry, so this is usually `None`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fare_basis: Option<FareBasis>,
+    /// Caveats attached to the fare that change what `fare_yen` means
+    /// (e.g. "繁忙期料金", "指定席満席のため自由席").
+    ///
+    /// Unverified: read from `priceInfo` keys (`caveatList`, `caveat`,
+    /// `comment`, ...) that only hand-made fixtures carry, so this is
+    /// usually empty.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub fare_caveats: Vec<String>,
+    /// Reserved seat availability (空席情報), if shown for this segment.
+    ///
+    /// Unverified: the `vacancy` key is assumed from a hand-made fixture,
+    /// so expect `None`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seat_availability: Option<SeatAvailability>,
 
     // nullable
     /// Departure time with timezone, if present.
@@ -127,6 +451,9 @@ pub struct SegmentDto {
     )]
     pub departure_time: Option<DateTime<FixedOffset>>,
     /// Arrival time with timezone, if present.
+    ///
+    /// At a transfer station this is the arriving train's time, read apart
+    /// from the next segment's departure, so the two give the wait.
     #[serde(
         skip_serializing_if = "Option::is_none",
         with = "crate::dt_minute_tz::option"
@@ -136,211 +463,1737 @@ pub struct SegmentDto {
         schemars(schema_with = "crate::dt_minute_tz::schema")
     )]
     pub arrival_time: Option<DateTime<FixedOffset>>,
+    /// Additional arrival times listed for the final stop, if any.
+    ///
+    /// Only populated on the last segment of a route; on intermediate stops
+    /// the extra `timeInfo` entries are the next departure instead.
+    #[serde(
+        skip_serializing_if = "Vec::is_empty",
+        serialize_with = "crate::dt_minute_tz::vec::serialize"
+    )]
+    #[cfg_attr(
+        feature = "schemars",
+        schemars(schema_with = "crate::dt_minute_tz::vec::schema")
+    )]
+    pub alternate_arrival_times: Vec<DateTime<FixedOffset>>,
+    /// Annotations on this segment (car position advice, fare caveats, ...).
+    ///
+    /// Unverified: of the keys read, `annotationList`, `annotation` and
+    /// `ridePosition` are only seen in hand-made fixtures.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub notes: Vec<String>,
+    /// Recommended car to board for the next transfer (車両位置), if shown.
+    ///
+    /// Unverified: read from `ridePosition`, like [`notes`](Self::notes).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub boarding_position: Option<BoardingPosition>,
 }
 
-pub fn load_next_data(input: &str) -> Result<Value> {
-    let s = input.trim_start();
+/// Where to board a train, as advised by the site.
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum BoardingPosition {
+    /// 前方
+    Front,
+    /// 中ほど
+    Middle,
+    /// 後方
+    Rear,
+    /// A specific car number (e.g. "8号車付近").
+    Car(u8),
+    /// Any other advice text.
+    Other(String),
+}
 
-    // allow JSON
-    if s.starts_with('{') {
-        return Ok(serde_json::from_str::<Value>(input)?);
-    }
+/// A transfer shorter than the threshold given to
+/// [`RouteDto::tight_transfers`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TightTransfer {
+    /// Index of the segment arriving at the transfer.
+    pub arriving_segment: usize,
+    /// Index of the segment departing from it. Walks in between are part of
+    /// the transfer.
+    pub departing_segment: usize,
+    /// Where the departing segment starts.
+    pub station: String,
+    /// Minutes from arrival to departure.
+    pub wait_minutes: u32,
+}
 
-    let doc = Html::parse_document(input);
-    let sel = Selector::parse(r#"script#__NEXT_DATA__"#).unwrap();
-    let json_text = doc
-        .select(&sel)
-        .next()
-        .ok_or_else(|| anyhow!("__NEXT_DATA__ not found in HTML"))?
-        .inner_html();
+/// A departure from or arrival at a station, from [`RouteDto::stop_events`].
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StopEvent {
+    /// When it happens, if the page gives the time.
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        with = "crate::dt_minute_tz::option"
+    )]
+    #[cfg_attr(
+        feature = "schemars",
+        schemars(schema_with = "crate::dt_minute_tz::schema")
+    )]
+    pub time: Option<DateTime<FixedOffset>>,
+    /// Station (or place) name.
+    pub station: String,
+    pub kind: StopEventKind,
+    /// Line of the segment that departs or arrives, if known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<String>,
+}
 
-    Ok(serde_json::from_str::<Value>(&json_text)?)
+/// Whether a [`StopEvent`] leaves or reaches its station.
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum StopEventKind {
+    Departure,
+    Arrival,
 }
 
-pub fn next_data_to_transit_dto(root: &Value) -> Result<TransitDto> {
-    let page_props = &root["props"]["pageProps"];
-    let navi = &page_props["naviSearchParam"];
+impl SegmentDto {
+    /// Deterministic id for this segment at `index` within the route of
+    /// `rank`, as 16 hex digits.
+    ///
+    /// Derived from rank, index, from/to and line only, so it is stable
+    /// across identical parses and crate versions (FNV-1a, not `std`'s
+    /// hasher). Computed on demand rather than serialized.
+    pub fn id(&self, rank: u32, index: usize) -> String {
+        const OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+        const PRIME: u64 = 0x0000_0100_0000_01b3;
 
-    let from = navi["displayInfo"]["fromName"]
-        .as_str()
-        .or_else(|| page_props["pageQuery"]["from"].as_str())
-        .unwrap_or("")
-        .to_string();
+        let index = index as u64;
+        let mut hash = OFFSET;
+        let mut feed = |bytes: &[u8]| {
+            for b in bytes {
+                hash = (hash ^ u64::from(*b)).wrapping_mul(PRIME);
+            }
+            // field separator
+            hash = (hash ^ 0xff).wrapping_mul(PRIME);
+        };
+        feed(&rank.to_le_bytes());
+        feed(&index.to_le_bytes());
+        feed(self.from.as_bytes());
+        feed(self.to.as_bytes());
+        feed(self.line.as_deref().unwrap_or("").as_bytes());
+        format!("{hash:016x}")
+    }
 
-    let to = navi["displayInfo"]["toName"]
-        .as_str()
-        .or_else(|| page_props["pageQuery"]["to"].as_str())
-        .unwrap_or("")
-        .to_string();
+    /// Whether this segment is run by `operator`.
+    ///
+    /// Compares against [`operator`](Self::operator) by substring, or the
+    /// start of the line name when the operator is unknown (so "東急"
+    /// matches "東急東横線"). Fullwidth letters and case are ignored, so
+    /// "JR" matches "ＪＲ山手線". Walking segments never match.
+    pub fn is_operated_by(&self, operator: &str) -> bool {
+        let wanted = fold_width(operator);
+        if wanted.is_empty() || self.mode == "walk" {
+            return false;
+        }
+        match (&self.operator, &self.line) {
+            (Some(op), _) => fold_width(op).contains(&wanted),
+            (None, Some(line)) => fold_width(line).starts_with(&wanted),
+            (None, None) => false,
+        }
+    }
 
-    let search_date_time = build_search_datetime(&page_props["pageQuery"]);
-    let base_date = search_date_time.as_ref();
+    /// [`fare_yen`](Self::fare_yen) as [`Yen`].
+    pub fn fare(&self) -> Option<Yen> {
+        self.fare_yen.map(Yen)
+    }
 
-    let features = navi["featureInfoList"]
-        .as_array()
-        .ok_or_else(|| anyhow!("featureInfoList missing"))?;
+    /// Whether this is a limited express or Shinkansen leg, i.e. one that
+    /// [`SeatPreference`] can change.
+    ///
+    /// The seat preference of a search applies to every such leg of the
+    /// route at once; use this to show which legs it affects. Commuter
+    /// services merely ranked 特急 (`"京王線特急"`) are not reservable;
+    /// only named trains count.
+    pub fn is_reservable(&self) -> bool {
+        self.mode == "rail"
+            && (self.train_name.is_some()
+                || self.seat_availability.is_some()
+                || self
+                    .line
+                    .as_deref()
+                    .is_some_and(|l| l.contains("新幹線") || is_named_limited_express(l)))
+    }
+}
 
-    let mut routes = Vec::new();
+/// Trim, map fullwidth ASCII to halfwidth and lowercase, for loose name
+/// comparison.
+fn fold_width(s: &str) -> String {
+    s.trim()
+        .chars()
+        .map(|c| match c {
+            // fullwidth forms sit 0xFEE0 above ASCII
+            '！'..='～' => char::from_u32(c as u32 - 0xFEE0).unwrap_or(c),
+            _ => c,
+        })
+        .flat_map(char::to_lowercase)
+        .collect()
+}
 
-    for (idx, feature) in features.iter().enumerate() {
-        let summary = &feature["summaryInfo"];
-        let v = vec![];
-        let edges = feature["edgeInfoList"].as_array().unwrap_or(&v);
+impl RouteSummaryDto {
+    /// Discrepancy of [`duration_from_times`](Self::duration_from_times),
+    /// in minutes either way, above which
+    /// [`TransitClient::transit_with_diagnostics`](crate::TransitClient::transit_with_diagnostics)
+    /// adds a warning.
+    pub const DURATION_MISMATCH_WARN_MINUTES: i64 = 5;
 
-        let departure_time = summary
-            .get("departureTime")
-            .and_then(as_nonempty_str)
-            .and_then(|s| base_date.and_then(|dt| time_on_date_with_rollover(dt, s, None)));
+    /// Minutes between `departure_time` and `arrival_time` minus
+    /// `duration_minutes`, or `None` if any of them is missing.
+    ///
+    /// Zero when the fields agree. A large value usually means a misparsed
+    /// duration or a wrong day rollover on one of the times.
+    pub fn duration_from_times(&self) -> Option<i64> {
+        let elapsed = (self.arrival_time? - self.departure_time?).num_minutes();
+        Some(elapsed - i64::from(self.duration_minutes?))
+    }
 
-        let arrival_time = summary
-            .get("arrivalTime")
-            .and_then(as_nonempty_str)
-            .and_then(|s| {
-                base_date.and_then(|dt| time_on_date_with_rollover(dt, s, departure_time))
-            });
+    /// [`total_price_yen`](Self::total_price_yen) as [`Yen`].
+    pub fn total_fare(&self) -> Option<Yen> {
+        self.total_price_yen.map(Yen)
+    }
 
-        let route_summary = RouteSummaryDto {
-            departure_time,
-            arrival_time,
-            duration_minutes: summary
-                .get("totalTime")
-                .and_then(|v| v.as_str())
-                .and_then(parse_ja_duration_minutes),
-            transfer_count: summary
-                .get("transferCount")
-                .and_then(|v| v.as_str())
-                .and_then(parse_u32_loose),
-            total_price_yen: summary
-                .get("totalPrice")
-                .and_then(|v| v.as_str())
-                .and_then(parse_u32_loose),
-            distance_km: summary
-                .get("distance")
-                .and_then(|v| v.as_str())
-                .and_then(parse_distance_km),
-            is_fast: summary.get("isFast").and_then(|v| v.as_bool()),
-            is_easy: summary.get("isEasy").and_then(|v| v.as_bool()),
-            is_cheap: summary.get("isCheap").and_then(|v| v.as_bool()),
-        };
+    /// The total fare for `passengers` people, or `None` if the fare is
+    /// unknown or the product overflows.
+    pub fn total_fare_for(&self, passengers: u32) -> Option<Yen> {
+        self.total_fare()?.checked_mul(passengers)
+    }
 
-        let segments = build_segments_from_edges(edges, base_date);
+    /// One-line summary such as "09:32→09:45 (13分, 乗換0回, 200円)".
+    /// Missing values are left out.
+    pub fn format(&self, options: &FormatOptions) -> String {
+        let time = |t: Option<DateTime<FixedOffset>>| {
+            t.map(|t| t.format("%H:%M").to_string()).unwrap_or_default()
+        };
+        let details: Vec<String> = [
+            self.duration_minutes.map(|m| options.duration(m)),
+            self.transfer_count.map(|n| options.transfers(n)),
+            self.total_price_yen.map(|yen| options.fare(yen)),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
 
-        routes.push(RouteDto {
-            rank: (idx as u32) + 1,
-            summary: route_summary,
-            segments,
-        });
+        let mut out = format!("{}→{}", time(self.departure_time), time(self.arrival_time));
+        if !details.is_empty() {
+            out.push_str(&format!(" ({})", details.join(", ")));
+        }
+        out
     }
-
-    Ok(TransitDto {
-        from,
-        to,
-        search_date_time,
-        routes,
-    })
 }
 
-fn build_segments_from_edges(
-    edges: &[Value],
-    base_date: Option<&DateTime<FixedOffset>>,
-) -> Vec<SegmentDto> {
-    let mut out = Vec::new();
+impl RouteDto {
+    /// [`SegmentDto::id`] of each segment, in order.
+    pub fn segment_ids(&self) -> Vec<String> {
+        self.segments
+            .iter()
+            .enumerate()
+            .map(|(i, s)| s.id(self.rank, i))
+            .collect()
+    }
 
-    if edges.len() < 2 {
-        return out;
+    /// Whether any segment is run by `operator`
+    /// (see [`SegmentDto::is_operated_by`]).
+    pub fn uses_operator(&self, operator: &str) -> bool {
+        self.segments.iter().any(|s| s.is_operated_by(operator))
     }
 
-    let mut last_time: Option<DateTime<FixedOffset>> = None;
+    /// Mermaid flowchart of this route: one node per station, one edge per
+    /// segment labeled with its line and times. Walking segments are drawn
+    /// dotted.
+    pub fn to_mermaid(&self) -> String {
+        self.to_mermaid_with(&FormatOptions::default())
+    }
 
-    for i in 0..(edges.len() - 1) {
-        let cur = &edges[i];
-        let next = &edges[i + 1];
+    /// [`to_mermaid`](Self::to_mermaid) with the labels in `options`.
+    pub fn to_mermaid_with(&self, options: &FormatOptions) -> String {
+        use std::fmt::Write;
 
-        let from = cur
-            .get("stationName")
-            .and_then(|v| v.as_str())
-            .unwrap_or("")
-            .to_string();
-        let to = next
-            .get("stationName")
-            .and_then(|v| v.as_str())
-            .unwrap_or("")
-            .to_string();
+        fn escape(s: &str) -> String {
+            s.replace('"', "#quot;")
+        }
 
-        let line = cur
-            .get("railNameExcludingDestination")
-            .and_then(|v| as_nonempty_str(v))
-            .or_else(|| cur.get("railName").and_then(|v| as_nonempty_str(v)))
-            .map(str::to_string);
+        let mut out = String::from("flowchart LR\n");
+        let mut node = 0;
+        let mut last_station: Option<&str> = None;
+        for segment in &self.segments {
+            if last_station != Some(segment.from.as_str()) {
+                if last_station.is_some() {
+                    node += 1;
+                }
+                let _ = writeln!(out, "    n{node}[\"{}\"]", escape(&segment.from));
+            }
+            let _ = writeln!(out, "    n{}[\"{}\"]", node + 1, escape(&segment.to));
 
-        let destination = cur
-            .get("destination")
-            .and_then(|v| as_nonempty_str(v))
-            .map(str::to_string);
+            let mut label = match (segment.mode.as_str(), &segment.line) {
+                ("walk", _) => options.walk().to_string(),
+                (_, Some(line)) => escape(line),
+                (_, None) => String::new(),
+            };
+            let time = |t: Option<DateTime<FixedOffset>>| {
+                t.map(|t| t.format("%H:%M").to_string()).unwrap_or_default()
+            };
+            if segment.departure_time.is_some() || segment.arrival_time.is_some() {
+                if !label.is_empty() {
+                    label.push_str("<br/>");
+                }
+                let _ = write!(
+                    label,
+                    "{}-{}",
+                    time(segment.departure_time),
+                    time(segment.arrival_time)
+                );
+            }
+            let arrow = if segment.mode == "walk" {
+                "-.->"
+            } else {
+                "-->"
+            };
+            let _ = writeln!(out, "    n{node} {arrow}|\"{label}\"| n{}", node + 1);
 
-        let mode = infer_mode(line.as_deref());
+            node += 1;
+            last_station = Some(segment.to.as_str());
+        }
+        out
+    }
 
-        let duration_minutes = cur
-            .get("timeOnBoard")
-            .and_then(|v| v.as_str())
-            .and_then(parse_u32_loose);
+    /// Whether any leg is a limited express or Shinkansen, i.e. the seat
+    /// preference can change the fare.
+    pub(crate) fn offers_seat_classes(&self) -> bool {
+        self.segments.iter().any(SegmentDto::is_reservable)
+    }
 
-        let fare_yen = cur
-            .get("priceInfo")
-            .and_then(|p| p.get("price"))
-            .and_then(|v| v.as_str())
-            .and_then(parse_u32_loose);
-
-        let departure_time = cur
-            .get("timeInfo")
-            .and_then(|v| v.as_array())
-            .and_then(|arr| arr.first())
-            .and_then(|x| x.get("time"))
-            .and_then(|t| as_nonempty_str(t))
-            .and_then(|s| base_date.and_then(|dt| time_on_date_with_rollover(dt, s, last_time)));
-        if let Some(dt) = departure_time {
-            last_time = Some(dt);
-        }
+    /// Whether both routes take the same services at the same times,
+    /// ignoring rank and summary badges.
+    pub(crate) fn same_journey(&self, other: &RouteDto) -> bool {
+        self.segments.len() == other.segments.len()
+            && self.segments.iter().zip(&other.segments).all(|(a, b)| {
+                a.from == b.from
+                    && a.to == b.to
+                    && a.line == b.line
+                    && a.departure_time == b.departure_time
+                    && a.arrival_time == b.arrival_time
+            })
+    }
 
-        let arrival_time = next
-            .get("timeInfo")
-            .and_then(|v| v.as_array())
-            .and_then(|arr| arr.first())
-            .and_then(|x| x.get("time"))
-            .and_then(|t| as_nonempty_str(t))
-            .and_then(|s| base_date.and_then(|dt| time_on_date_with_rollover(dt, s, last_time)));
-        if let Some(dt) = arrival_time {
-            last_time = Some(dt);
+    /// Public result page URL showing this route for the search `args`.
+    ///
+    /// Uses the same query as the search itself, with `no=` set to this
+    /// route's rank.
+    pub fn web_url(&self, args: &TransitArgs) -> String {
+        let mut query = build_transit_query(args);
+        for (key, value) in &mut query {
+            if key == "no" {
+                *value = self.rank.to_string();
+            }
         }
+        format!("{}/search/result?{}", *BASE_URL, encode_query(&query))
+    }
 
-        out.push(SegmentDto {
-            mode,
-            from,
-            to,
-            line,
-            destination,
-            duration_minutes,
-            fare_yen,
-            departure_time,
-            arrival_time,
-        });
+    /// Transfer time below which [`tight_transfers`](Self::tight_transfers)
+    /// is usually asked to flag a route.
+    pub const DEFAULT_TIGHT_TRANSFER_MINUTES: u32 = 3;
+
+    /// Transfers with less than `threshold_minutes` between arriving and
+    /// departing, walking included, e.g. to keep only safe routes.
+    ///
+    /// A transfer whose times are unknown is never flagged; see
+    /// [`transfers_with_unknown_wait`](Self::transfers_with_unknown_wait).
+    pub fn tight_transfers(&self, threshold_minutes: u32) -> Vec<TightTransfer> {
+        transfer_waits(&self.segments)
+            .into_iter()
+            .filter_map(|(arriving, departing, wait)| {
+                let wait = wait.filter(|&w| w < threshold_minutes)?;
+                Some(TightTransfer {
+                    arriving_segment: arriving,
+                    departing_segment: departing,
+                    station: self.segments[departing].from.clone(),
+                    wait_minutes: wait,
+                })
+            })
+            .collect()
     }
 
-    out
+    /// `(arriving_segment, departing_segment)` of each transfer whose time
+    /// cannot be computed because a time is missing.
+    pub fn transfers_with_unknown_wait(&self) -> Vec<(usize, usize)> {
+        transfer_waits(&self.segments)
+            .into_iter()
+            .filter(|(_, _, wait)| wait.is_none())
+            .map(|(arriving, departing, _)| (arriving, departing))
+            .collect()
+    }
+
+    /// The route as timed stops: each segment's departure followed by its
+    /// arrival, in route order (which is chronological).
+    ///
+    /// Every segment contributes both events, walks included. An event whose
+    /// time the page leaves out keeps its place with `time: None`, so
+    /// exporters can decide whether to skip it.
+    pub fn stop_events(&self) -> Vec<StopEvent> {
+        self.segments
+            .iter()
+            .flat_map(|s| {
+                [
+                    (StopEventKind::Departure, s.departure_time, &s.from),
+                    (StopEventKind::Arrival, s.arrival_time, &s.to),
+                ]
+                .map(|(kind, time, station)| StopEvent {
+                    time,
+                    station: station.clone(),
+                    kind,
+                    line: s.line.clone(),
+                })
+            })
+            .collect()
+    }
+
+    /// Earliest and latest plausible arrival `(min, max)` for this route.
+    ///
+    /// Considers the summary arrival, the last segment's arrival and its
+    /// [`SegmentDto::alternate_arrival_times`]. Without alternates both
+    /// values are equal. Returns `None` if no arrival time is known.
+    pub fn arrival_window(&self) -> Option<(DateTime<FixedOffset>, DateTime<FixedOffset>)> {
+        let last = self.segments.last();
+        let candidates = self
+            .summary
+            .arrival_time
+            .into_iter()
+            .chain(last.and_then(|s| s.arrival_time))
+            .chain(
+                last.into_iter()
+                    .flat_map(|s| s.alternate_arrival_times.iter().copied()),
+            );
+
+        candidates.fold(None, |acc, t| match acc {
+            None => Some((t, t)),
+            Some((min, max)) => Some((min.min(t), max.max(t))),
+        })
+    }
 }
 
-fn infer_mode(line: Option<&str>) -> String {
-    let s = line.unwrap_or("");
-    if s.contains("徒歩") {
-        "walk".to_string()
-    } else if s.contains("空路")
-        || s.contains("フライト")
-        || s.contains("飛行機")
-        || s.contains("ＡＮＡ")
-        || s.contains("ＪＡＬ")
-    {
-        "flight".to_string()
-    } else if s.contains("バス") || s.contains("連絡バス") || s.contains("高速") {
+/// `(arriving, departing, minutes)` for each pair of consecutive non-walk
+/// segments. Minutes are `None` if a time is missing or the departure is
+/// before the arrival.
+fn transfer_waits(segments: &[SegmentDto]) -> Vec<(usize, usize, Option<u32>)> {
+    let rides: Vec<usize> = (0..segments.len())
+        .filter(|&i| segments[i].mode != "walk")
+        .collect();
+    rides
+        .windows(2)
+        .map(|pair| {
+            let (arriving, departing) = (pair[0], pair[1]);
+            let wait = segments[arriving]
+                .arrival_time
+                .zip(segments[departing].departure_time)
+                .and_then(|(arrival, departure)| {
+                    u32::try_from((departure - arrival).num_minutes()).ok()
+                });
+            (arriving, departing, wait)
+        })
+        .collect()
+}
+
+/// Pick the route with the highest `score`.
+///
+/// Routes scoring NaN are ignored; returns `None` if `routes` is empty or
+/// every score is NaN. On ties the earlier route wins.
+pub fn select_best<F: Fn(&RouteDto) -> f64>(routes: &[RouteDto], score: F) -> Option<&RouteDto> {
+    routes
+        .iter()
+        .map(|r| (r, score(r)))
+        .filter(|(_, s)| !s.is_nan())
+        .fold(None, |best: Option<(&RouteDto, f64)>, (r, s)| match best {
+            Some((_, b)) if b >= s => best,
+            _ => Some((r, s)),
+        })
+        .map(|(r, _)| r)
+}
+
+/// Drop routes using any of `operators`, keeping the order of the rest.
+///
+/// Kept routes keep their site rank, so [`RouteDto::web_url`] still opens
+/// them; ranks may therefore have gaps.
+pub fn avoid_operators(routes: Vec<RouteDto>, operators: &[String]) -> Vec<RouteDto> {
+    routes
+        .into_iter()
+        .filter(|r| !operators.iter().any(|op| r.uses_operator(op)))
+        .collect()
+}
+
+/// Keep the routes with at least one ride whose every non-walking segment
+/// is run by one of `operators`, in their order and with their site rank,
+/// as [`avoid_operators`] does.
+///
+/// An empty `operators` keeps every route.
+pub fn only_operators(routes: Vec<RouteDto>, operators: &[String]) -> Vec<RouteDto> {
+    if operators.is_empty() {
+        return routes;
+    }
+    routes
+        .into_iter()
+        .filter(|r| {
+            let mut rides = r.segments.iter().filter(|s| s.mode != "walk").peekable();
+            rides.peek().is_some() && rides.all(|s| operators.iter().any(|op| s.is_operated_by(op)))
+        })
+        .collect()
+}
+
+/// Child fare (小児運賃) for an adult fare of a JR (`jr`) or other operator.
+///
+/// IC fares are halved, dropping fractions of 1 yen. Ticket fares are
+/// halved and rounded to 10 yen: down on JR, up elsewhere (170 yen is 80 on
+/// JR but 90 on most private railways).
+pub fn child_fare(adult: Yen, basis: Option<FareBasis>, jr: bool) -> Yen {
+    Yen(match basis {
+        Some(FareBasis::Ic) => adult.0 / 2,
+        _ if jr => adult.0 / 2 / 10 * 10,
+        _ => adult.0.div_ceil(20) * 10,
+    })
+}
+
+/// Serializes the wrapped value with every datetime as JST wall-clock time
+/// without an offset ("2025-12-18T09:30" instead of
+/// "2025-12-18T09:30+09:00"), for consumers that cannot handle offsets.
+///
+/// Lossy: the output no longer says which timezone it is in and cannot be
+/// deserialized back, since parsing still requires an offset. Works with
+/// any serializer.
+#[derive(Debug, Clone, Copy)]
+pub struct NaiveLocalTimes<T>(pub T);
+
+impl<T: Serialize> Serialize for NaiveLocalTimes<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        crate::dt_minute_tz::with_naive_local(|| self.0.serialize(serializer))
+    }
+}
+
+/// Fare limit for [`TransitClient::transit_under_budget`](crate::TransitClient::transit_under_budget).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Budget {
+    /// Highest allowed `total_price_yen`, inclusive.
+    pub max_yen: u32,
+    /// Keep routes whose fare is unknown instead of treating them as over
+    /// budget (default: `false`).
+    pub keep_unknown_fares: bool,
+}
+
+impl Budget {
+    /// A limit of `max_yen` that drops routes with an unknown fare.
+    pub const fn new(max_yen: u32) -> Self {
+        Self {
+            max_yen,
+            keep_unknown_fares: false,
+        }
+    }
+
+    /// Whether `route` fits the budget.
+    pub fn allows(&self, route: &RouteDto) -> bool {
+        match route.summary.total_price_yen {
+            Some(yen) => yen <= self.max_yen,
+            None => self.keep_unknown_fares,
+        }
+    }
+
+    /// Keep the routes that fit, ordered best first for `criteria`.
+    ///
+    /// Routes missing the compared value sort last; ties keep their input
+    /// order.
+    pub fn filter(
+        &self,
+        routes: impl IntoIterator<Item = RouteDto>,
+        criteria: TransitCriteria,
+    ) -> Vec<RouteDto> {
+        let mut routes: Vec<_> = routes.into_iter().filter(|r| self.allows(r)).collect();
+        routes.sort_by_key(|r| {
+            let s = &r.summary;
+            let arrival = s.arrival_time.map(|t| t.timestamp());
+            match criteria {
+                TransitCriteria::EarliestArrival => (last_if_none(arrival), None),
+                TransitCriteria::LowestCost => (
+                    last_if_none(s.total_price_yen.map(i64::from)),
+                    Some(last_if_none(arrival)),
+                ),
+                TransitCriteria::FewestTransfers => (
+                    last_if_none(s.transfer_count.map(i64::from)),
+                    Some(last_if_none(arrival)),
+                ),
+            }
+        });
+        routes
+    }
+}
+
+impl From<u32> for Budget {
+    fn from(max_yen: u32) -> Self {
+        Self::new(max_yen)
+    }
+}
+
+/// Sort key placing `None` after every value.
+fn last_if_none(value: Option<i64>) -> (bool, Option<i64>) {
+    (value.is_none(), value)
+}
+
+/// Version of the newest frozen serialization view (see [`TransitDto::to_json_v1`]).
+///
+/// Bump this when a new `to_json_vN` view is added.
+pub const fn dto_version() -> u32 {
+    1
+}
+
+/// Revision of the parser's behavior, recorded by
+/// [`TransitDto::to_versioned_json`].
+///
+/// Bump this whenever a change to `next_data_to_transit_dto_with` makes the
+/// same page parse differently.
+pub const PARSER_REVISION: u32 = 12;
+
+impl TransitDto {
+    /// Replace the adult fares with estimated child fares ([`child_fare`])
+    /// and mark them with `fare_is_estimate` and
+    /// [`PassengerType::Child`]. Routes already converted are left alone.
+    ///
+    /// Each segment fare is halved on its own, as every operator rounds its
+    /// own fare. The total is the sum of those when every segment fare is
+    /// known and there is no discount, else the halved adult total, rounded
+    /// the JR way only when every ride is on JR.
+    pub fn estimate_child_fares(&mut self) {
+        for route in &mut self.routes {
+            let summary = &mut route.summary;
+            if summary.fare_passenger_type == PassengerType::Child {
+                continue;
+            }
+            let mut rides = route.segments.iter().filter(|s| s.mode != "walk");
+            let all_jr = rides.clone().next().is_some() && rides.all(|s| s.is_operated_by("JR"));
+            for segment in &mut route.segments {
+                let jr = segment.is_operated_by("JR");
+                segment.fare_yen = segment
+                    .fare_yen
+                    .map(|yen| child_fare(Yen(yen), segment.fare_basis, jr).0);
+            }
+            let segment_sum = route
+                .segments
+                .iter()
+                .map(|s| s.fare_yen)
+                .sum::<Option<u32>>()
+                .filter(|_| summary.fare_discount.is_none());
+            summary.total_price_yen = match (segment_sum, summary.total_price_yen) {
+                (Some(sum), Some(_)) => Some(sum),
+                (_, total) => total.map(|yen| child_fare(Yen(yen), summary.fare_basis, all_jr).0),
+            };
+            summary.fare_discount = summary.fare_discount.map(|yen| Yen(yen.0 / 2));
+            summary.fare_is_estimate = true;
+            summary.fare_passenger_type = PassengerType::Child;
+        }
+    }
+
+    /// [`unknown_segment_ratio`](Self::unknown_segment_ratio) above which
+    /// [`TransitClient::transit_with_diagnostics`](crate::TransitClient::transit_with_diagnostics)
+    /// adds a warning.
+    pub const UNKNOWN_SEGMENT_WARN_RATIO: f64 = 0.5;
+
+    /// Share of segments across all routes whose mode is `"unknown"`
+    /// (0.0 when there are no segments).
+    ///
+    /// Such segments have no line name, so a high ratio usually means the
+    /// page markup changed and line names are no longer found.
+    pub fn unknown_segment_ratio(&self) -> f64 {
+        let (unknown, total) = self
+            .routes
+            .iter()
+            .flat_map(|r| &r.segments)
+            .fold((0usize, 0usize), |(unknown, total), s| {
+                (unknown + usize::from(s.mode == "unknown"), total + 1)
+            });
+        if total == 0 {
+            0.0
+        } else {
+            unknown as f64 / total as f64
+        }
+    }
+
+    /// Serialize using the frozen v1 field set and names.
+    ///
+    /// Unlike the default `Serialize` impl (always the latest shape), fields
+    /// added to the DTOs after v1 never appear in this output.
+    ///
+    /// # Errors
+    /// Returns an error if serialization fails.
+    pub fn to_json_v1(&self) -> serde_json::Result<String> {
+        serde_json::to_string(&crate::dto_v1::TransitV1::from(self))
+    }
+
+    /// Serialize with times as JST wall-clock time without an offset; see
+    /// [`NaiveLocalTimes`].
+    ///
+    /// # Errors
+    /// Returns an error if serialization fails.
+    pub fn to_json_naive_local(&self) -> serde_json::Result<String> {
+        serde_json::to_string(&NaiveLocalTimes(self))
+    }
+
+    /// Whether the last train still gets you to the destination.
+    ///
+    /// Only meaningful for [`DateKind::LastTrain`](crate::args::DateKind::LastTrain)
+    /// searches. Returns `false` when no route has both segments and an
+    /// arrival time. A search with no route at all fails with
+    /// [`TransitError::NoRouteFound`] instead of returning a DTO; treat
+    /// that as unreachable too.
+    pub fn last_train_reachable(&self) -> bool {
+        self.routes.iter().any(|r| {
+            !r.segments.is_empty()
+                && (r.summary.arrival_time.is_some()
+                    || r.segments.last().is_some_and(|s| s.arrival_time.is_some()))
+        })
+    }
+
+    /// The route arriving first.
+    ///
+    /// Ties go to the cheaper route, then to the lower rank. Routes without
+    /// an arrival time are skipped; returns `None` if no route has one.
+    /// See [`select_best`] for custom scoring.
+    pub fn earliest_route(&self) -> Option<&RouteDto> {
+        self.routes
+            .iter()
+            .filter_map(|r| r.summary.arrival_time.map(|t| (t, r)))
+            .min_by_key(|(t, r)| {
+                (
+                    *t,
+                    last_if_none(r.summary.total_price_yen.map(i64::from)),
+                    r.rank,
+                )
+            })
+            .map(|(_, r)| r)
+    }
+
+    /// The route with the lowest `total_price_yen`.
+    ///
+    /// Ties go to the route arriving first, then to the lower rank. Routes
+    /// without a fare are skipped; returns `None` if no route has one.
+    pub fn cheapest_route(&self) -> Option<&RouteDto> {
+        self.routes
+            .iter()
+            .filter_map(|r| r.summary.total_price_yen.map(|yen| (yen, r)))
+            .min_by_key(|(yen, r)| {
+                (
+                    *yen,
+                    last_if_none(r.summary.arrival_time.map(|t| t.timestamp())),
+                    r.rank,
+                )
+            })
+            .map(|(_, r)| r)
+    }
+
+    /// The station the top route starts riding from, e.g. the nearest
+    /// station when `from` is a facility.
+    ///
+    /// This is the `from` of the first segment that is not a walk (of the
+    /// first segment if all are walks), so it differs from [`from`](Self::from)
+    /// when the route begins by walking to a station. `None` without routes.
+    pub fn origin_station(&self) -> Option<&str> {
+        let segments = &self.routes.first()?.segments;
+        segments
+            .iter()
+            .find(|s| s.mode != "walk")
+            .or(segments.first())
+            .map(|s| s.from.as_str())
+    }
+
+    /// The station the top route stops riding at; the `to` of the last
+    /// segment that is not a walk. See [`origin_station`](Self::origin_station).
+    pub fn destination_station(&self) -> Option<&str> {
+        let segments = &self.routes.first()?.segments;
+        segments
+            .iter()
+            .rfind(|s| s.mode != "walk")
+            .or(segments.last())
+            .map(|s| s.to.as_str())
+    }
+
+    /// Compact digest of the route with the given `rank`, or `None` if no
+    /// such route was returned.
+    pub fn notification_summary(&self, rank: u32) -> Option<NotificationSummary> {
+        let route = self.routes.iter().find(|r| r.rank == rank)?;
+        Some(NotificationSummary {
+            departure_time: route.summary.departure_time,
+            arrival_time: route.summary.arrival_time,
+            first_line: route
+                .segments
+                .iter()
+                .filter(|s| s.mode != "walk")
+                .find_map(|s| s.line.clone()),
+            transfer_count: route.summary.transfer_count,
+            total_price_yen: route.summary.total_price_yen,
+        })
+    }
+}
+
+pub fn load_next_data(input: &str) -> Result<Value> {
+    // Cached bodies may carry a BOM (sometimes after whitespace) in front.
+    let s = input.trim_start_matches(|c: char| c == '\u{feff}' || c.is_whitespace());
+
+    // allow JSON
+    if s.starts_with('{') {
+        return Ok(serde_json::from_str::<Value>(s)?);
+    }
+
+    let doc = Html::parse_document(s);
+    let sel = Selector::parse(r#"script#__NEXT_DATA__"#).unwrap();
+    let json_text = doc
+        .select(&sel)
+        .next()
+        .ok_or_else(|| anyhow!("__NEXT_DATA__ not found in HTML"))?
+        .inner_html();
+
+    Ok(serde_json::from_str::<Value>(&json_text)?)
+}
+
+/// Options controlling how a result page is parsed.
+#[derive(Debug, Clone)]
+pub struct ParseOptions {
+    /// Maximum number of routes parsed from `featureInfoList`; extra entries
+    /// are ignored with a warning. Defaults to [`ParseOptions::DEFAULT_MAX_ROUTES`].
+    pub max_routes: usize,
+}
+
+impl ParseOptions {
+    pub const DEFAULT_MAX_ROUTES: usize = 50;
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            max_routes: Self::DEFAULT_MAX_ROUTES,
+        }
+    }
+}
+
+pub fn next_data_to_transit_dto(root: &Value) -> Result<TransitDto> {
+    next_data_to_transit_dto_with(root, &ParseOptions::default())
+}
+
+pub fn next_data_to_transit_dto_with(root: &Value, options: &ParseOptions) -> Result<TransitDto> {
+    tracing::trace!(parser_revision = PARSER_REVISION, "parsing __NEXT_DATA__");
+    let page_props = &root["props"]["pageProps"];
+    let navi = &page_props["naviSearchParam"];
+
+    let display_from = as_nonempty_str(&navi["displayInfo"]["fromName"]);
+    let display_to = as_nonempty_str(&navi["displayInfo"]["toName"]);
+    let query_from = as_nonempty_str(&page_props["pageQuery"]["from"]);
+    let query_to = as_nonempty_str(&page_props["pageQuery"]["to"]);
+
+    // Endpoints known before any route is parsed; replaced below once the
+    // top route's stations can fill in for a blank display name.
+    let from = display_from.or(query_from).unwrap_or("").to_string();
+    let to = display_to.or(query_to).unwrap_or("").to_string();
+
+    let search_date_time = build_search_datetime(&page_props["pageQuery"]);
+    let base_date = search_date_time.as_ref();
+    let date_kind = page_props["pageQuery"]["type"].as_str();
+    let wants_ic = page_props["pageQuery"]["ticket"].as_str() == Some("ic");
+    let mut warnings = Vec::new();
+
+    if navi.is_null() && root["page"].as_str() != Some("/search/print") {
+        return Err(TransitError::InvalidSearch {
+            reason: format!(
+                "search parameters were rejected (redirected to page `{}`)",
+                root["page"].as_str().unwrap_or("?")
+            ),
+        }
+        .into());
+    }
+
+    let features = navi["featureInfoList"]
+        .as_array()
+        .ok_or_else(|| anyhow!("featureInfoList missing"))?;
+    if features.is_empty() {
+        return Err(classify_empty_result(page_props, &from, &to).into());
+    }
+
+    if features.len() > options.max_routes {
+        tracing::warn!(
+            count = features.len(),
+            max_routes = options.max_routes,
+            "featureInfoList truncated"
+        );
+        warnings.push(format!(
+            "featureInfoList truncated from {} to {} routes",
+            features.len(),
+            options.max_routes
+        ));
+    }
+
+    let context = RouteContext {
+        base_date,
+        date_kind,
+        wants_ic,
+        from: &from,
+        to: &to,
+    };
+    let mut routes = Vec::new();
+
+    for (idx, feature) in features.iter().take(options.max_routes).enumerate() {
+        // Notices and ads share the list with routes. Skip them without
+        // renumbering, so ranks keep matching the site's `no=`.
+        if !is_route(feature) {
+            tracing::warn!(index = idx, "featureInfoList entry is not a route");
+            warnings.push(format!(
+                "featureInfoList[{idx}]: no summaryInfo or edgeInfoList; entry skipped"
+            ));
+            continue;
+        }
+
+        if let Some(route) = parse_route(feature, (idx as u32) + 1, &context, &mut warnings) {
+            routes.push(route);
+        }
+    }
+
+    if routes.is_empty() {
+        return Err(TransitError::NoRouteFound {
+            from,
+            to,
+            did_you_mean: Vec::new(),
+        }
+        .into());
+    }
+
+    if derive_badges(&mut routes) {
+        warnings.push("isFast/isEasy/isCheap missing; derived from route summaries".into());
+    }
+
+    let top = &routes[0].segments;
+    let from = display_from
+        .or(top
+            .first()
+            .map(|s| s.from.as_str())
+            .filter(|s| !s.trim().is_empty()))
+        .unwrap_or(&from)
+        .to_string();
+    let to = display_to
+        .or(top
+            .last()
+            .map(|s| s.to.as_str())
+            .filter(|s| !s.trim().is_empty()))
+        .unwrap_or(&to)
+        .to_string();
+
+    Ok(TransitDto {
+        from,
+        to,
+        search_date_time,
+        routes,
+        warnings,
+    })
+}
+
+/// Whether a `featureInfoList` entry has a summary or edges to parse.
+fn is_route(feature: &Value) -> bool {
+    feature["summaryInfo"].is_object()
+        || feature["edgeInfoList"]
+            .as_array()
+            .is_some_and(|edges| !edges.is_empty())
+}
+
+/// What the routes of one page share.
+struct RouteContext<'a> {
+    /// Search date and time the clock times are placed on.
+    base_date: Option<&'a DateTime<FixedOffset>>,
+    /// `pageQuery.type` (departure, arrival, first or last train).
+    date_kind: Option<&'a str>,
+    wants_ic: bool,
+    /// Endpoints for a segment synthesized from the summary.
+    from: &'a str,
+    to: &'a str,
+}
+
+/// Parse one `featureInfoList` entry, or `None` if it has neither segments
+/// nor summary times.
+fn parse_route(
+    feature: &Value,
+    rank: u32,
+    context: &RouteContext<'_>,
+    warnings: &mut Vec<String>,
+) -> Option<RouteDto> {
+    let summary = &feature["summaryInfo"];
+    let v = vec![];
+    let edges = feature["edgeInfoList"].as_array().unwrap_or(&v);
+
+    let departure_time = summary
+        .get("departureTime")
+        .and_then(as_nonempty_str)
+        .and_then(|s| {
+            context
+                .base_date
+                .and_then(|dt| time_on_date_with_rollover(dt, s, None))
+        });
+    let departs_next_day = context
+        .base_date
+        .filter(|_| context.date_kind == Some("1"))
+        .zip(departure_time)
+        .map(|(search, departure)| departs_after_midnight(search, &departure));
+    // Segment times start from midnight of the next day.
+    let mut next_day_start = None;
+    let departure_time = match departure_time {
+        Some(departure) if departs_next_day == Some(true) => {
+            let departure = departure + TimeDelta::days(1);
+            next_day_start = departure.with_time(NaiveTime::MIN).single();
+            Some(departure)
+        }
+        departure => departure,
+    };
+    if departs_next_day == Some(true) {
+        warnings.push(format!(
+            "route {}: departs the day after the search date",
+            rank
+        ));
+    }
+
+    let duration_minutes = summary.get("totalTime").and_then(as_minutes_any);
+
+    let arrival_time = summary
+        .get("arrivalTime")
+        .and_then(as_nonempty_str)
+        .and_then(|s| {
+            context
+                .base_date
+                .and_then(|dt| time_on_date_with_rollover(dt, s, departure_time))
+        });
+    let arrival_time = anchor_by_duration(arrival_time, departure_time, duration_minutes);
+    let calendar_days = calendar_days(departure_time, arrival_time);
+
+    let segments = build_segments_from_edges(edges, context.base_date, next_day_start);
+    let ticket_sections = segments
+        .iter()
+        .filter(|s| s.fare_basis == Some(FareBasis::Ticket))
+        .count();
+    if context.wants_ic && ticket_sections > 0 {
+        warnings.push(format!(
+            "route {}: IC fare unavailable for {ticket_sections} section(s); ticket fare used",
+            rank
+        ));
+    }
+
+    let notes = parse_notes(summary, ROUTE_NOTE_KEYS);
+    let total_price_yen = summary.get("totalPrice").and_then(as_u32_any);
+    let fare_discount = parse_fare_discount(summary, &notes);
+    if let (Some(total), Some(Yen(discount))) = (total_price_yen, fare_discount)
+        && let Some(sum) = segments.iter().map(|s| s.fare_yen).sum::<Option<u32>>()
+        && i64::from(sum) - i64::from(discount) != i64::from(total)
+    {
+        warnings.push(format!(
+            "route {}: segment fares {sum} yen minus discount {discount} yen do not match total {total} yen",
+            rank
+        ));
+    }
+
+    let route_summary = RouteSummaryDto {
+        departure_time,
+        arrival_time,
+        departs_next_day,
+        spans_midnight: calendar_days.map(|days| days > 1),
+        calendar_days,
+        duration_minutes,
+        onboard_minutes: summary
+            .get("timeOnBoard")
+            .and_then(as_minutes_any)
+            .or_else(|| sum_onboard_minutes(&segments)),
+        transfer_count: summary.get("transferCount").and_then(as_u32_any),
+        min_transfer_minutes: transfer_waits(&segments)
+            .into_iter()
+            .filter_map(|(_, _, wait)| wait)
+            .min(),
+        total_price_yen,
+        fare_discount,
+        distance_km: summary.get("distance").and_then(as_km_any),
+        co2_grams: summary.get("co2").and_then(parse_co2_grams),
+        is_fast: summary.get("isFast").and_then(|v| v.as_bool()),
+        is_easy: summary.get("isEasy").and_then(|v| v.as_bool()),
+        is_cheap: summary.get("isCheap").and_then(|v| v.as_bool()),
+        is_convenient: summary.get("isConvenient").and_then(|v| v.as_bool()),
+        service_position: parse_service_position(summary, context.date_kind, rank),
+        fare_is_estimate: parse_fare_is_estimate(summary),
+        fare_passenger_type: PassengerType::Adult,
+        notes,
+        fare_basis: combine_fare_basis(&segments),
+        has_fare_caveats: segments.iter().any(|s| !s.fare_caveats.is_empty()),
+    };
+
+    let segments = if !segments.is_empty() {
+        segments
+    } else if let Some(segment) =
+        synthesize_segment(edges, context.from, context.to, &route_summary)
+    {
+        warnings.push(format!(
+            "route {}: fewer than 2 edges; segment synthesized from summary",
+            rank
+        ));
+        vec![segment]
+    } else {
+        warnings.push(format!(
+            "route {}: no segments and no summary times; route dropped",
+            rank
+        ));
+        return None;
+    };
+
+    Some(RouteDto {
+        rank,
+        summary: route_summary,
+        segments,
+    })
+}
+
+/// Parse a single `featureInfoList` entry of a document read with
+/// [`load_next_data`].
+///
+/// The entry does not carry the search date, so all times are `None`, and
+/// the route gets rank 1. Parse the whole page with
+/// [`next_data_to_transit_dto`] for times and warnings.
+impl TryFrom<&Value> for RouteDto {
+    type Error = anyhow::Error;
+
+    fn try_from(feature: &Value) -> Result<Self> {
+        if !is_route(feature) {
+            bail!("not a route: no summaryInfo or edgeInfoList");
+        }
+        let context = RouteContext {
+            base_date: None,
+            date_kind: None,
+            wants_ic: false,
+            from: "",
+            to: "",
+        };
+        parse_route(feature, 1, &context, &mut Vec::new())
+            .ok_or_else(|| anyhow!("route has no segments"))
+    }
+}
+
+/// Parse a pair of consecutive `edgeInfoList` entries, `[from, to]`, into
+/// the segment between them. Times are `None`, as for [`RouteDto`].
+impl TryFrom<&Value> for SegmentDto {
+    type Error = anyhow::Error;
+
+    fn try_from(edges: &Value) -> Result<Self> {
+        let edges = edges
+            .as_array()
+            .filter(|edges| edges.len() == 2)
+            .ok_or_else(|| anyhow!("expected an array of 2 edges"))?;
+        build_segments_from_edges(edges, None, None)
+            .pop()
+            .ok_or_else(|| anyhow!("edges do not form a segment"))
+    }
+}
+
+/// Total duration of the non-walking segments, if every one is known.
+fn sum_onboard_minutes(segments: &[SegmentDto]) -> Option<u32> {
+    if segments.is_empty() {
+        return None;
+    }
+    segments
+        .iter()
+        .filter(|s| s.mode != "walk")
+        .map(|s| match s.mode.as_str() {
+            "unknown" => None,
+            _ => s.duration_minutes,
+        })
+        .sum()
+}
+
+/// Fill `is_fast` / `is_easy` / `is_cheap` when the page left them all null.
+///
+/// The shortest duration, fewest transfers and lowest price win (ties share
+/// the badge). Nothing is derived for a single route, or if any route
+/// already carries a badge. Returns whether badges were derived.
+fn derive_badges(routes: &mut [RouteDto]) -> bool {
+    let all_missing = routes.iter().all(|r| {
+        r.summary.is_fast.is_none() && r.summary.is_easy.is_none() && r.summary.is_cheap.is_none()
+    });
+    if routes.len() < 2 || !all_missing {
+        return false;
+    }
+
+    fn mark(
+        routes: &mut [RouteDto],
+        key: fn(&RouteSummaryDto) -> Option<u32>,
+        set: fn(&mut RouteSummaryDto, bool),
+    ) {
+        let Some(best) = routes.iter().filter_map(|r| key(&r.summary)).min() else {
+            return;
+        };
+        for route in routes.iter_mut() {
+            let value = key(&route.summary);
+            set(&mut route.summary, value == Some(best));
+        }
+    }
+    mark(routes, |s| s.duration_minutes, |s, b| s.is_fast = Some(b));
+    mark(routes, |s| s.transfer_count, |s, b| s.is_easy = Some(b));
+    mark(routes, |s| s.total_price_yen, |s, b| s.is_cheap = Some(b));
+    true
+}
+
+/// `line` without a trailing "品川行" / "品川方面行" (optionally "行き" or in
+/// brackets) naming `destination`, if it has one and something remains.
+fn strip_destination<'a>(line: &'a str, destination: &str) -> Option<&'a str> {
+    let place = destination
+        .trim()
+        .trim_end_matches('き')
+        .trim_end_matches('行')
+        .trim_end_matches("方面");
+    if place.is_empty() {
+        return None;
+    }
+    let line = line.trim_end();
+    let suffixes = ["方面行き", "方面行", "行き", "行"].map(|s| format!("{place}{s}"));
+    let rest = [("", ""), ("(", ")"), ("（", "）"), ("[", "]")]
+        .iter()
+        .flat_map(|(open, close)| suffixes.iter().map(move |s| format!("{open}{s}{close}")))
+        .find_map(|suffix| line.strip_suffix(suffix.as_str()))?;
+    let rest = rest.trim_end_matches([' ', '\u{3000}', '・', '-', '/']);
+    (!rest.is_empty()).then_some(rest)
+}
+
+/// Phrases of the site's error message for a search outside its coverage.
+///
+/// Unverified, like the keys read in [`classify_empty_result`]: no captured
+/// page has shown the message yet.
+const OUT_OF_AREA_MARKERS: [&str; 2] = ["エリア外", "対象外の地域"];
+
+/// Explain an empty `featureInfoList`.
+///
+/// A search outside the covered area is assumed to carry an error message
+/// saying so (`errorInfo.message` or `errorMessage`).
+/// An input the site could not resolve has no display name echoed back;
+/// otherwise both places are known and there is simply no route.
+fn classify_empty_result(page_props: &Value, from: &str, to: &str) -> TransitError {
+    let navi = &page_props["naviSearchParam"];
+    let message = [
+        &navi["errorInfo"]["message"],
+        &navi["errorMessage"],
+        &page_props["errorMessage"],
+    ]
+    .into_iter()
+    .find_map(as_nonempty_str);
+    if let Some(message) = message
+        && OUT_OF_AREA_MARKERS.iter().any(|m| message.contains(m))
+    {
+        return TransitError::OutOfServiceArea {
+            from: from.to_string(),
+            to: to.to_string(),
+            message: message.to_string(),
+        };
+    }
+
+    for (field, name_key) in [(PlaceField::From, "fromName"), (PlaceField::To, "toName")] {
+        let resolved = navi["displayInfo"][name_key].as_str().map(str::trim);
+        let query = page_props["pageQuery"][field.to_string()]
+            .as_str()
+            .map(str::trim)
+            .unwrap_or("");
+        if resolved.is_none_or(str::is_empty) && !query.is_empty() {
+            return TransitError::PlaceNotFound {
+                field,
+                query: query.to_string(),
+                did_you_mean: Vec::new(),
+            };
+        }
+    }
+
+    TransitError::NoRouteFound {
+        from: from.to_string(),
+        to: to.to_string(),
+        did_you_mean: Vec::new(),
+    }
+}
+
+/// Times are placed on the day of `start` (default: `base_date`), moving to
+/// the next day when a clock time goes backwards.
+fn build_segments_from_edges(
+    edges: &[Value],
+    base_date: Option<&DateTime<FixedOffset>>,
+    start: Option<DateTime<FixedOffset>>,
+) -> Vec<SegmentDto> {
+    let mut out = Vec::new();
+
+    if edges.len() < 2 {
+        return out;
+    }
+
+    let mut last_time = start;
+
+    for i in 0..(edges.len() - 1) {
+        let cur = &edges[i];
+        let next = &edges[i + 1];
+
+        let from = cur
+            .get("stationName")
+            .and_then(|v| v.as_str())
+            .unwrap_or("")
+            .to_string();
+        let to = next
+            .get("stationName")
+            .and_then(|v| v.as_str())
+            .unwrap_or("")
+            .to_string();
+
+        let destination = cur
+            .get("destination")
+            .and_then(|v| as_nonempty_str(v))
+            .map(str::to_string);
+
+        let line = cur
+            .get("railNameExcludingDestination")
+            .and_then(|v| as_nonempty_str(v))
+            .or_else(|| {
+                let line = cur.get("railName").and_then(|v| as_nonempty_str(v))?;
+                Some(
+                    destination
+                        .as_deref()
+                        .and_then(|d| strip_destination(line, d))
+                        .unwrap_or(line),
+                )
+            })
+            .map(str::to_string);
+
+        let operator = cur
+            .get("companyName")
+            .and_then(as_nonempty_str)
+            .map(str::to_string);
+
+        let mode = infer_mode(line.as_deref());
+        let (train_name, train_number) = match line.as_deref() {
+            Some(line) if mode == "rail" => parse_named_train(line),
+            _ => (None, None),
+        };
+
+        let duration_minutes = cur.get("timeOnBoard").and_then(as_u32_any);
+
+        let fare_yen = cur
+            .get("priceInfo")
+            .and_then(|p| p.get("price"))
+            .and_then(as_u32_any);
+        let fare_basis = cur.get("priceInfo").and_then(parse_fare_basis);
+        let fare_caveats = cur
+            .get("priceInfo")
+            .map(|p| parse_notes(p, FARE_CAVEAT_KEYS))
+            .unwrap_or_default();
+        let seat_availability = cur
+            .get("vacancy")
+            .and_then(as_nonempty_str)
+            .map(parse_seat_availability);
+
+        let cur_times = time_entries(cur);
+        let next_times = time_entries(next);
+        // A transfer station lists the arrival and then the departure, so
+        // an edge departs at its last entry and is reached at its first.
+        let departure_time = cur_times
+            .last()
+            .and_then(TimeEntry::time)
+            .and_then(|s| base_date.and_then(|dt| time_on_date_with_rollover(dt, s, last_time)));
+        if let Some(dt) = departure_time {
+            last_time = Some(dt);
+        }
+
+        let anchor = last_time;
+        let arrival_time = next_times
+            .first()
+            .and_then(TimeEntry::time)
+            .and_then(|s| base_date.and_then(|dt| time_on_date_with_rollover(dt, s, last_time)));
+        let arrival_time = anchor_by_duration(arrival_time, departure_time, duration_minutes);
+        if let Some(dt) = arrival_time {
+            last_time = Some(dt);
+        }
+
+        let alternate_arrival_times = if i + 2 == edges.len() {
+            next_times
+                .iter()
+                .skip(1)
+                .filter_map(TimeEntry::time)
+                .filter_map(|s| base_date.and_then(|dt| time_on_date_with_rollover(dt, s, anchor)))
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        out.push(SegmentDto {
+            mode,
+            from,
+            to,
+            line,
+            operator,
+            destination,
+            train_name,
+            train_number,
+            duration_minutes,
+            fare_yen,
+            fare_basis,
+            fare_caveats,
+            seat_availability,
+            departure_time,
+            arrival_time,
+            alternate_arrival_times,
+            notes: parse_notes(cur, SEGMENT_NOTE_KEYS),
+            boarding_position: cur
+                .get("ridePosition")
+                .and_then(as_nonempty_str)
+                .map(parse_boarding_position),
+        });
+    }
+
+    out
+}
+
+/// One entry of an edge's `timeInfo` list.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+struct TimeEntry {
+    /// "HH:MM", possibly past 24:00.
+    #[serde(default)]
+    time: Option<String>,
+}
+
+impl TimeEntry {
+    fn time(&self) -> Option<&str> {
+        self.time.as_deref().filter(|t| !t.trim().is_empty())
+    }
+}
+
+/// The `timeInfo` entries of an edge, in order.
+///
+/// An entry that cannot be read becomes an empty one, so positions are kept.
+fn time_entries(edge: &Value) -> Vec<TimeEntry> {
+    edge.get("timeInfo")
+        .and_then(Value::as_array)
+        .map(|arr| {
+            arr.iter()
+                .map(|x| serde_json::from_value(x.clone()).unwrap_or_default())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Build a single origin-to-destination segment for a route whose
+/// `edgeInfoList` has fewer than 2 entries.
+///
+/// Needs both summary times; otherwise the route cannot be described and
+/// `None` is returned.
+fn synthesize_segment(
+    edges: &[Value],
+    from: &str,
+    to: &str,
+    summary: &RouteSummaryDto,
+) -> Option<SegmentDto> {
+    let (departure_time, arrival_time) = (summary.departure_time?, summary.arrival_time?);
+    let edge = edges.first();
+    let line = edge
+        .and_then(|e| {
+            e.get("railNameExcludingDestination")
+                .and_then(as_nonempty_str)
+                .or_else(|| e.get("railName").and_then(as_nonempty_str))
+        })
+        .map(str::to_string);
+    let from = edge
+        .and_then(|e| e.get("stationName"))
+        .and_then(as_nonempty_str)
+        .unwrap_or(from);
+
+    Some(SegmentDto {
+        mode: infer_mode(line.as_deref()),
+        from: from.to_string(),
+        to: to.to_string(),
+        line,
+        operator: edges
+            .first()
+            .and_then(|e| e.get("companyName"))
+            .and_then(as_nonempty_str)
+            .map(str::to_string),
+        destination: None,
+        train_name: None,
+        train_number: None,
+        duration_minutes: summary.duration_minutes,
+        fare_yen: summary.total_price_yen,
+        fare_basis: None,
+        fare_caveats: Vec::new(),
+        seat_availability: None,
+        departure_time: Some(departure_time),
+        arrival_time: Some(arrival_time),
+        alternate_arrival_times: Vec::new(),
+        notes: Vec::new(),
+        boarding_position: None,
+    })
+}
+
+/// Read the fare basis of a segment from its `priceInfo`.
+///
+/// Uses `priceType` ("ic" / "ticket" / "normal") or the `isIcPrice` flag.
+fn parse_fare_basis(price_info: &Value) -> Option<FareBasis> {
+    match price_info.get("priceType").and_then(as_nonempty_str) {
+        Some("ic") => return Some(FareBasis::Ic),
+        Some("ticket" | "normal") => return Some(FareBasis::Ticket),
+        _ => {}
+    }
+    price_info
+        .get("isIcPrice")
+        .and_then(|v| v.as_bool())
+        .map(|ic| if ic { FareBasis::Ic } else { FareBasis::Ticket })
+}
+
+fn parse_seat_availability(mark: &str) -> SeatAvailability {
+    match mark {
+        "○" | "◯" => SeatAvailability::Available,
+        "△" => SeatAvailability::Few,
+        "×" | "✕" => SeatAvailability::Full,
+        _ => SeatAvailability::Unknown,
+    }
+}
+
+/// Fare basis of a route total: `Mixed` if the priced segments disagree.
+fn combine_fare_basis(segments: &[SegmentDto]) -> Option<FareBasis> {
+    segments
+        .iter()
+        .filter_map(|s| s.fare_basis)
+        .reduce(|a, b| if a == b { a } else { FareBasis::Mixed })
+}
+
+/// Read the first/last service marker from the summary.
+///
+/// Explicit flags (`isFirstTrain` / `isLastTrain`) and the `serviceLabel`
+/// text win; otherwise first/last-train searches (`type` 3 / 2) mark only
+/// rank 1, the first/last feasible service. Later ranks of such a search
+/// are alternatives, not the first or last service.
+fn parse_service_position(
+    summary: &Value,
+    date_kind: Option<&str>,
+    rank: u32,
+) -> Option<ServicePosition> {
+    if summary.get("isLastTrain").and_then(|v| v.as_bool()) == Some(true) {
+        return Some(ServicePosition::LastOfDay);
+    }
+    if summary.get("isFirstTrain").and_then(|v| v.as_bool()) == Some(true) {
+        return Some(ServicePosition::FirstOfDay);
+    }
+    if let Some(label) = summary.get("serviceLabel").and_then(as_nonempty_str) {
+        return Some(if label.contains("終電") {
+            ServicePosition::LastOfDay
+        } else if label.contains("始発") {
+            ServicePosition::FirstOfDay
+        } else {
+            ServicePosition::Other(label.to_string())
+        });
+    }
+
+    match date_kind {
+        _ if rank != 1 => None,
+        Some("2") => Some(ServicePosition::LastOfDay),
+        Some("3") => Some(ServicePosition::FirstOfDay),
+        _ => None,
+    }
+}
+
+/// Detect an estimated fare from the `isFareEstimate` flag, an approximate
+/// `totalPrice` ("約170円") or estimate wording in the notes.
+fn parse_fare_is_estimate(summary: &Value) -> bool {
+    if let Some(flag) = summary.get("isFareEstimate").and_then(|v| v.as_bool()) {
+        return flag;
+    }
+    let approximate_price = summary
+        .get("totalPrice")
+        .and_then(as_nonempty_str)
+        .is_some_and(|p| p.starts_with('約') || p.contains("概算"));
+    approximate_price
+        || parse_notes(summary, ROUTE_NOTE_KEYS)
+            .iter()
+            .any(|n| n.contains("概算"))
+}
+
+/// Read a fare discount from the `discountPrice` field, or else from a note
+/// stating an amount taken off ("70円引き") or a 乗継割引 amount. Other
+/// notes with a price, such as "学割適用外 170円", are not discounts.
+fn parse_fare_discount(summary: &Value, notes: &[String]) -> Option<Yen> {
+    if let Some(yen) = summary.get("discountPrice").and_then(as_u32_any) {
+        return Some(Yen(yen));
+    }
+    notes.iter().find_map(|note| {
+        let note = normalize_digits(note);
+        let yen = match note.split_once("円引") {
+            Some((before, _)) => yen_amount(&format!("{before}円")),
+            None if note.contains("乗継割引") => yen_amount(&note),
+            None => None,
+        };
+        yen.map(Yen)
+    })
+}
+
+/// The first "<digits>円" amount in `text`, allowing fullwidth digits and
+/// thousands separators.
+fn yen_amount(text: &str) -> Option<u32> {
+    let text = normalize_digits(text);
+    let (before, _) = text.split_once('円')?;
+    let digits: String = before
+        .chars()
+        .rev()
+        .take_while(|c| c.is_ascii_digit() || *c == ',')
+        .filter(char::is_ascii_digit)
+        .collect();
+    digits.chars().rev().collect::<String>().parse().ok()
+}
+
+/// Summary keys holding route notes.
+const ROUTE_NOTE_KEYS: &[&str] = &["noteList", "notes"];
+
+/// Edge keys holding segment annotations. Partly guessed; see
+/// [`SegmentDto::notes`].
+const SEGMENT_NOTE_KEYS: &[&str] = &[
+    "noteList",
+    "notes",
+    "annotationList",
+    "annotation",
+    "comment",
+    "ridePosition",
+];
+
+/// `priceInfo` keys holding fare caveats. Guessed; see
+/// [`SegmentDto::fare_caveats`].
+const FARE_CAVEAT_KEYS: &[&str] = &["noteList", "notes", "caveatList", "caveat", "comment"];
+
+/// Collect notes from the given keys of `obj`.
+///
+/// Values may be a string, an object with a `text` field, or an array of
+/// either. Entries are trimmed, entity-decoded and deduplicated.
+fn parse_notes(obj: &Value, keys: &[&str]) -> Vec<String> {
+    let mut out: Vec<String> = Vec::new();
+    for key in keys {
+        let Some(value) = obj.get(*key) else {
+            continue;
+        };
+        let entries = match value.as_array() {
+            Some(entries) => entries.as_slice(),
+            None => std::slice::from_ref(value),
+        };
+        for entry in entries {
+            let text =
+                as_nonempty_str(entry).or_else(|| entry.get("text").and_then(as_nonempty_str));
+            if let Some(text) = text.map(decode_entities)
+                && !out.contains(&text)
+            {
+                out.push(text);
+            }
+        }
+    }
+    out
+}
+
+/// Decode the HTML entities that show up in annotation text.
+fn decode_entities(s: &str) -> String {
+    if !s.contains('&') {
+        return s.to_string();
+    }
+    s.replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+        .trim()
+        .to_string()
+}
+
+/// Position texts meaning the middle of the train, matched as whole
+/// tokens so that names such as "中目黒方" are not read as one.
+const MIDDLE_POSITIONS: &[&str] = &["中ほど", "中央"];
+
+fn parse_boarding_position(text: &str) -> BoardingPosition {
+    let digits = normalize_digits(text);
+    if let Some(pos) = digits.find("号車") {
+        let mut digits: Vec<char> = digits[..pos]
+            .chars()
+            .rev()
+            .take_while(char::is_ascii_digit)
+            .collect();
+        digits.reverse();
+        if let Ok(car) = digits.into_iter().collect::<String>().parse() {
+            return BoardingPosition::Car(car);
+        }
+    }
+    if text.contains('前') {
+        BoardingPosition::Front
+    } else if is_middle_position(text) {
+        BoardingPosition::Middle
+    } else if text.contains('後') {
+        BoardingPosition::Rear
+    } else {
+        BoardingPosition::Other(text.to_string())
+    }
+}
+
+/// Whether `text` is one of [`MIDDLE_POSITIONS`], alone or followed by
+/// "付近" / "寄り" / "の車両".
+fn is_middle_position(text: &str) -> bool {
+    let text = text.trim();
+    let token = ["付近", "寄り", "の車両"]
+        .iter()
+        .find_map(|suffix| text.strip_suffix(suffix))
+        .unwrap_or(text);
+    MIDDLE_POSITIONS.contains(&token)
+}
+
+/// Whether `line` names a limited express service ("ＪＲ特急ひたち"), as
+/// opposed to a commuter train of the 特急 type ("東急東横線特急").
+fn is_named_limited_express(line: &str) -> bool {
+    let line = line.trim();
+    let line = line.strip_prefix("ＪＲ").unwrap_or(line);
+    line.strip_prefix("特急")
+        .is_some_and(|name| !name.trim().is_empty())
+}
+
+/// Line-name prefixes that precede a train name ("ＪＲ新幹線のぞみ２２５号").
+const TRAIN_NAME_PREFIXES: &[&str] = &["ＪＲ", "新幹線", "特急", "急行", "快速"];
+
+/// Split a named service such as "ＪＲ新幹線のぞみ２２５号" into
+/// `("のぞみ", "225")`. Lines without a trailing `<number>号` yield nothing.
+fn parse_named_train(line: &str) -> (Option<String>, Option<String>) {
+    let Some(rest) = line.trim().strip_suffix('号') else {
+        return (None, None);
+    };
+    let name_end = rest
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| c.is_ascii_digit() || ('０'..='９').contains(c))
+        .last()
+        .map(|(i, _)| i);
+    let Some(name_end) = name_end else {
+        return (None, None);
+    };
+
+    let number = normalize_digits(&rest[name_end..]).into_owned();
+
+    let mut name = rest[..name_end].trim();
+    while let Some(stripped) = TRAIN_NAME_PREFIXES
+        .iter()
+        .find_map(|p| name.strip_prefix(p))
+    {
+        name = stripped.trim_start();
+    }
+    let name = (!name.is_empty()).then(|| name.to_string());
+    (name, Some(number))
+}
+
+fn infer_mode(line: Option<&str>) -> String {
+    let s = line.unwrap_or("");
+    if s.contains("徒歩") {
+        "walk".to_string()
+    } else if s.contains("空路")
+        || s.contains("フライト")
+        || s.contains("飛行機")
+        || s.contains("ＡＮＡ")
+        || s.contains("ＪＡＬ")
+    {
+        "flight".to_string()
+    } else if s.contains("バス") || s.contains("連絡バス") || s.contains("高速") {
         "bus".to_string()
     } else if s.contains("フェリー") || s.contains("船") {
         "ferry".to_string()
@@ -356,7 +2209,34 @@ fn as_nonempty_str(v: &Value) -> Option<&str> {
     if s.is_empty() { None } else { Some(s) }
 }
 
+/// A count or amount sent either as text ("1,234円") or as a JSON number.
+fn as_u32_any(v: &Value) -> Option<u32> {
+    match v {
+        Value::String(s) => parse_u32_loose(s),
+        _ => v.as_u64().and_then(|n| u32::try_from(n).ok()),
+    }
+}
+
+/// A duration sent either as text ("1時間2分") or as a JSON number of
+/// minutes.
+fn as_minutes_any(v: &Value) -> Option<u32> {
+    match v {
+        Value::String(s) => parse_ja_duration_minutes(s),
+        _ => v.as_u64().and_then(|n| u32::try_from(n).ok()),
+    }
+}
+
+/// A distance sent either as text ("3.4km", "500m") or as a JSON number of
+/// kilometers.
+fn as_km_any(v: &Value) -> Option<f64> {
+    match v {
+        Value::String(s) => parse_distance_km(s),
+        _ => v.as_f64().filter(|km| km.is_finite() && *km >= 0.0),
+    }
+}
+
 fn parse_u32_loose(s: &str) -> Option<u32> {
+    let s = normalize_digits(s);
     let digits: String = s.chars().filter(|c| c.is_ascii_digit()).collect();
     if digits.is_empty() {
         None
@@ -367,6 +2247,7 @@ fn parse_u32_loose(s: &str) -> Option<u32> {
 
 fn parse_ja_duration_minutes(s: &str) -> Option<u32> {
     // "4分", "1時間2分", "3時間" など
+    let s = &*normalize_digits(s);
     let hours: u32;
     let mut minutes: u32 = 0;
 
@@ -401,6 +2282,13 @@ fn parse_ja_duration_minutes(s: &str) -> Option<u32> {
 }
 
 fn parse_distance_km(s: &str) -> Option<f64> {
+    // approximate distances: "約12.3km", "およそ 500m"
+    let s = normalize_digits(s);
+    let s = s.trim();
+    let s = s
+        .strip_prefix("約")
+        .or_else(|| s.strip_prefix("およそ"))
+        .unwrap_or(s);
     let t = s.trim().replace(',', "");
     if let Some(km) = t.strip_suffix("km") {
         return km.trim().parse::<f64>().ok();
@@ -411,6 +2299,32 @@ fn parse_distance_km(s: &str) -> Option<f64> {
     None
 }
 
+/// Read a CO2 amount such as "約1.2kg" or "350g" as grams. A bare number
+/// is taken as grams.
+fn parse_co2_grams(v: &Value) -> Option<u32> {
+    let s = match v {
+        Value::String(s) => s,
+        _ => return as_u32_any(v),
+    };
+    let s = normalize_digits(s);
+    let s = s.trim();
+    let s = s
+        .strip_prefix("約")
+        .or_else(|| s.strip_prefix("およそ"))
+        .unwrap_or(s);
+    let t = s.trim().replace(',', "");
+    let (number, scale) = if let Some(kg) = t.strip_suffix("kg") {
+        (kg, 1000.0)
+    } else if let Some(g) = t.strip_suffix('g') {
+        (g, 1.0)
+    } else {
+        (t.as_str(), 1.0)
+    };
+    let grams = number.trim().parse::<f64>().ok()? * scale;
+    (grams.is_finite() && grams >= 0.0 && grams <= f64::from(u32::MAX))
+        .then(|| grams.round() as u32)
+}
+
 fn build_search_datetime(page_query: &Value) -> Option<DateTime<FixedOffset>> {
     let y = page_query.get("y")?.as_str()?.parse::<i32>().ok()?;
     let m = page_query.get("m")?.as_str()?.parse::<u32>().ok()?;
@@ -428,6 +2342,7 @@ fn build_search_datetime(page_query: &Value) -> Option<DateTime<FixedOffset>> {
         .and_then(|s| s.parse::<u32>().ok())
         .unwrap_or(0);
 
+    // inverse of `minute_digits` in the request builder
     let mm = m1 * 10 + m2;
 
     let date = NaiveDate::from_ymd_opt(y, m, d)?;
@@ -441,6 +2356,7 @@ const fn jst_offset() -> FixedOffset {
 }
 
 fn parse_hhmm(s: &str) -> Option<NaiveTime> {
+    let s = normalize_digits(s);
     let mut it = s.split(':');
     let h = it.next()?.trim().parse::<u32>().ok()?;
     let m = it.next()?.trim().parse::<u32>().ok()?;
@@ -468,3 +2384,1544 @@ fn time_on_date_with_rollover(
     let dt = date.and_time(time);
     base.offset().from_local_datetime(&dt).single()
 }
+
+/// How far before the search time a departure may be and still be on the
+/// search date (e.g. a walk to the station).
+const EARLY_DEPARTURE_TOLERANCE_MINUTES: i64 = 60;
+
+/// Whether `departure`, placed on the search date, is really on the next
+/// day: it is more than [`EARLY_DEPARTURE_TOLERANCE_MINUTES`] before the
+/// search time, as when a 23:00 search is answered with the 05:10 first
+/// train.
+fn departs_after_midnight(
+    search: &DateTime<FixedOffset>,
+    departure: &DateTime<FixedOffset>,
+) -> bool {
+    departure.date_naive() == search.date_naive()
+        && *search - *departure > TimeDelta::minutes(EARLY_DEPARTURE_TOLERANCE_MINUTES)
+}
+
+/// Move `arrival` to `departure + minutes` when that lands on the same
+/// clock time a whole number of days later.
+///
+/// The day rollover only sees clock times, so a 24-hour ferry would arrive
+/// "at departure"; the duration tells how many days really passed.
+fn anchor_by_duration(
+    arrival: Option<DateTime<FixedOffset>>,
+    departure: Option<DateTime<FixedOffset>>,
+    minutes: Option<u32>,
+) -> Option<DateTime<FixedOffset>> {
+    let (Some(arrival), Some(departure), Some(minutes)) = (arrival, departure, minutes) else {
+        return arrival;
+    };
+    let expected = departure + chrono::Duration::minutes(i64::from(minutes));
+    if expected > arrival && expected.time() == arrival.time() {
+        Some(expected)
+    } else {
+        Some(arrival)
+    }
+}
+
+/// Calendar days from the departure date to the arrival date, inclusive.
+fn calendar_days(
+    departure: Option<DateTime<FixedOffset>>,
+    arrival: Option<DateTime<FixedOffset>>,
+) -> Option<u32> {
+    let days = (arrival?.date_naive() - departure?.date_naive()).num_days();
+    u32::try_from(days + 1).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_fixture(input: &str) -> TransitDto {
+        next_data_to_transit_dto(&load_next_data(input).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_to_json_v1_golden() {
+        let dto = parse_fixture(include_str!("../tests/fixtures/basic.json"));
+        let actual: Value = serde_json::from_str(&dto.to_json_v1().unwrap()).unwrap();
+        let golden: Value =
+            serde_json::from_str(include_str!("../tests/fixtures/basic.v1.json")).unwrap();
+        assert_eq!(actual, golden);
+    }
+
+    #[test]
+    fn test_try_from_value() {
+        let root = load_next_data(include_str!("../tests/fixtures/transfer_heavy.json")).unwrap();
+        let feature = &root["props"]["pageProps"]["naviSearchParam"]["featureInfoList"][0];
+        let full = next_data_to_transit_dto(&root).unwrap();
+        let expected = &full.routes[0];
+
+        let route = RouteDto::try_from(feature).unwrap();
+        assert_eq!(route.rank, 1);
+        assert_eq!(route.summary.total_price_yen, Some(610));
+        assert_eq!(route.summary.transfer_count, Some(2));
+        assert_eq!(route.summary.departure_time, None);
+        assert_eq!(route.segments.len(), expected.segments.len());
+        for (segment, expected) in route.segments.iter().zip(&expected.segments) {
+            assert_eq!(
+                (&segment.from, &segment.to, &segment.line, segment.fare_yen),
+                (
+                    &expected.from,
+                    &expected.to,
+                    &expected.line,
+                    expected.fare_yen
+                )
+            );
+            assert_eq!(segment.departure_time, None);
+        }
+
+        let edges = feature["edgeInfoList"].as_array().unwrap();
+        let segment = SegmentDto::try_from(&Value::from(edges[1..3].to_vec())).unwrap();
+        assert_eq!(
+            (segment.from.as_str(), segment.to.as_str()),
+            ("渋谷", "二子玉川")
+        );
+        assert_eq!(segment.duration_minutes, Some(14));
+
+        assert!(RouteDto::try_from(&serde_json::json!({"type": "ad"})).is_err());
+        assert!(SegmentDto::try_from(&Value::from(edges.clone())).is_err());
+        assert!(SegmentDto::try_from(&edges[0]).is_err());
+    }
+
+    #[test]
+    fn test_fullwidth_numbers() {
+        let counts = [
+            ("１，２３４円", Some(1234)),
+            ("￥１７０", Some(170)),
+            ("２", Some(2)),
+            ("なし", None),
+        ];
+        for (input, expected) in counts {
+            assert_eq!(parse_u32_loose(input), expected, "input: {input}");
+        }
+
+        let durations = [
+            ("１３分", Some(13)),
+            ("１時間２分", Some(62)),
+            ("２４時間０分", Some(1440)),
+        ];
+        for (input, expected) in durations {
+            assert_eq!(parse_ja_duration_minutes(input), expected, "input: {input}");
+        }
+
+        let distances = [
+            ("３．４km", Some(3.4)),
+            ("約１，２３４．５km", Some(1234.5)),
+            ("８００m", Some(0.8)),
+        ];
+        for (input, expected) in distances {
+            assert_eq!(parse_distance_km(input), expected, "input: {input}");
+        }
+
+        let fares = [
+            ("乗継割引 ７０円引き", Some(70)),
+            ("割引（１，０２０円）", Some(1020)),
+        ];
+        for (input, expected) in fares {
+            assert_eq!(yen_amount(input), expected, "input: {input}");
+        }
+
+        let times = [
+            ("０９：３２", NaiveTime::from_hms_opt(9, 32, 0)),
+            ("９時", None),
+        ];
+        for (input, expected) in times {
+            assert_eq!(parse_hhmm(input), expected, "input: {input}");
+        }
+
+        assert_eq!(
+            parse_boarding_position("１２号車付近"),
+            BoardingPosition::Car(12)
+        );
+        assert_eq!(
+            parse_named_train("ＪＲ新幹線のぞみ２２５号"),
+            (Some("のぞみ".into()), Some("225".into()))
+        );
+    }
+
+    #[test]
+    fn test_child_fare_rounding() {
+        let cases = [
+            (170, Some(FareBasis::Ticket), true, 80),
+            (170, Some(FareBasis::Ticket), false, 90),
+            (210, None, true, 100),
+            (210, None, false, 110),
+            (200, None, false, 100),
+            (13_870, Some(FareBasis::Ticket), true, 6_930),
+            (168, Some(FareBasis::Ic), true, 84),
+            (157, Some(FareBasis::Ic), false, 78),
+            (178, Some(FareBasis::Mixed), true, 80),
+            (178, Some(FareBasis::Mixed), false, 90),
+            (0, None, false, 0),
+        ];
+        for (adult, basis, jr, child) in cases {
+            assert_eq!(
+                child_fare(Yen(adult), basis, jr),
+                Yen(child),
+                "{adult} yen {basis:?} jr={jr}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_estimate_child_fares() {
+        let mut dto = parse_fixture(include_str!("../tests/fixtures/shinkansen.json"));
+        let adult = dto.routes[0].clone();
+        assert_eq!(adult.summary.fare_passenger_type, PassengerType::Adult);
+        dto.estimate_child_fares();
+        let child = &dto.routes[0];
+        assert_eq!(child.summary.fare_passenger_type, PassengerType::Child);
+        assert!(child.summary.fare_is_estimate);
+        for (adult, child) in adult.segments.iter().zip(&child.segments) {
+            assert_eq!(
+                child.fare_yen,
+                adult.fare_yen.map(|yen| child_fare(
+                    Yen(yen),
+                    adult.fare_basis,
+                    adult.is_operated_by("JR")
+                )
+                .0)
+            );
+        }
+        let json = serde_json::to_value(&child.summary).unwrap();
+        assert_eq!(json["farePassengerType"], "child");
+
+        // idempotent
+        let once = child.summary.total_price_yen;
+        dto.estimate_child_fares();
+        assert_eq!(dto.routes[0].summary.total_price_yen, once);
+
+        // a discount keeps the halved total
+        let mut dto = parse_fixture(include_str!("../tests/fixtures/transfer_discount.json"));
+        dto.estimate_child_fares();
+        let summary = &dto.routes[0].summary;
+        assert_eq!(summary.total_price_yen, Some(180));
+        assert_eq!(summary.fare_discount, Some(Yen(35)));
+    }
+
+    #[test]
+    fn test_co2() {
+        let dto = parse_fixture(include_str!("../tests/fixtures/co2.json"));
+        let co2: Vec<_> = dto.routes.iter().map(|r| r.summary.co2_grams).collect();
+        assert_eq!(co2, [Some(58), Some(230)]);
+        let json = serde_json::to_value(&dto.routes[0].summary).unwrap();
+        assert_eq!(json["co2Grams"], 58);
+
+        let dto = parse_fixture(include_str!("../tests/fixtures/basic.json"));
+        assert_eq!(dto.routes[0].summary.co2_grams, None);
+        let json = serde_json::to_value(&dto.routes[0].summary).unwrap();
+        assert!(json.get("co2Grams").is_none());
+
+        let cases = [
+            (serde_json::json!("350g"), Some(350)),
+            (serde_json::json!("約1.2kg"), Some(1200)),
+            (serde_json::json!("１，２００g"), Some(1200)),
+            (serde_json::json!("0.05kg"), Some(50)),
+            (serde_json::json!(420), Some(420)),
+            (serde_json::json!("不明"), None),
+            (serde_json::json!("-5g"), None),
+            (serde_json::json!(""), None),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(parse_co2_grams(&input), expected, "input: {input}");
+        }
+    }
+
+    #[test]
+    fn test_origin_and_destination_stations() {
+        let dto = parse_fixture(include_str!("../tests/fixtures/facility_origin.json"));
+        assert_eq!(dto.from, "沖縄美ら海水族館");
+        assert_eq!(dto.to, "沖縄県庁");
+        assert_eq!(dto.origin_station(), Some("記念公園前"));
+        assert_eq!(dto.destination_station(), Some("県庁北口"));
+
+        // station to station: same as from/to
+        let dto = parse_fixture(include_str!("../tests/fixtures/one_edge.json"));
+        assert_eq!(dto.origin_station(), Some(dto.from.as_str()));
+
+        let mut dto = parse_fixture(include_str!("../tests/fixtures/basic.json"));
+        // the trailing walk to 渋谷ヒカリエ is not ridden
+        assert_eq!(dto.destination_station(), Some("渋谷"));
+        dto.routes.clear();
+        assert_eq!(dto.origin_station(), None);
+        assert_eq!(dto.destination_station(), None);
+    }
+
+    #[test]
+    fn test_parse_distance_km() {
+        let cases = [
+            ("3.4km", Some(3.4)),
+            ("1,234.5km", Some(1234.5)),
+            ("800m", Some(0.8)),
+            ("約12.3km", Some(12.3)),
+            ("約 500m", Some(0.5)),
+            ("およそ7km", Some(7.0)),
+            ("約", None),
+            ("約十キロ", None),
+            ("", None),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(
+                parse_distance_km(input),
+                expected,
+                "Failed on input: {}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_service_position() {
+        let dto = parse_fixture(include_str!("../tests/fixtures/basic.json"));
+        assert_eq!(dto.routes[0].summary.service_position, None);
+
+        let dto = parse_fixture(include_str!("../tests/fixtures/last_train.json"));
+        assert_eq!(
+            dto.routes[0].summary.service_position,
+            Some(ServicePosition::LastOfDay)
+        );
+
+        let cases = [
+            (
+                r#"{"isFirstTrain":true}"#,
+                Some(ServicePosition::FirstOfDay),
+            ),
+            (
+                r#"{"serviceLabel":"終電"}"#,
+                Some(ServicePosition::LastOfDay),
+            ),
+            (
+                r#"{"serviceLabel":"臨時"}"#,
+                Some(ServicePosition::Other("臨時".into())),
+            ),
+            (r#"{"isLastTrain":false}"#, None),
+        ];
+        for (input, expected) in cases {
+            let summary: Value = serde_json::from_str(input).unwrap();
+            assert_eq!(parse_service_position(&summary, Some("1"), 1), expected);
+        }
+
+        let plain = Value::Object(Default::default());
+        assert_eq!(
+            parse_service_position(&plain, Some("3"), 1),
+            Some(ServicePosition::FirstOfDay)
+        );
+        assert_eq!(parse_service_position(&plain, Some("3"), 2), None);
+        assert_eq!(parse_service_position(&plain, Some("2"), 2), None);
+    }
+
+    #[test]
+    fn test_notes() {
+        let dto = parse_fixture(include_str!("../tests/fixtures/basic.json"));
+        assert!(dto.routes[0].summary.notes.is_empty());
+        let json = serde_json::to_value(&dto).unwrap();
+        assert!(json["routes"][0]["summary"].get("notes").is_none());
+
+        let dto = parse_fixture(include_str!("../tests/fixtures/notes.json"));
+        assert_eq!(
+            dto.routes[0].summary.notes,
+            vec!["運賃は目安です", "一部列車は運休しています"]
+        );
+    }
+
+    #[test]
+    fn test_empty_result_classification() {
+        let err = next_data_to_transit_dto(
+            &load_next_data(include_str!("../tests/fixtures/place_not_found.json")).unwrap(),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<TransitError>(),
+            Some(&TransitError::PlaceNotFound {
+                field: PlaceField::To,
+                query: "渋やx".into(),
+                did_you_mean: vec![],
+            })
+        );
+
+        let mut root = load_next_data(include_str!("../tests/fixtures/basic.json")).unwrap();
+        root["props"]["pageProps"]["naviSearchParam"]["featureInfoList"] = Value::Array(vec![]);
+        let err = next_data_to_transit_dto(&root).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<TransitError>(),
+            Some(TransitError::NoRouteFound { from, to, .. }) if from == "新宿" && to == "渋谷"
+        ));
+
+        let err = next_data_to_transit_dto(
+            &load_next_data(include_str!("../tests/fixtures/out_of_service_area.json")).unwrap(),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<TransitError>(),
+            Some(&TransitError::OutOfServiceArea {
+                from: "知床岬".into(),
+                to: "羅臼".into(),
+                message: "指定された地点はサービス提供エリア外のため検索できません".into(),
+            })
+        );
+
+        // an unrelated error message keeps the usual classification
+        root["props"]["pageProps"]["naviSearchParam"]["errorInfo"] =
+            serde_json::json!({"message": "経路が見つかりませんでした"});
+        let err = next_data_to_transit_dto(&root).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<TransitError>(),
+            Some(TransitError::NoRouteFound { .. })
+        ));
+    }
+
+    #[test]
+    fn test_numeric_fields() {
+        let dto = parse_fixture(include_str!("../tests/fixtures/numeric_fields.json"));
+        let route = &dto.routes[0];
+        assert_eq!(route.summary.total_price_yen, Some(170));
+        assert_eq!(route.summary.transfer_count, Some(0));
+        assert_eq!(route.summary.duration_minutes, Some(13));
+        assert_eq!(route.summary.distance_km, Some(3.4));
+        assert_eq!(route.segments[0].fare_yen, Some(170));
+        assert_eq!(route.segments[0].duration_minutes, Some(7));
+
+        // same result as the string-typed page
+        let strings = parse_fixture(include_str!("../tests/fixtures/basic.json"));
+        assert_eq!(
+            serde_json::to_value(&dto.routes).unwrap(),
+            serde_json::to_value(&strings.routes).unwrap()
+        );
+
+        assert_eq!(as_u32_any(&serde_json::json!(1234)), Some(1234));
+        assert_eq!(as_u32_any(&serde_json::json!("1,234円")), Some(1234));
+        assert_eq!(as_u32_any(&serde_json::json!(-1)), None);
+        assert_eq!(as_u32_any(&serde_json::json!(1.5)), None);
+        assert_eq!(as_u32_any(&serde_json::json!(null)), None);
+
+        assert_eq!(as_minutes_any(&serde_json::json!(62)), Some(62));
+        assert_eq!(as_minutes_any(&serde_json::json!("1時間2分")), Some(62));
+        assert_eq!(as_km_any(&serde_json::json!(12)), Some(12.0));
+        assert_eq!(as_km_any(&serde_json::json!("500m")), Some(0.5));
+        assert_eq!(as_km_any(&serde_json::json!(-1.0)), None);
+    }
+
+    #[test]
+    fn test_fare_discount() {
+        let dto = parse_fixture(include_str!("../tests/fixtures/transfer_discount.json"));
+        let route = &dto.routes[0];
+        assert_eq!(route.summary.fare_discount, Some(Yen(70)));
+        let sum: u32 = route.segments.iter().filter_map(|s| s.fare_yen).sum();
+        assert_eq!(sum - 70, route.summary.total_price_yen.unwrap());
+        assert!(dto.warnings.is_empty(), "{:?}", dto.warnings);
+
+        // read from the note when the field is missing
+        let mut root =
+            load_next_data(include_str!("../tests/fixtures/transfer_discount.json")).unwrap();
+        let summary =
+            &mut root["props"]["pageProps"]["naviSearchParam"]["featureInfoList"][0]["summaryInfo"];
+        summary.as_object_mut().unwrap().remove("discountPrice");
+        summary["totalPrice"] = "370円".into();
+        let dto = next_data_to_transit_dto(&root).unwrap();
+        assert_eq!(dto.routes[0].summary.fare_discount, Some(Yen(70)));
+        assert_eq!(
+            dto.warnings,
+            ["route 1: segment fares 430 yen minus discount 70 yen do not match total 370 yen"]
+        );
+
+        let dto = parse_fixture(include_str!("../tests/fixtures/basic.json"));
+        assert_eq!(dto.routes[0].summary.fare_discount, None);
+
+        assert_eq!(yen_amount("乗継割引（１，０２０円引き）"), Some(1020));
+        let notes = |notes: &[&str]| notes.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+        let empty = serde_json::json!({});
+        for (note, discount) in [
+            ("乗継割引（１，０２０円引き）", Some(1020)),
+            ("70円引き", Some(70)),
+            ("乗継割引 50円", Some(50)),
+            ("学割適用外 170円", None),
+            ("往復割引 なし 200円", None),
+        ] {
+            assert_eq!(
+                parse_fare_discount(&empty, &notes(&[note])),
+                discount.map(Yen),
+                "{note}"
+            );
+        }
+        assert_eq!(yen_amount("乗継割引適用"), None);
+    }
+
+    #[test]
+    fn test_fare_caveats() {
+        let dto = parse_fixture(include_str!(
+            "../tests/fixtures/peak_season_shinkansen.json"
+        ));
+        let route = &dto.routes[0];
+        assert_eq!(
+            route.segments[0].fare_caveats,
+            ["繁忙期のため特急料金に200円加算", "指定席満席のため自由席"]
+        );
+        assert!(route.segments[1].fare_caveats.is_empty());
+        assert!(route.summary.has_fare_caveats);
+
+        let dto = parse_fixture(include_str!("../tests/fixtures/basic.json"));
+        assert!(
+            dto.routes[0]
+                .segments
+                .iter()
+                .all(|s| s.fare_caveats.is_empty())
+        );
+        assert!(!dto.routes[0].summary.has_fare_caveats);
+    }
+
+    #[test]
+    fn test_fare_basis() {
+        let dto = parse_fixture(include_str!("../tests/fixtures/fare_basis_mixed.json"));
+        let route = &dto.routes[0];
+        assert_eq!(route.summary.fare_basis, Some(FareBasis::Mixed));
+        let bases: Vec<_> = route.segments.iter().map(|s| s.fare_basis).collect();
+        assert_eq!(bases, [Some(FareBasis::Ic), Some(FareBasis::Ticket)]);
+        assert_eq!(
+            dto.warnings,
+            ["route 1: IC fare unavailable for 1 section(s); ticket fare used"]
+        );
+
+        let dto = parse_fixture(include_str!("../tests/fixtures/basic.json"));
+        assert_eq!(dto.routes[0].summary.fare_basis, None);
+        assert!(dto.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_seat_availability() {
+        let dto = parse_fixture(include_str!("../tests/fixtures/seat_availability.json"));
+        assert_eq!(
+            dto.routes[0].segments[0].seat_availability,
+            Some(SeatAvailability::Few)
+        );
+        assert_eq!(parse_seat_availability("○"), SeatAvailability::Available);
+        assert_eq!(parse_seat_availability("×"), SeatAvailability::Full);
+        assert_eq!(parse_seat_availability("－"), SeatAvailability::Unknown);
+
+        let dto = parse_fixture(include_str!("../tests/fixtures/basic.json"));
+        assert!(
+            dto.routes[0]
+                .segments
+                .iter()
+                .all(|s| s.seat_availability.is_none())
+        );
+    }
+
+    #[test]
+    fn test_top_page_redirect() {
+        let root = load_next_data(include_str!("../tests/fixtures/top_page.html")).unwrap();
+        let err = next_data_to_transit_dto(&root).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<TransitError>(),
+            Some(TransitError::InvalidSearch { reason }) if reason.contains("`/`")
+        ));
+    }
+
+    #[test]
+    fn test_select_best() {
+        let base = parse_fixture(include_str!("../tests/fixtures/basic.json")).routes[0].clone();
+        let route = |rank, minutes, yen| {
+            let mut r = base.clone();
+            r.rank = rank;
+            r.summary.duration_minutes = Some(minutes);
+            r.summary.total_price_yen = Some(yen);
+            r
+        };
+        let routes = [route(1, 30, 900), route(2, 45, 400), route(3, 50, 400)];
+
+        let cost_heavy = |r: &RouteDto| {
+            let yen = r.summary.total_price_yen.unwrap_or(u32::MAX) as f64;
+            let min = r.summary.duration_minutes.unwrap_or(u32::MAX) as f64;
+            -(yen * 10.0 + min)
+        };
+        assert_eq!(select_best(&routes, cost_heavy).map(|r| r.rank), Some(2));
+
+        let nan_for_fast = |r: &RouteDto| if r.rank == 1 { f64::NAN } else { 0.0 };
+        assert_eq!(select_best(&routes, nan_for_fast).map(|r| r.rank), Some(2));
+        assert!(select_best(&routes, |_| f64::NAN).is_none());
+        assert!(select_best(&[], |_| 0.0).is_none());
+    }
+
+    #[test]
+    fn test_unknown_segment_ratio() {
+        let dto = parse_fixture(include_str!("../tests/fixtures/unknown_lines.json"));
+        assert_eq!(dto.routes[1].segments.len(), 3);
+        assert!((dto.unknown_segment_ratio() - 0.6).abs() < 1e-9);
+
+        let dto = parse_fixture(include_str!("../tests/fixtures/basic.json"));
+        assert_eq!(dto.unknown_segment_ratio(), 0.0);
+
+        let empty = TransitDto {
+            routes: Vec::new(),
+            ..dto
+        };
+        assert_eq!(empty.unknown_segment_ratio(), 0.0);
+    }
+
+    #[test]
+    fn test_seat_fare_comparison() {
+        let base =
+            parse_fixture(include_str!("../tests/fixtures/shinkansen.json")).routes[0].clone();
+        assert!(base.offers_seat_classes());
+        let priced = |yen| {
+            let mut r = base.clone();
+            r.summary.total_price_yen = Some(yen);
+            r
+        };
+        let mut other_train = priced(30000);
+        other_train.segments[0].line = Some("ＪＲ新幹線のぞみ２２７号".into());
+
+        let comparison = SeatFareComparison::new(
+            base.clone(),
+            vec![
+                (SeatPreference::Reserved, priced(23920)),
+                (SeatPreference::GreenCar, other_train),
+            ],
+        );
+        let delta = |seat| comparison.get(seat).and_then(|f| f.delta_yen);
+        assert_eq!(delta(SeatPreference::NonReserved), Some(0));
+        assert_eq!(delta(SeatPreference::Reserved), Some(530));
+        assert_eq!(delta(SeatPreference::GreenCar), None);
+        assert!(!comparison.get(SeatPreference::GreenCar).unwrap().same_route);
+
+        let basic = parse_fixture(include_str!("../tests/fixtures/basic.json")).routes[0].clone();
+        assert!(!basic.offers_seat_classes());
+    }
+
+    #[test]
+    fn test_operator_matching() {
+        let base = parse_fixture(include_str!("../tests/fixtures/basic.json")).routes[0].clone();
+        let segment = |line: &str, operator: Option<&str>| SegmentDto {
+            line: Some(line.into()),
+            operator: operator.map(str::to_string),
+            mode: infer_mode(Some(line)),
+            ..base.segments[0].clone()
+        };
+
+        let jr = segment("ＪＲ山手線外回り", None);
+        assert!(jr.is_operated_by("ＪＲ"));
+        assert!(jr.is_operated_by(" jr "));
+        assert!(!jr.is_operated_by("東急"));
+        assert!(!jr.is_operated_by(""));
+
+        let tokyu = segment("東横線", Some("東急電鉄"));
+        assert!(tokyu.is_operated_by("東急"));
+        assert!(!tokyu.is_operated_by("東横"));
+
+        assert!(!segment("徒歩", None).is_operated_by("徒歩"));
+
+        let mut private = base.clone();
+        private.segments[0] = tokyu;
+        let routes: Vec<_> = [base.clone(), private, base.clone()]
+            .into_iter()
+            .zip(1..)
+            .map(|(route, rank)| RouteDto { rank, ..route })
+            .collect();
+        let kept = avoid_operators(routes.clone(), &["東急".into()]);
+        assert_eq!(kept.iter().map(|r| r.rank).collect::<Vec<_>>(), [1, 3]);
+        let kept = avoid_operators(routes, &["JR".into(), "東急".into()]);
+        assert!(kept.is_empty());
+
+        let mut root = load_next_data(include_str!("../tests/fixtures/basic.json")).unwrap();
+        let features = &mut root["props"]["pageProps"]["naviSearchParam"]["featureInfoList"];
+        features[0]["edgeInfoList"][0]["companyName"] = "ＪＲ東日本".into();
+        let dto = next_data_to_transit_dto(&root).unwrap();
+        assert_eq!(
+            dto.routes[0].segments[0].operator.as_deref(),
+            Some("ＪＲ東日本")
+        );
+    }
+
+    #[test]
+    fn test_only_operators() {
+        // JR only (with a walk) and JR + 東急
+        let jr = parse_fixture(include_str!("../tests/fixtures/shinkansen.json")).routes;
+        let mixed = parse_fixture(include_str!("../tests/fixtures/transfer_heavy.json")).routes;
+        let routes: Vec<_> = mixed
+            .into_iter()
+            .chain(jr)
+            .zip(1..)
+            .map(|(route, rank)| RouteDto { rank, ..route })
+            .collect();
+
+        let kept = only_operators(routes.clone(), &["JR".into()]);
+        assert_eq!(kept.len(), 1);
+        assert_eq!(kept[0].rank, 2);
+        assert!(
+            kept[0].segments[0]
+                .line
+                .as_deref()
+                .unwrap()
+                .contains("のぞみ")
+        );
+
+        let kept = only_operators(routes.clone(), &["JR".into(), "東急".into()]);
+        assert_eq!(kept.iter().map(|r| r.rank).collect::<Vec<_>>(), [1, 2]);
+        assert!(only_operators(routes.clone(), &["京王".into()]).is_empty());
+        assert_eq!(only_operators(routes.clone(), &[]).len(), routes.len());
+
+        // a route of walks only has no ride to check
+        let mut walk = routes[1].clone();
+        walk.segments.retain(|s| s.mode == "walk");
+        assert!(!walk.segments.is_empty());
+        assert!(only_operators(vec![walk], &["JR".into()]).is_empty());
+    }
+
+    #[test]
+    fn test_time_entries() {
+        let entries = |json: &str| time_entries(&serde_json::from_str(json).unwrap());
+
+        let parsed = entries(r#"{"timeInfo":[{"time":"09:39"},{"time":"09:41"}]}"#);
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[0].time(), Some("09:39"));
+
+        // unreadable entries keep their position
+        let parsed = entries(r#"{"timeInfo":[{"time":930},{"time":""},{"time":"09:45"}]}"#);
+        assert_eq!(parsed.len(), 3);
+        assert_eq!(parsed[0], TimeEntry::default());
+        assert_eq!(parsed[1].time(), None);
+        assert_eq!(parsed[2].time(), Some("09:45"));
+
+        assert!(entries(r#"{"stationName":"渋谷"}"#).is_empty());
+    }
+
+    #[test]
+    fn test_onboard_minutes() {
+        let dto = parse_fixture(include_str!("../tests/fixtures/basic.json"));
+        let summary = &dto.routes[0].summary;
+        // 7 minutes on the Yamanote line, 6 minutes walking
+        assert_eq!(summary.duration_minutes, Some(13));
+        assert_eq!(summary.onboard_minutes, Some(7));
+
+        let mut root = load_next_data(include_str!("../tests/fixtures/basic.json")).unwrap();
+        let features = &mut root["props"]["pageProps"]["naviSearchParam"]["featureInfoList"];
+        features[0]["summaryInfo"]["timeOnBoard"] = "5分".into();
+        let dto = next_data_to_transit_dto(&root).unwrap();
+        assert_eq!(dto.routes[0].summary.onboard_minutes, Some(5));
+
+        let dto = parse_fixture(include_str!("../tests/fixtures/zero_edges.json"));
+        assert_eq!(dto.routes[0].summary.onboard_minutes, None);
+    }
+
+    #[test]
+    fn test_load_next_data_leading_bom_and_whitespace() {
+        let json = include_str!("../tests/fixtures/basic.json");
+        let expected = load_next_data(json).unwrap();
+
+        for input in [
+            format!("\u{feff}{json}"),
+            format!("\r\n\n \t{json}"),
+            format!("\n\u{feff}\u{feff}  {json}"),
+            format!("\u{feff}\n<html><script id=\"__NEXT_DATA__\">{json}</script></html>"),
+        ] {
+            assert_eq!(load_next_data(&input).unwrap(), expected);
+        }
+        assert!(load_next_data("\u{feff}  ").is_err());
+    }
+
+    #[test]
+    fn test_earliest_and_cheapest_route() {
+        let mut dto = parse_fixture(include_str!("../tests/fixtures/basic.json"));
+        let base = dto.routes[0].clone();
+        let route = |rank, arrival_minutes: Option<i64>, yen| {
+            let mut r = base.clone();
+            r.rank = rank;
+            r.summary.arrival_time = arrival_minutes.and_then(|m| {
+                base.summary
+                    .arrival_time
+                    .map(|t| t + chrono::Duration::minutes(m))
+            });
+            r.summary.total_price_yen = yen;
+            r
+        };
+        let rank = |r: Option<&RouteDto>| r.map(|r| r.rank);
+
+        dto.routes = vec![
+            route(1, Some(10), Some(300)),
+            route(2, Some(0), Some(500)),
+            route(3, Some(0), Some(400)),
+            route(4, None, Some(200)),
+            route(5, Some(5), None),
+            route(6, Some(0), Some(400)),
+        ];
+        // 2, 3 and 6 arrive first; 3 and 6 are cheaper; 3 has the lower rank
+        assert_eq!(rank(dto.earliest_route()), Some(3));
+        assert_eq!(rank(dto.cheapest_route()), Some(4));
+
+        dto.routes = vec![
+            route(1, Some(10), Some(200)),
+            route(2, Some(5), Some(200)),
+            route(3, None, Some(200)),
+        ];
+        assert_eq!(rank(dto.cheapest_route()), Some(2));
+
+        dto.routes = vec![route(1, None, None)];
+        assert!(dto.earliest_route().is_none());
+        assert!(dto.cheapest_route().is_none());
+        dto.routes.clear();
+        assert!(dto.earliest_route().is_none());
+    }
+
+    #[test]
+    fn test_budget_filter() {
+        let base = parse_fixture(include_str!("../tests/fixtures/basic.json")).routes[0].clone();
+        let route = |rank, yen, arrival_minutes: i64, transfers| {
+            let mut r = base.clone();
+            r.rank = rank;
+            r.summary.total_price_yen = yen;
+            r.summary.arrival_time = base
+                .summary
+                .arrival_time
+                .map(|t| t + chrono::Duration::minutes(arrival_minutes));
+            r.summary.transfer_count = Some(transfers);
+            r
+        };
+        let routes = vec![
+            route(1, Some(1600), 0, 0),
+            route(2, Some(1500), 10, 2),
+            route(3, None, 5, 1),
+            route(4, Some(900), 20, 1),
+        ];
+        let ranks = |routes: Vec<RouteDto>| routes.iter().map(|r| r.rank).collect::<Vec<_>>();
+
+        let budget = Budget::from(1500);
+        assert_eq!(
+            ranks(budget.filter(routes.clone(), TransitCriteria::EarliestArrival)),
+            [2, 4]
+        );
+        assert_eq!(
+            ranks(budget.filter(routes.clone(), TransitCriteria::LowestCost)),
+            [4, 2]
+        );
+
+        let lenient = Budget {
+            keep_unknown_fares: true,
+            ..budget
+        };
+        assert_eq!(
+            ranks(lenient.filter(routes.clone(), TransitCriteria::EarliestArrival)),
+            [3, 2, 4]
+        );
+        assert_eq!(
+            ranks(lenient.filter(routes.clone(), TransitCriteria::LowestCost)),
+            [4, 2, 3]
+        );
+        assert_eq!(
+            ranks(lenient.filter(routes, TransitCriteria::FewestTransfers)),
+            [3, 4, 2]
+        );
+    }
+
+    #[test]
+    fn test_segment_notes() {
+        let dto = parse_fixture(include_str!("../tests/fixtures/segment_notes.json"));
+        let segments = &dto.routes[0].segments;
+        assert_eq!(
+            segments[0].notes,
+            vec!["進行方向後ろ寄り", "階段&エスカレーター付近"]
+        );
+        assert_eq!(segments[1].notes, vec!["この区間は運賃別途"]);
+        assert!(dto.routes[0].summary.notes.is_empty());
+
+        let dto = parse_fixture(include_str!("../tests/fixtures/basic.json"));
+        assert!(dto.routes[0].segments.iter().all(|s| s.notes.is_empty()));
+    }
+
+    #[test]
+    fn test_to_mermaid() {
+        let mut route =
+            parse_fixture(include_str!("../tests/fixtures/basic.json")).routes[0].clone();
+        route.segments.truncate(2);
+        assert_eq!(
+            route.to_mermaid(),
+            "flowchart LR
+    n0[\"新宿\"]
+    n1[\"渋谷\"]
+    n0 -->|\"ＪＲ山手線外回り<br/>09:32-09:39\"| n1
+    n2[\"渋谷ヒカリエ\"]
+    n1 -.->|\"徒歩<br/>09:39-09:45\"| n2
+"
+        );
+
+        let en = FormatOptions {
+            locale: crate::format::Locale::En,
+            ..FormatOptions::default()
+        };
+        assert!(
+            route
+                .to_mermaid_with(&en)
+                .contains(r#"n1 -.->|"Walk<br/>09:39-09:45"| n2"#)
+        );
+
+        route.segments[1].to = r#"渋谷"ヒカリエ""#.into();
+        assert!(
+            route
+                .to_mermaid()
+                .contains(r#"n2["渋谷#quot;ヒカリエ#quot;"]"#)
+        );
+    }
+
+    #[test]
+    fn test_summary_format_styles() {
+        use crate::format::{CurrencyStyle, DurationStyle, Locale};
+
+        let mut summary = parse_fixture(include_str!("../tests/fixtures/basic.json")).routes[0]
+            .summary
+            .clone();
+        summary.duration_minutes = Some(65);
+        summary.transfer_count = Some(1);
+        summary.total_price_yen = Some(1234);
+
+        let cases = [
+            (
+                CurrencyStyle::Kanji,
+                DurationStyle::Japanese,
+                Locale::Ja,
+                "09:32→09:45 (1時間5分, 乗換1回, 1,234円)",
+            ),
+            (
+                CurrencyStyle::Kanji,
+                DurationStyle::Compact,
+                Locale::Ja,
+                "09:32→09:45 (1h05, 乗換1回, 1,234円)",
+            ),
+            (
+                CurrencyStyle::Symbol,
+                DurationStyle::Japanese,
+                Locale::Ja,
+                "09:32→09:45 (1時間5分, 乗換1回, ¥1,234)",
+            ),
+            (
+                CurrencyStyle::Symbol,
+                DurationStyle::Compact,
+                Locale::En,
+                "09:32→09:45 (1h05, 1 transfer, ¥1,234)",
+            ),
+            (
+                CurrencyStyle::Code,
+                DurationStyle::Japanese,
+                Locale::Ja,
+                "09:32→09:45 (1時間5分, 乗換1回, 1 234 JPY)",
+            ),
+            (
+                CurrencyStyle::Code,
+                DurationStyle::Compact,
+                Locale::En,
+                "09:32→09:45 (1h05, 1 transfer, 1 234 JPY)",
+            ),
+        ];
+        for (currency_style, duration_style, locale, expected) in cases {
+            let options = FormatOptions {
+                currency_style,
+                duration_style,
+                locale,
+            };
+            assert_eq!(summary.format(&options), expected, "{options:?}");
+        }
+
+        summary.total_price_yen = None;
+        summary.transfer_count = None;
+        summary.duration_minutes = None;
+        assert_eq!(summary.format(&FormatOptions::default()), "09:32→09:45");
+    }
+
+    #[test]
+    fn test_blank_display_names_fall_back_to_stations() {
+        let dto = parse_fixture(include_str!("../tests/fixtures/blank_display_names.json"));
+        assert_eq!(dto.from, "新宿");
+        assert_eq!(dto.to, "渋谷ヒカリエ");
+
+        let dto = parse_fixture(include_str!("../tests/fixtures/basic.json"));
+        assert_eq!((dto.from.as_str(), dto.to.as_str()), ("新宿", "渋谷"));
+    }
+
+    #[test]
+    fn test_heterogeneous_features_skip_non_routes() {
+        // synthetic: the fixture is hand-written, not a captured page
+        let dto = parse_fixture(include_str!(
+            "../tests/fixtures/heterogeneous_features.json"
+        ));
+        let ranks: Vec<_> = dto.routes.iter().map(|r| r.rank).collect();
+        assert_eq!(ranks, [1, 3]);
+        assert_eq!(dto.routes[1].summary.total_price_yen, Some(210));
+        assert_eq!(
+            dto.warnings,
+            ["featureInfoList[1]: no summaryInfo or edgeInfoList; entry skipped"]
+        );
+    }
+
+    #[test]
+    fn test_duration_from_times() {
+        let dto = parse_fixture(include_str!("../tests/fixtures/basic.json"));
+        assert_eq!(dto.routes[0].summary.duration_from_times(), Some(0));
+
+        let dto = parse_fixture(include_str!("../tests/fixtures/duration_mismatch.json"));
+        let mut summary = dto.routes[0].summary.clone();
+        assert_eq!(summary.duration_minutes, Some(73));
+        assert_eq!(summary.duration_from_times(), Some(-60));
+
+        summary.arrival_time = None;
+        assert_eq!(summary.duration_from_times(), None);
+    }
+
+    #[test]
+    fn test_rail_name_destination_stripped() {
+        let dto = parse_fixture(include_str!("../tests/fixtures/rail_name_destination.json"));
+        let segment = &dto.routes[0].segments[0];
+        assert_eq!(segment.line.as_deref(), Some("ＪＲ山手線外回り"));
+        assert_eq!(segment.destination.as_deref(), Some("品川"));
+        assert_eq!(segment.mode, "rail");
+
+        assert_eq!(
+            strip_destination("東急東横線 横浜行き", "横浜"),
+            Some("東急東横線")
+        );
+        assert_eq!(
+            strip_destination("京王線（高尾山口行）", "高尾山口"),
+            Some("京王線")
+        );
+        assert_eq!(strip_destination("ＪＲ山手線外回り", "品川"), None);
+        assert_eq!(strip_destination("品川行", "品川"), None);
+    }
+
+    #[test]
+    fn test_total_fare_for() {
+        let dto = parse_fixture(include_str!("../tests/fixtures/basic.json"));
+        let route = &dto.routes[0];
+        assert_eq!(route.summary.total_fare(), Some(Yen(170)));
+        assert_eq!(route.summary.total_fare_for(3), Some(Yen(510)));
+        assert_eq!(
+            route
+                .segments
+                .iter()
+                .map(SegmentDto::fare)
+                .sum::<Option<Yen>>(),
+            None
+        );
+
+        let mut summary = route.summary.clone();
+        summary.total_price_yen = Some(u32::MAX);
+        assert_eq!(summary.total_fare_for(1), Some(Yen(u32::MAX)));
+        assert_eq!(summary.total_fare_for(2), None);
+        summary.total_price_yen = None;
+        assert_eq!(summary.total_fare_for(2), None);
+    }
+
+    #[test]
+    fn test_transfer_station_times() {
+        let dto = parse_fixture(include_str!("../tests/fixtures/transfer_heavy.json"));
+        assert!(dto.warnings.is_empty(), "{:?}", dto.warnings);
+        let route = &dto.routes[0];
+        let segments = &route.segments;
+        assert_eq!(segments.len(), 3);
+
+        let at = |s: &str| chrono::DateTime::parse_from_rfc3339(s).unwrap();
+        // marked entries at 渋谷, positional ones at 二子玉川 (past midnight)
+        assert_eq!(
+            segments[0].arrival_time,
+            Some(at("2025-12-18T23:52:00+09:00"))
+        );
+        assert_eq!(
+            segments[1].departure_time,
+            Some(at("2025-12-18T23:58:00+09:00"))
+        );
+        assert_eq!(
+            segments[1].arrival_time,
+            Some(at("2025-12-19T00:12:00+09:00"))
+        );
+        assert_eq!(
+            segments[2].departure_time,
+            Some(at("2025-12-19T00:15:00+09:00"))
+        );
+        assert_eq!(segments[2].arrival_time, route.summary.arrival_time);
+
+        let waits: Vec<i64> = segments
+            .windows(2)
+            .map(|pair| {
+                (pair[1].departure_time.unwrap() - pair[0].arrival_time.unwrap()).num_minutes()
+            })
+            .collect();
+        assert_eq!(waits, [6, 3]);
+        assert_eq!(route.summary.min_transfer_minutes, Some(3));
+        for segment in segments {
+            let ride = segment.arrival_time.unwrap() - segment.departure_time.unwrap();
+            assert_eq!(Some(ride.num_minutes() as u32), segment.duration_minutes);
+        }
+
+        // the transfer at 大手町 is no longer read as 0 minutes
+        let dto = parse_fixture(include_str!("../tests/fixtures/transfer_discount.json"));
+        assert_eq!(dto.routes[0].summary.min_transfer_minutes, Some(3));
+    }
+
+    #[test]
+    fn test_tight_transfers() {
+        let dto = parse_fixture(include_str!("../tests/fixtures/basic.json"));
+        let ride = dto.routes[0].segments[0].clone();
+        let walk = dto.routes[0].segments[1].clone();
+        let at = |segment: &SegmentDto, depart: i64, arrive: Option<i64>| {
+            let base = ride.departure_time.unwrap();
+            SegmentDto {
+                departure_time: Some(base + chrono::Duration::minutes(depart)),
+                arrival_time: arrive.map(|m| base + chrono::Duration::minutes(m)),
+                ..segment.clone()
+            }
+        };
+        let route = RouteDto {
+            segments: vec![
+                at(&ride, 0, Some(10)),
+                // 2 minutes, walk included
+                at(&walk, 10, Some(11)),
+                at(&ride, 12, Some(20)),
+                // 5 minutes
+                at(&ride, 25, None),
+                // unknown: the previous arrival is missing
+                at(&ride, 40, Some(50)),
+            ],
+            ..dto.routes[0].clone()
+        };
+
+        let tight = route.tight_transfers(RouteDto::DEFAULT_TIGHT_TRANSFER_MINUTES);
+        assert_eq!(
+            tight,
+            [TightTransfer {
+                arriving_segment: 0,
+                departing_segment: 2,
+                station: ride.from.clone(),
+                wait_minutes: 2,
+            }]
+        );
+        let waits: Vec<_> = route
+            .tight_transfers(6)
+            .iter()
+            .map(|t| t.wait_minutes)
+            .collect();
+        assert_eq!(waits, [2, 5]);
+        assert_eq!(route.transfers_with_unknown_wait(), [(3, 4)]);
+        assert_eq!(
+            transfer_waits(&route.segments)
+                .into_iter()
+                .filter_map(|(_, _, w)| w)
+                .min(),
+            Some(2)
+        );
+
+        // a direct route has no transfer
+        assert_eq!(dto.routes[0].summary.min_transfer_minutes, None);
+        assert!(dto.routes[0].tight_transfers(60).is_empty());
+    }
+
+    #[test]
+    fn test_stop_events() {
+        let dto = parse_fixture(include_str!("../tests/fixtures/basic.json"));
+        let mut route = dto.routes[0].clone();
+        let events = route.stop_events();
+        assert_eq!(events.len(), route.segments.len() * 2);
+        let summary: Vec<_> = events
+            .iter()
+            .map(|e| {
+                (
+                    e.kind,
+                    e.station.as_str(),
+                    e.time.map(|t| t.format("%H:%M").to_string()),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary[..2],
+            [
+                (StopEventKind::Departure, "新宿", Some("09:32".into())),
+                (StopEventKind::Arrival, "渋谷", Some("09:39".into())),
+            ]
+        );
+        assert!(events.windows(2).all(|w| w[0].time <= w[1].time));
+        assert_eq!(events[0].line.as_deref(), Some("ＪＲ山手線外回り"));
+
+        // a missing time keeps the event in place
+        route.segments[0].arrival_time = None;
+        let events = route.stop_events();
+        assert_eq!(events[1].kind, StopEventKind::Arrival);
+        assert_eq!(events[1].station, "渋谷");
+        assert_eq!(events[1].time, None);
+        assert_eq!(events.len(), route.segments.len() * 2);
+    }
+
+    #[test]
+    fn test_departs_next_day() {
+        let dto = parse_fixture(include_str!("../tests/fixtures/next_day_departure.json"));
+        let route = &dto.routes[0];
+        assert_eq!(route.summary.departs_next_day, Some(true));
+        assert_eq!(
+            route.summary.departure_time.unwrap().to_rfc3339(),
+            "2025-12-19T00:10:00+09:00"
+        );
+        assert_eq!(
+            route.segments[0].departure_time.unwrap().to_rfc3339(),
+            "2025-12-19T00:10:00+09:00"
+        );
+        assert_eq!(
+            route.summary.arrival_time.unwrap().to_rfc3339(),
+            "2025-12-19T00:32:00+09:00"
+        );
+        assert_eq!(route.summary.spans_midnight, Some(false));
+        assert_eq!(
+            dto.warnings,
+            ["route 1: departs the day after the search date"]
+        );
+        let json = serde_json::to_value(&route.summary).unwrap();
+        assert_eq!(json["departsNextDay"], true);
+
+        let dto = parse_fixture(include_str!("../tests/fixtures/basic.json"));
+        assert_eq!(dto.routes[0].summary.departs_next_day, Some(false));
+        assert!(dto.warnings.is_empty());
+
+        // a departure slightly before the echoed search time stays on the
+        // search date
+        let mut root = load_next_data(include_str!("../tests/fixtures/basic.json")).unwrap();
+        root["props"]["pageProps"]["pageQuery"]["m2"] = "5".into();
+        let dto = next_data_to_transit_dto(&root).unwrap();
+        let route = &dto.routes[0];
+        assert_eq!(route.summary.departs_next_day, Some(false));
+        assert_eq!(
+            route.summary.departure_time.unwrap().to_rfc3339(),
+            "2025-12-18T09:32:00+09:00"
+        );
+        assert_eq!(
+            route.segments[0].departure_time.unwrap().to_rfc3339(),
+            "2025-12-18T09:32:00+09:00"
+        );
+        assert!(dto.warnings.is_empty(), "{:?}", dto.warnings);
+
+        // a late search answered with the first train of the next morning
+        let mut root = load_next_data(include_str!("../tests/fixtures/basic.json")).unwrap();
+        let props = &mut root["props"]["pageProps"];
+        props["pageQuery"]["hh"] = "23".into();
+        props["pageQuery"]["m1"] = "0".into();
+        props["pageQuery"]["m2"] = "0".into();
+        props["naviSearchParam"]["featureInfoList"][0]["summaryInfo"]["departureTime"] =
+            "05:10".into();
+        let dto = next_data_to_transit_dto(&root).unwrap();
+        let route = &dto.routes[0];
+        assert_eq!(route.summary.departs_next_day, Some(true));
+        assert_eq!(
+            route.summary.departure_time.unwrap().to_rfc3339(),
+            "2025-12-19T05:10:00+09:00"
+        );
+
+        // only departure-time searches are checked
+        let dto = parse_fixture(include_str!("../tests/fixtures/last_train.json"));
+        assert_eq!(dto.routes[0].summary.departs_next_day, None);
+        let json = serde_json::to_value(&dto.routes[0].summary).unwrap();
+        assert!(json.get("departsNextDay").is_none());
+    }
+
+    #[test]
+    fn test_calendar_days() {
+        let summary = |fixture| parse_fixture(fixture).routes[0].summary.clone();
+
+        let same_day = summary(include_str!("../tests/fixtures/basic.json"));
+        assert_eq!(same_day.spans_midnight, Some(false));
+        assert_eq!(same_day.calendar_days, Some(1));
+
+        let bus = summary(include_str!("../tests/fixtures/overnight_bus.json"));
+        assert_eq!(
+            bus.departure_time.unwrap().to_rfc3339(),
+            "2025-12-18T23:10:00+09:00"
+        );
+        assert_eq!(
+            bus.arrival_time.unwrap().to_rfc3339(),
+            "2025-12-19T07:05:00+09:00"
+        );
+        assert_eq!(bus.spans_midnight, Some(true));
+        assert_eq!(bus.calendar_days, Some(2));
+        assert_eq!(bus.duration_from_times(), Some(0));
+
+        let dto = parse_fixture(include_str!("../tests/fixtures/multi_day_ferry.json"));
+        let ferry = &dto.routes[0];
+        assert_eq!(ferry.segments[0].mode, "ferry");
+        assert_eq!(
+            ferry.summary.arrival_time.unwrap().to_rfc3339(),
+            "2025-12-19T11:00:00+09:00"
+        );
+        assert_eq!(ferry.summary.spans_midnight, Some(true));
+        assert_eq!(ferry.summary.calendar_days, Some(2));
+        assert_eq!(ferry.summary.duration_from_times(), Some(0));
+        assert_eq!(ferry.segments[0].arrival_time, ferry.summary.arrival_time);
+
+        assert_eq!(calendar_days(same_day.departure_time, None), None);
+    }
+
+    #[test]
+    fn test_to_json_naive_local() {
+        let dto = parse_fixture(include_str!("../tests/fixtures/basic.json"));
+        let json = dto.to_json_naive_local().unwrap();
+        assert!(
+            json.contains(r#""departureTime":"2025-12-18T09:32""#),
+            "{json}"
+        );
+        assert!(!json.contains("+09:00"), "{json}");
+        assert!(serde_json::to_string(&dto).unwrap().contains("+09:00"));
+    }
+
+    #[test]
+    fn test_is_reservable() {
+        let dto = parse_fixture(include_str!("../tests/fixtures/shinkansen.json"));
+        let segments = &dto.routes[0].segments;
+        assert!(segments[0].is_reservable());
+        assert!(!segments[1].is_reservable());
+
+        let mut express = segments[1].clone();
+        express.mode = "rail".into();
+        express.line = Some("ＪＲ特急ひたち".into());
+        assert!(express.is_reservable());
+        for commuter in ["京王線特急", "東急東横線特急", "小田急線快速急行", "特急"]
+        {
+            express.line = Some(commuter.into());
+            assert!(!express.is_reservable(), "{commuter}");
+        }
+        express.line = Some("ＪＲ特急ひたち".into());
+        express.mode = "walk".into();
+        assert!(!express.is_reservable());
+
+        let dto = parse_fixture(include_str!("../tests/fixtures/basic.json"));
+        assert!(dto.routes[0].segments.iter().all(|s| !s.is_reservable()));
+    }
+
+    #[test]
+    fn test_named_train() {
+        let dto = parse_fixture(include_str!("../tests/fixtures/shinkansen.json"));
+        let segment = &dto.routes[0].segments[0];
+        assert_eq!(segment.line.as_deref(), Some("ＪＲ新幹線のぞみ２２５号"));
+        assert_eq!(segment.train_name.as_deref(), Some("のぞみ"));
+        assert_eq!(segment.train_number.as_deref(), Some("225"));
+        assert_eq!(dto.routes[0].segments[1].train_name, None);
+
+        assert_eq!(
+            parse_named_train("特急あずさ5号"),
+            (Some("あずさ".into()), Some("5".into()))
+        );
+        assert_eq!(parse_named_train("ＪＲ山手線外回り"), (None, None));
+        assert_eq!(parse_named_train("一号"), (None, None));
+    }
+
+    #[test]
+    fn test_notification_summary() {
+        let dto = parse_fixture(include_str!("../tests/fixtures/basic.json"));
+        let summary = dto.notification_summary(1).unwrap();
+        assert_eq!(summary.first_line.as_deref(), Some("ＪＲ山手線外回り"));
+        assert_eq!(summary.transfer_count, Some(0));
+        assert_eq!(summary.total_price_yen, Some(170));
+        assert_eq!(
+            serde_json::to_value(&summary).unwrap(),
+            serde_json::json!({
+                "departureTime": "2025-12-18T09:32+09:00",
+                "arrivalTime": "2025-12-18T09:45+09:00",
+                "firstLine": "ＪＲ山手線外回り",
+                "transferCount": 0,
+                "totalPriceYen": 170,
+            })
+        );
+        assert!(dto.notification_summary(2).is_none());
+    }
+
+    #[test]
+    fn test_boarding_position() {
+        let dto = parse_fixture(include_str!("../tests/fixtures/boarding_position.json"));
+        let positions: Vec<_> = dto.routes[0]
+            .segments
+            .iter()
+            .map(|s| s.boarding_position.clone())
+            .collect();
+        assert_eq!(
+            positions,
+            [
+                Some(BoardingPosition::Front),
+                Some(BoardingPosition::Car(8))
+            ]
+        );
+        assert_eq!(parse_boarding_position("後ろ寄り"), BoardingPosition::Rear);
+        for middle in ["中ほど", "中央", "中ほどの車両"] {
+            assert_eq!(parse_boarding_position(middle), BoardingPosition::Middle);
+        }
+        assert_eq!(
+            parse_boarding_position("中目黒方"),
+            BoardingPosition::Other("中目黒方".into())
+        );
+        assert_eq!(
+            parse_boarding_position("１２号車付近"),
+            BoardingPosition::Car(12)
+        );
+        assert_eq!(
+            parse_boarding_position("階段付近"),
+            BoardingPosition::Other("階段付近".into())
+        );
+
+        let dto = parse_fixture(include_str!("../tests/fixtures/basic.json"));
+        assert!(dto.routes[0].segments[0].boarding_position.is_none());
+    }
+
+    #[cfg(feature = "schemars")]
+    #[test]
+    fn test_schema_has_boarding_position() {
+        let schema = serde_json::to_value(schemars::schema_for!(TransitDto)).unwrap();
+        let segment = &schema["$defs"]["SegmentDto"]["properties"];
+        assert!(segment.get("boardingPosition").is_some());
+    }
+
+    #[test]
+    fn test_derive_badges() {
+        let dto = parse_fixture(include_str!("../tests/fixtures/badges_null.json"));
+        let badges: Vec<_> = dto
+            .routes
+            .iter()
+            .map(|r| (r.summary.is_fast, r.summary.is_easy, r.summary.is_cheap))
+            .collect();
+        assert_eq!(
+            badges,
+            [
+                (Some(true), Some(false), Some(false)),
+                (Some(false), Some(true), Some(false)),
+                (Some(false), Some(false), Some(true)),
+            ]
+        );
+        assert_eq!(dto.warnings.len(), 1);
+
+        let mut root = load_next_data(include_str!("../tests/fixtures/badges_null.json")).unwrap();
+        let list = &mut root["props"]["pageProps"]["naviSearchParam"]["featureInfoList"];
+        list.as_array_mut().unwrap().truncate(1);
+        let dto = next_data_to_transit_dto(&root).unwrap();
+        assert_eq!(dto.routes[0].summary.is_fast, None);
+        assert!(dto.warnings.is_empty());
+
+        let dto = parse_fixture(include_str!("../tests/fixtures/basic.json"));
+        assert_eq!(dto.routes[0].summary.is_fast, Some(true));
+        assert!(dto.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_web_url() {
+        let mut dto = parse_fixture(include_str!("../tests/fixtures/basic.json"));
+        let route = dto.routes.pop().unwrap();
+        let args = TransitArgs {
+            from: "新宿".into(),
+            to: "渋谷".into(),
+            date: DateTime::parse_from_rfc3339("2025-12-18T09:30:00+09:00").unwrap(),
+            rank: 3,
+            ..Default::default()
+        };
+        let expected = format!(
+            "{}/search/result?from=%E6%96%B0%E5%AE%BF&to=%E6%B8%8B%E8%B0%B7\
+             &y=2025&m=12&d=18&hh=9&m1=3&m2=0&type=1&s=0&no=1\
+             &ticket=normal&expkind=1&ws=3&al=1&shin=1&ex=1&hb=1&lb=1&sr=1",
+            *BASE_URL
+        );
+        assert_eq!(route.web_url(&args), expected);
+    }
+
+    #[test]
+    fn test_last_train_reachable() {
+        let dto = parse_fixture(include_str!("../tests/fixtures/last_train.json"));
+        assert!(dto.last_train_reachable());
+
+        let dto = parse_fixture(include_str!(
+            "../tests/fixtures/last_train_unreachable.json"
+        ));
+        assert!(!dto.last_train_reachable());
+    }
+
+    #[test]
+    fn test_segment_ids() {
+        let a = parse_fixture(include_str!("../tests/fixtures/basic.json"));
+        let b = parse_fixture(include_str!("../tests/fixtures/basic.json"));
+        let ids = a.routes[0].segment_ids();
+        assert_eq!(ids, b.routes[0].segment_ids());
+        assert_eq!(ids.len(), 2);
+        assert_eq!(ids[0], "150f1b903455beba");
+        assert_ne!(ids[0], ids[1]);
+        assert!(ids.iter().all(|id| id.len() == 16));
+
+        let segment = &a.routes[0].segments[0];
+        assert_ne!(segment.id(1, 0), segment.id(2, 0));
+        assert_ne!(segment.id(1, 0), segment.id(1, 1));
+    }
+
+    #[test]
+    fn test_fare_is_estimate() {
+        let dto = parse_fixture(include_str!("../tests/fixtures/fare_estimate.json"));
+        assert!(dto.routes[0].summary.fare_is_estimate);
+        assert_eq!(dto.routes[0].summary.total_price_yen, Some(170));
+
+        let dto = parse_fixture(include_str!("../tests/fixtures/basic.json"));
+        assert!(!dto.routes[0].summary.fare_is_estimate);
+        let json = serde_json::to_value(&dto).unwrap();
+        assert_eq!(json["routes"][0]["summary"]["fareIsEstimate"], false);
+    }
+
+    #[test]
+    fn test_routes_without_segments() {
+        let dto = parse_fixture(include_str!("../tests/fixtures/zero_edges.json"));
+        let segments = &dto.routes[0].segments;
+        assert_eq!(segments.len(), 1);
+        assert_eq!(
+            (segments[0].from.as_str(), segments[0].to.as_str()),
+            ("新宿", "渋谷")
+        );
+        assert_eq!(segments[0].mode, "unknown");
+        assert_eq!(
+            segments[0].departure_time,
+            dto.routes[0].summary.departure_time
+        );
+        assert_eq!(segments[0].duration_minutes, Some(13));
+        assert_eq!(dto.warnings.len(), 1);
+
+        let dto = parse_fixture(include_str!("../tests/fixtures/one_edge.json"));
+        let segments = &dto.routes[0].segments;
+        assert_eq!(segments.len(), 1);
+        assert_eq!(segments[0].line.as_deref(), Some("ＪＲ山手線外回り"));
+        assert_eq!(segments[0].mode, "rail");
+
+        let mut root = load_next_data(include_str!("../tests/fixtures/zero_edges.json")).unwrap();
+        let summary =
+            &mut root["props"]["pageProps"]["naviSearchParam"]["featureInfoList"][0]["summaryInfo"];
+        summary["arrivalTime"] = "".into();
+        let err = next_data_to_transit_dto(&root).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<TransitError>(),
+            Some(TransitError::NoRouteFound { .. })
+        ));
+    }
+
+    #[test]
+    fn test_max_routes() {
+        let mut root = load_next_data(include_str!("../tests/fixtures/basic.json")).unwrap();
+        let list = &mut root["props"]["pageProps"]["naviSearchParam"]["featureInfoList"];
+        let feature = list[0].clone();
+        *list = Value::Array(vec![feature; 60]);
+
+        let dto = next_data_to_transit_dto(&root).unwrap();
+        assert_eq!(dto.routes.len(), ParseOptions::DEFAULT_MAX_ROUTES);
+        assert_eq!(dto.warnings.len(), 1);
+
+        let dto = next_data_to_transit_dto_with(&root, &ParseOptions { max_routes: 3 }).unwrap();
+        assert_eq!(dto.routes.len(), 3);
+        assert_eq!(dto.routes[2].rank, 3);
+    }
+
+    #[test]
+    fn test_arrival_window() {
+        let dto = parse_fixture(include_str!("../tests/fixtures/basic.json"));
+        let (min, max) = dto.routes[0].arrival_window().unwrap();
+        assert_eq!(min, max);
+        assert_eq!(min.to_rfc3339(), "2025-12-18T09:45:00+09:00");
+
+        let dto = parse_fixture(include_str!("../tests/fixtures/alternate_arrivals.json"));
+        let route = &dto.routes[0];
+        assert_eq!(route.segments[0].alternate_arrival_times.len(), 0);
+        assert_eq!(route.segments[1].alternate_arrival_times.len(), 1);
+        let (min, max) = route.arrival_window().unwrap();
+        assert_eq!(min.to_rfc3339(), "2025-12-18T09:45:00+09:00");
+        assert_eq!(max.to_rfc3339(), "2025-12-18T09:52:00+09:00");
+    }
+}
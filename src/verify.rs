@@ -0,0 +1,123 @@
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::transit_dto::{
+    ParseOptions, RouteSummaryDto, SegmentDto, TransitDto, next_data_to_transit_dto_with,
+};
+
+/// Origin used by [`TransitClient::verify_parser`](crate::TransitClient::verify_parser).
+pub const DEFAULT_VERIFY_FROM: &str = "新宿";
+/// Destination used by [`TransitClient::verify_parser`](crate::TransitClient::verify_parser).
+pub const DEFAULT_VERIFY_TO: &str = "渋谷";
+
+/// Whether one field could be extracted from the page.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FieldCheck {
+    /// Field path in the DTO, e.g. `routes[].summary.arrivalTime`.
+    pub field: &'static str,
+    pub extracted: bool,
+}
+
+/// Report of how well the parser understood a result page.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ParserVerification {
+    /// Next.js build id of the page, if present. A change here often
+    /// precedes markup drift.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub build_id: Option<String>,
+    /// Parse error, if the page could not be parsed at all.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Number of parsed routes.
+    pub route_count: usize,
+    /// Per-field extraction results. A field counts as extracted when it is
+    /// present on at least one route / segment.
+    pub fields: Vec<FieldCheck>,
+}
+
+impl ParserVerification {
+    /// `true` if the page parsed and every checked field was extracted.
+    pub fn is_ok(&self) -> bool {
+        self.error.is_none() && self.fields.iter().all(|f| f.extracted)
+    }
+
+    /// Names of the fields that could not be extracted.
+    pub fn missing_fields(&self) -> Vec<&'static str> {
+        self.fields
+            .iter()
+            .filter(|f| !f.extracted)
+            .map(|f| f.field)
+            .collect()
+    }
+}
+
+/// Parse a `__NEXT_DATA__` value and check which fields were extracted.
+pub fn verify_next_data(root: &Value, options: &ParseOptions) -> ParserVerification {
+    let build_id = root["buildId"].as_str().map(str::to_string);
+    match next_data_to_transit_dto_with(root, options) {
+        Ok(dto) => ParserVerification {
+            build_id,
+            error: None,
+            route_count: dto.routes.len(),
+            fields: field_checks(&dto),
+        },
+        Err(e) => ParserVerification {
+            build_id,
+            error: Some(e.to_string()),
+            route_count: 0,
+            fields: Vec::new(),
+        },
+    }
+}
+
+fn field_checks(dto: &TransitDto) -> Vec<FieldCheck> {
+    let routes = &dto.routes;
+    let summary = |f: fn(&RouteSummaryDto) -> bool| routes.iter().any(|r| f(&r.summary));
+    let segment = |f: fn(&SegmentDto) -> bool| routes.iter().flat_map(|r| &r.segments).any(f);
+
+    vec![
+        check("from", !dto.from.is_empty()),
+        check("to", !dto.to.is_empty()),
+        check("searchDateTime", dto.search_date_time.is_some()),
+        check("routes", !routes.is_empty()),
+        check(
+            "routes[].summary.departureTime",
+            summary(|s| s.departure_time.is_some()),
+        ),
+        check(
+            "routes[].summary.arrivalTime",
+            summary(|s| s.arrival_time.is_some()),
+        ),
+        check(
+            "routes[].summary.durationMinutes",
+            summary(|s| s.duration_minutes.is_some()),
+        ),
+        check(
+            "routes[].summary.transferCount",
+            summary(|s| s.transfer_count.is_some()),
+        ),
+        check(
+            "routes[].summary.totalPriceYen",
+            summary(|s| s.total_price_yen.is_some()),
+        ),
+        check(
+            "routes[].segments",
+            routes.iter().any(|r| !r.segments.is_empty()),
+        ),
+        check("routes[].segments[].line", segment(|s| s.line.is_some())),
+        check(
+            "routes[].segments[].departureTime",
+            segment(|s| s.departure_time.is_some()),
+        ),
+        check(
+            "routes[].segments[].arrivalTime",
+            segment(|s| s.arrival_time.is_some()),
+        ),
+    ]
+}
+
+const fn check(field: &'static str, extracted: bool) -> FieldCheck {
+    FieldCheck { field, extracted }
+}
@@ -0,0 +1,102 @@
+//! An amount of Japanese yen.
+
+use std::{fmt, iter::Sum, ops::Add};
+
+use serde::{Deserialize, Serialize};
+
+use crate::format::FormatOptions;
+
+/// An amount in yen.
+///
+/// Serialized as a plain number and displayed as "1,234円" (use
+/// [`FormatOptions::fare`] for other styles). `+` saturates at `u32::MAX`;
+/// use [`checked_add`](Self::checked_add) to detect overflow.
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct Yen(pub u32);
+
+impl Yen {
+    /// The sum, or `None` on overflow.
+    pub const fn checked_add(self, other: Yen) -> Option<Yen> {
+        match self.0.checked_add(other.0) {
+            Some(yen) => Some(Yen(yen)),
+            None => None,
+        }
+    }
+
+    /// The fare for `passengers` people, or `None` on overflow.
+    pub const fn checked_mul(self, passengers: u32) -> Option<Yen> {
+        match self.0.checked_mul(passengers) {
+            Some(yen) => Some(Yen(yen)),
+            None => None,
+        }
+    }
+}
+
+impl From<u32> for Yen {
+    fn from(yen: u32) -> Self {
+        Self(yen)
+    }
+}
+
+impl From<Yen> for u32 {
+    fn from(yen: Yen) -> Self {
+        yen.0
+    }
+}
+
+impl Add for Yen {
+    type Output = Yen;
+
+    fn add(self, other: Yen) -> Yen {
+        Yen(self.0.saturating_add(other.0))
+    }
+}
+
+impl Sum for Yen {
+    fn sum<I: Iterator<Item = Yen>>(iter: I) -> Yen {
+        iter.fold(Yen(0), Add::add)
+    }
+}
+
+impl<'a> Sum<&'a Yen> for Yen {
+    fn sum<I: Iterator<Item = &'a Yen>>(iter: I) -> Yen {
+        iter.copied().sum()
+    }
+}
+
+impl fmt::Display for Yen {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&FormatOptions::default().fare(self.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display_and_serde() {
+        assert_eq!(Yen(1234).to_string(), "1,234円");
+        assert_eq!(Yen(0).to_string(), "0円");
+        assert_eq!(serde_json::to_string(&Yen(1234)).unwrap(), "1234");
+        assert_eq!(serde_json::from_str::<Yen>("170").unwrap(), Yen(170));
+    }
+
+    #[test]
+    fn test_sum_and_overflow() {
+        let fares = [Some(Yen(170)), Some(Yen(210))];
+        assert_eq!(fares.iter().copied().sum::<Option<Yen>>(), Some(Yen(380)));
+        let fares = [Some(Yen(170)), None];
+        assert_eq!(fares.iter().copied().sum::<Option<Yen>>(), None);
+        assert_eq!([Yen(1), Yen(2)].iter().sum::<Yen>(), Yen(3));
+
+        assert_eq!(Yen(u32::MAX) + Yen(1), Yen(u32::MAX));
+        assert_eq!(Yen(u32::MAX).checked_add(Yen(1)), None);
+        assert_eq!(Yen(1234).checked_mul(3), Some(Yen(3702)));
+        assert_eq!(Yen(u32::MAX / 2 + 1).checked_mul(2), None);
+    }
+}
@@ -1,35 +1,142 @@
-use std::{collections::HashSet, sync::LazyLock};
+use std::{fmt, sync::LazyLock};
 
 use chrono::{Datelike, Timelike};
 use serde::{Deserialize, Deserializer, Serialize, de};
 
 use crate::{
-    args::TransitArgs,
-    http::http_client,
+    args::{TransitArgs, normalize_place},
+    client::default_client,
+    clock::{Clock, SystemClock},
+    maintenance::maintenance_page,
+    protocol,
     transit_dto::{TransitDto, load_next_data, next_data_to_transit_dto},
 };
 
-fn de_f64_from_str<'de, D>(deserializer: D) -> Result<f64, D::Error>
+fn de_opt_f64_from_str<'de, D>(deserializer: D) -> Result<Option<f64>, D::Error>
 where
     D: Deserializer<'de>,
 {
     let s = String::deserialize(deserializer)?;
-    s.parse::<f64>().map_err(de::Error::custom)
+    if s.trim().is_empty() {
+        return Ok(None);
+    }
+    s.parse::<f64>().map(Some).map_err(de::Error::custom)
+}
+
+/// Mean Earth radius used for great-circle distances.
+const EARTH_RADIUS_KM: f64 = 6371.0;
+
+/// A point in degrees, as taken by the distance helpers.
+///
+/// Built from a `(lat, lon)` tuple, a [`YxhooPlace`], or with the `geo`
+/// feature a `geo_types::Point` (`x` = longitude, `y` = latitude).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LatLon {
+    pub lat: f64,
+    pub lon: f64,
+}
+
+impl LatLon {
+    /// Whether both values are finite and within ±90° / ±180°.
+    pub fn is_valid(&self) -> bool {
+        self.lat.is_finite()
+            && self.lon.is_finite()
+            && self.lat.abs() <= 90.0
+            && self.lon.abs() <= 180.0
+    }
+
+    /// Great-circle (haversine) distance in kilometers.
+    pub fn distance_km(&self, other: LatLon) -> f64 {
+        let (lat1, lat2) = (self.lat.to_radians(), other.lat.to_radians());
+        let dlat = lat2 - lat1;
+        let dlon = (other.lon - self.lon).to_radians();
+        let a = (dlat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (dlon / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS_KM * a.sqrt().asin()
+    }
+}
+
+impl From<(f64, f64)> for LatLon {
+    fn from((lat, lon): (f64, f64)) -> Self {
+        Self { lat, lon }
+    }
+}
+
+/// A place without coordinates gives NaN values, which are not
+/// [valid](LatLon::is_valid).
+impl From<&YxhooPlace> for LatLon {
+    fn from(place: &YxhooPlace) -> Self {
+        Self {
+            lat: place.lat.unwrap_or(f64::NAN),
+            lon: place.lon.unwrap_or(f64::NAN),
+        }
+    }
+}
+
+#[cfg(feature = "geo")]
+impl From<geo_types::Point<f64>> for LatLon {
+    fn from(point: geo_types::Point<f64>) -> Self {
+        Self {
+            lat: point.y(),
+            lon: point.x(),
+        }
+    }
+}
+
+#[cfg(feature = "geo")]
+impl TryFrom<&YxhooPlace> for geo_types::Point<f64> {
+    type Error = anyhow::Error;
+
+    /// `x` = longitude, `y` = latitude. Fails when the site sent no usable
+    /// coordinates.
+    fn try_from(place: &YxhooPlace) -> anyhow::Result<Self> {
+        let at = LatLon::from(place);
+        if !at.is_valid() {
+            anyhow::bail!(
+                "place `{}` has no usable coordinates ({:?}, {:?})",
+                place.suggest,
+                place.lat,
+                place.lon
+            );
+        }
+        Ok(geo_types::Point::new(at.lon, at.lat))
+    }
 }
 
 /// Kind of suggested place returned by Yxhoo.
+///
+/// Serialized as `"station"`, `"busStop"`, `"facility"` or `"other"` (the same
+/// strings as [`Display`](fmt::Display)). Deserialization also accepts the
+/// API's short codes (`st`, `bu`, `la`); unknown values become `Other`.
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub enum YxhooPlaceKind {
-    #[serde(rename(deserialize = "st"))]
+    #[serde(rename = "station", alias = "st")]
     Station,
-    #[serde(rename(deserialize = "bu"))]
+    #[serde(rename = "busStop", alias = "bu")]
     BusStation,
-    #[serde(rename(deserialize = "la"))]
+    #[serde(rename = "facility", alias = "la")]
     Facility,
-    #[serde(other)]
+    #[serde(rename = "other", other)]
     Other,
 }
 
+impl YxhooPlaceKind {
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Station => "station",
+            Self::BusStation => "busStop",
+            Self::Facility => "facility",
+            Self::Other => "other",
+        }
+    }
+}
+
+impl fmt::Display for YxhooPlaceKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
 /// Suggested place entry.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all(deserialize = "PascalCase"))]
@@ -38,17 +145,76 @@ pub struct YxhooPlace {
     pub suggest: String,
     /// Reading in kana. (e.g., しぶや)
     pub yomi: String,
-    /// Latitude as a float.
-    #[serde(deserialize_with = "de_f64_from_str")]
-    pub lat: f64,
-    /// Longitude as a float.
-    #[serde(deserialize_with = "de_f64_from_str")]
-    pub lon: f64,
+    /// Latitude as a float; `None` when the site sent none.
+    #[serde(deserialize_with = "de_opt_f64_from_str")]
+    pub lat: Option<f64>,
+    /// Longitude as a float; `None` when the site sent none.
+    #[serde(deserialize_with = "de_opt_f64_from_str")]
+    pub lon: Option<f64>,
     /// Full address of the place.
     pub address: String,
     /// Kind of the place.
     #[serde(rename(serialize = "type", deserialize = "Id"))]
     pub kind: YxhooPlaceKind,
+    /// The untouched result object as sent by the site, including fields
+    /// not modeled above.
+    ///
+    /// Unstable: the keys and values are whatever the unofficial API returns
+    /// and may change at any time.
+    #[cfg(feature = "raw-fields")]
+    #[serde(skip_deserializing, skip_serializing_if = "Option::is_none")]
+    pub raw: Option<serde_json::Value>,
+}
+
+impl YxhooPlace {
+    /// The name to pass as `from` / `to` when routing to or from this place.
+    ///
+    /// This is the crate's heuristic, not documented site behavior:
+    ///
+    /// - [`Station`](YxhooPlaceKind::Station) and
+    ///   [`BusStation`](YxhooPlaceKind::BusStation): the stop name.
+    /// - [`Facility`](YxhooPlaceKind::Facility): the facility name exactly as
+    ///   suggested, on the assumption that a name the suggest API offered is
+    ///   one the search accepts. How the site then picks the stops near the
+    ///   facility is up to the site.
+    /// - [`Other`](YxhooPlaceKind::Other): the address when present, as such
+    ///   entries looked like areas rather than points, otherwise the
+    ///   suggested name.
+    pub fn nearest_routable_name(&self) -> &str {
+        match self.kind {
+            YxhooPlaceKind::Station | YxhooPlaceKind::BusStation | YxhooPlaceKind::Facility => {
+                &self.suggest
+            }
+            YxhooPlaceKind::Other if !self.address.trim().is_empty() => self.address.trim(),
+            YxhooPlaceKind::Other => &self.suggest,
+        }
+    }
+
+    /// Distance in kilometers from this place to `to`; NaN if either has no
+    /// usable coordinates.
+    pub fn distance_km(&self, to: impl Into<LatLon>) -> f64 {
+        let (from, to) = (LatLon::from(self), to.into());
+        if from.is_valid() && to.is_valid() {
+            from.distance_km(to)
+        } else {
+            f64::NAN
+        }
+    }
+}
+
+impl YxhooSuggestResponse {
+    /// The station or bus stop closest to `to`, skipping places without
+    /// usable coordinates.
+    pub fn nearest_station(&self, to: impl Into<LatLon>) -> Option<&YxhooPlace> {
+        let to = to.into();
+        self.results
+            .iter()
+            .filter(|p| matches!(p.kind, YxhooPlaceKind::Station | YxhooPlaceKind::BusStation))
+            .map(|p| (p, p.distance_km(to)))
+            .filter(|(_, km)| !km.is_nan())
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(p, _)| p)
+    }
 }
 
 /// Suggest API response payload.
@@ -66,88 +232,242 @@ pub struct YxhooSuggestResponse {
     pub results: Vec<YxhooPlace>,
 }
 
-static BASE_URL: LazyLock<String> =
+/// Parse an `/api/suggest` body, keeping each raw result object when the
+/// `raw-fields` feature is enabled.
+pub(crate) fn parse_suggest_response(body: &str) -> anyhow::Result<YxhooSuggestResponse> {
+    #[cfg(feature = "raw-fields")]
+    {
+        let value: serde_json::Value = serde_json::from_str(body)?;
+        let mut response: YxhooSuggestResponse = serde_json::from_value(value.clone())?;
+        if let Some(raw) = value["Result"].as_array() {
+            for (place, raw) in response.results.iter_mut().zip(raw) {
+                place.raw = Some(raw.clone());
+            }
+        }
+        Ok(response)
+    }
+    #[cfg(not(feature = "raw-fields"))]
+    Ok(serde_json::from_str(body)?)
+}
+
+/// Outcome of resolving a free-form query to a single place.
+#[derive(Debug, Clone)]
+pub enum PlaceResolution {
+    /// One clear match.
+    Unique(YxhooPlace),
+    /// Several candidates; the caller should let the user pick.
+    Multiple(Vec<YxhooPlace>),
+    /// No suggestions at all.
+    None,
+}
+
+impl PlaceResolution {
+    /// Classify suggest results.
+    ///
+    /// The rule is:
+    /// - no results: [`PlaceResolution::None`]
+    /// - exactly one result: [`PlaceResolution::Unique`]
+    /// - exactly one [`YxhooPlaceKind::Station`] among several results:
+    ///   [`PlaceResolution::Unique`] with that station
+    /// - otherwise: [`PlaceResolution::Multiple`] with all results, in the
+    ///   order returned by the API
+    pub fn from_places(mut places: Vec<YxhooPlace>) -> Self {
+        if places.len() <= 1 {
+            return places.pop().map_or(Self::None, Self::Unique);
+        }
+
+        let mut stations = places.iter().filter(|p| p.kind == YxhooPlaceKind::Station);
+        if let (Some(station), None) = (stations.next(), stations.next()) {
+            return Self::Unique(station.clone());
+        }
+
+        Self::Multiple(places)
+    }
+}
+
+/// Rank suggestions by similarity to `query` and return up to `limit` names.
+///
+/// The score counts characters of the query found in the candidate (twice
+/// for a shared prefix), minus the length difference; stations get a small
+/// bonus. Ties keep the API order.
+pub(crate) fn best_matches(query: &str, places: &[YxhooPlace], limit: usize) -> Vec<String> {
+    let query: Vec<char> = query.trim().chars().collect();
+    let score = |place: &YxhooPlace| -> i64 {
+        let name: Vec<char> = place.suggest.chars().collect();
+        let shared = query.iter().filter(|c| name.contains(c)).count() as i64;
+        let prefix = query.iter().zip(&name).take_while(|(a, b)| a == b).count() as i64;
+        let len_diff = (query.len() as i64 - name.len() as i64).abs();
+        let station = i64::from(place.kind == YxhooPlaceKind::Station);
+        shared + prefix * 2 - len_diff + station
+    };
+
+    let mut scored: Vec<(i64, &YxhooPlace)> = places.iter().map(|p| (score(p), p)).collect();
+    scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+
+    let mut out: Vec<String> = Vec::new();
+    for (_, place) in scored {
+        if out.len() == limit {
+            break;
+        }
+        if !out.contains(&place.suggest) {
+            out.push(place.suggest.clone());
+        }
+    }
+    out
+}
+
+pub(crate) static BASE_URL: LazyLock<String> =
     LazyLock::new(|| "https://transit.yXhoo.co.jp".replace("X", "a"));
 
 /// Suggest places by a free-form query string.
 ///
+/// Uses the default client; see [`init_default_client`](crate::init_default_client).
+///
 /// # Errors
 /// Returns an error if the HTTP request fails or the response cannot be parsed.
 pub async fn suggest_places(query: &str) -> anyhow::Result<YxhooSuggestResponse> {
-    let client = http_client();
-    let response = client
-        .get(format!("{}/api/suggest", *BASE_URL))
-        .query(&[("value", query)])
-        .send()
-        .await?;
-
-    let response: YxhooSuggestResponse = response.json().await?;
-
-    Ok(response)
+    default_client()?.suggest_places(query).await
 }
 
+/// Split a minute (0..=59) into the tens/units digits sent as `m1`/`m2`.
+///
+/// `chrono` guarantees `minute() < 60` (leap seconds are carried in the
+/// nanosecond field), so `m1` is always in `0..=5`. The inverse is
+/// `m1 * 10 + m2`, see `build_search_datetime` in `transit_dto`.
 #[inline]
 const fn minute_digits(min: u32) -> (u32, u32) {
+    debug_assert!(min < 60);
     (min / 10, min % 10)
 }
 
-/// Search transit routes using the given arguments.
-///
-/// # Errors
-/// Returns an error if the HTTP request fails or the response cannot be parsed.
-pub async fn transit(args: &TransitArgs) -> anyhow::Result<TransitDto> {
-    let client = http_client();
+/// Build the query parameters for `/search/print`.
+pub(crate) fn build_transit_query(args: &TransitArgs) -> Vec<(String, String)> {
     let mut q: Vec<(String, String)> = Vec::new();
+    let mut push = |key: &str, value: String| q.push((key.into(), value));
 
     // from / to
-    q.push(("from".into(), args.from.clone()));
-    q.push(("to".into(), args.to.clone()));
+    push(protocol::FROM, normalize_place(&args.from));
+    push(protocol::TO, normalize_place(&args.to));
+    for via in args.clean_via().0 {
+        push(protocol::VIA, via);
+    }
 
-    // date -> y,m,d,hh,m1,m2
-    let dt = args.date;
-    q.push(("y".into(), dt.year().to_string()));
-    q.push(("m".into(), dt.month().to_string()));
-    q.push(("d".into(), dt.day().to_string()));
-    q.push(("hh".into(), dt.hour().to_string()));
+    // date -> y,m,d,hh,m1,m2 (JST wall-clock time)
+    let dt = args.jst_date();
+    push(protocol::YEAR, dt.year().to_string());
+    push(protocol::MONTH, dt.month().to_string());
+    push(protocol::DAY, dt.day().to_string());
+    push(protocol::HOUR, dt.hour().to_string());
 
     let (m1, m2) = minute_digits(dt.minute());
-    q.push(("m1".into(), m1.to_string()));
-    q.push(("m2".into(), m2.to_string()));
+    push(protocol::MINUTE_TENS, m1.to_string());
+    push(protocol::MINUTE_UNITS, m2.to_string());
 
     // type (Departure/Arrival/First/Last/NotSpecified)
-    q.push(("type".into(), args.date_kind.as_u32().to_string()));
+    push(protocol::DATE_KIND, args.date_kind.as_u32().to_string());
 
     // criteria
-    let s = args.criteria.as_u32();
-    q.push(("s".into(), s.to_string()));
+    push(protocol::CRITERIA, args.criteria.as_u32().to_string());
 
     // rank
-    q.push(("no".into(), args.rank.to_string()));
+    push(protocol::RANK, args.rank.to_string());
 
     // options
     let opt = &args.options;
-    q.push(("ticket".into(), opt.ticket_preference.as_str().to_string()));
-    q.push(("expkind".into(), opt.seat_preference.as_u32().to_string()));
-    q.push(("ws".into(), opt.walking_speed.as_u32().to_string()));
-    let set: HashSet<&'static str> = opt.available_means.iter().map(|m| m.as_str()).collect();
+    push(protocol::TICKET, opt.ticket_preference.as_str().to_string());
+    push(protocol::SEAT, opt.seat_preference.as_u32().to_string());
+    push(
+        protocol::WALKING_SPEED,
+        opt.walking_speed.as_u32().to_string(),
+    );
 
-    for key in ["al", "shin", "ex", "hb", "lb", "sr"] {
-        let v = if set.contains(key) { "1" } else { "0" };
-        q.push((key.into(), v.into()));
+    for (means, key) in protocol::MEANS {
+        let on = opt.available_means.contains(&means);
+        push(
+            key,
+            if on {
+                protocol::MEANS_ON
+            } else {
+                protocol::MEANS_OFF
+            }
+            .into(),
+        );
     }
 
-    let response = client
-        .get(format!("{}/search/print", *BASE_URL))
-        .query(&q)
-        .send()
-        .await?;
+    q
+}
 
-    let response: String = response.text().await?;
+/// Encode query pairs like an HTML form submission
+/// (`application/x-www-form-urlencoded`, UTF-8).
+///
+/// Spaces become `+`; everything except ASCII alphanumerics and `*-._` is
+/// percent-encoded. This matches what a browser sends from the site's
+/// search form and is used for every request instead of the backend's
+/// own query serializer.
+pub(crate) fn encode_query(pairs: &[(String, String)]) -> String {
+    fn encode(s: &str, out: &mut String) {
+        for b in s.bytes() {
+            match b {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'*' | b'-' | b'.' | b'_' => {
+                    out.push(b as char)
+                }
+                b' ' => out.push('+'),
+                _ => out.push_str(&format!("%{b:02X}")),
+            }
+        }
+    }
+
+    let mut out = String::new();
+    for (i, (k, v)) in pairs.iter().enumerate() {
+        if i > 0 {
+            out.push('&');
+        }
+        encode(k, &mut out);
+        out.push('=');
+        encode(v, &mut out);
+    }
+    out
+}
+
+/// Search transit routes using the given arguments.
+///
+/// Uses the default client; see [`init_default_client`](crate::init_default_client).
+///
+/// # Errors
+/// Returns an error if the HTTP request fails or the response cannot be parsed.
+pub async fn transit(args: &TransitArgs) -> anyhow::Result<TransitDto> {
+    default_client()?.transit(args).await
+}
 
-    let serde_value = load_next_data(&response)?;
-    let structured = next_data_to_transit_dto(&serde_value)?;
+/// URL of the result page for `args`, as fetched by [`transit`].
+///
+/// Uses the default client's base URL; see
+/// [`TransitClient::transit_request_url`](crate::TransitClient::transit_request_url).
+///
+/// # Errors
+/// Returns an error if the default client cannot be built.
+pub fn transit_request_url(args: &TransitArgs) -> anyhow::Result<String> {
+    Ok(default_client()?.transit_request_url(args))
+}
 
-    Ok(structured)
+/// Parse a result page fetched elsewhere, without making any request.
+///
+/// A pure parse with the default [`ParseOptions`](crate::transit_dto::ParseOptions):
+/// no client is involved, so there are no argument warnings, child fares
+/// or operator filters. Use
+/// [`TransitClient::transit_from_body`](crate::TransitClient::transit_from_body)
+/// to parse with a client's settings. Accepts the HTML page or its bare
+/// `__NEXT_DATA__` JSON.
+///
+/// # Errors
+/// Returns [`TransitError::Maintenance`](crate::TransitError::Maintenance)
+/// for the maintenance page, or an error if the page cannot be parsed or
+/// has no route.
+pub fn transit_from_body(body: &str) -> anyhow::Result<TransitDto> {
+    if let Some(e) = maintenance_page(body, None, SystemClock.now_jst()) {
+        return Err(e.into());
+    }
+    next_data_to_transit_dto(&load_next_data(body)?)
 }
 
 // tests
@@ -158,6 +478,355 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn test_minute_digits() {
+        let cases = [
+            (0, (0, 0)),
+            (5, (0, 5)),
+            (9, (0, 9)),
+            (10, (1, 0)),
+            (59, (5, 9)),
+        ];
+        for (min, expected) in cases {
+            assert_eq!(minute_digits(min), expected, "Failed on minute: {}", min);
+        }
+
+        // m1 * 10 + m2 must reconstruct the minute (see build_search_datetime)
+        for min in 0..60 {
+            let (m1, m2) = minute_digits(min);
+            assert!(m1 < 6 && m2 < 10);
+            assert_eq!(m1 * 10 + m2, min);
+        }
+    }
+
+    fn place(name: &str, kind: YxhooPlaceKind) -> YxhooPlace {
+        YxhooPlace {
+            suggest: name.into(),
+            yomi: String::new(),
+            lat: Some(35.0),
+            lon: Some(139.0),
+            address: String::new(),
+            kind,
+            #[cfg(feature = "raw-fields")]
+            raw: None,
+        }
+    }
+
+    #[test]
+    fn test_distance_and_nearest_station() {
+        use YxhooPlaceKind::*;
+
+        let at = |name, kind, lat: f64, lon: f64| YxhooPlace {
+            lat: Some(lat).filter(|l| !l.is_nan()),
+            lon: Some(lon).filter(|l| !l.is_nan()),
+            ..place(name, kind)
+        };
+        let response = YxhooSuggestResponse {
+            query: "渋谷".into(),
+            total_results: 4,
+            results: vec![
+                at("渋谷", Station, 35.658, 139.7016),
+                at("原宿", Station, 35.6702, 139.7027),
+                at("渋谷ヒカリエ", Facility, 35.659, 139.7036),
+                at("不明", Station, f64::NAN, f64::NAN),
+            ],
+        };
+        let shibuya = &response.results[0];
+        let km = shibuya.distance_km(&response.results[1]);
+        assert!((km - 1.36).abs() < 0.01, "{km}");
+        assert!(shibuya.distance_km(&response.results[3]).is_nan());
+
+        let near_harajuku = (35.669, 139.703);
+        assert_eq!(
+            response.nearest_station(near_harajuku).unwrap().suggest,
+            "原宿"
+        );
+        assert_eq!(
+            response
+                .nearest_station((35.6595, 139.7035))
+                .unwrap()
+                .suggest,
+            "渋谷"
+        );
+
+        let empty: YxhooPlace = serde_json::from_value(serde_json::json!({
+            "Suggest": "不明", "Yomi": "", "Lat": "", "Lon": "", "Address": "", "Id": "st"
+        }))
+        .unwrap();
+        assert_eq!((empty.lat, empty.lon), (None, None));
+        let json = serde_json::to_value(&empty).unwrap();
+        assert!(json["lat"].is_null(), "{json}");
+
+        let shibuya: YxhooPlace = serde_json::from_value(serde_json::json!({
+            "Suggest": "渋谷", "Yomi": "しぶや", "Lat": "35.658", "Lon": "139.7016",
+            "Address": "", "Id": "st"
+        }))
+        .unwrap();
+        let json = serde_json::to_value(&shibuya).unwrap();
+        assert_eq!(
+            (json["lat"].as_f64(), json["lon"].as_f64()),
+            (Some(35.658), Some(139.7016))
+        );
+    }
+
+    #[cfg(feature = "geo")]
+    #[test]
+    fn test_geo_point_conversions() {
+        let shibuya = YxhooPlace {
+            lat: Some(35.658),
+            lon: Some(139.7016),
+            ..place("渋谷", YxhooPlaceKind::Station)
+        };
+        let point = geo_types::Point::try_from(&shibuya).unwrap();
+        assert_eq!((point.x(), point.y()), (139.7016, 35.658));
+
+        let harajuku = geo_types::Point::new(139.7027, 35.6702);
+        assert_eq!(
+            shibuya.distance_km(harajuku),
+            shibuya.distance_km((35.6702, 139.7027))
+        );
+
+        let empty = YxhooPlace {
+            lat: None,
+            lon: None,
+            ..place("不明", YxhooPlaceKind::Station)
+        };
+        let err = geo_types::Point::try_from(&empty).unwrap_err();
+        assert!(err.to_string().contains("no usable coordinates"), "{err}");
+    }
+
+    #[test]
+    fn test_transit_from_body() {
+        let args: TransitArgs = "新宿>渋谷".parse().unwrap();
+        let dto = transit_from_body(include_str!("../tests/fixtures/basic.json")).unwrap();
+        assert_eq!(dto.routes[0].summary.total_price_yen, Some(170));
+
+        let err =
+            transit_from_body(include_str!("../tests/fixtures/maintenance.html")).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(crate::TransitError::Maintenance { .. })
+        ));
+        assert!(transit_from_body("<html></html>").is_err());
+
+        let url = transit_request_url(&args).unwrap();
+        assert!(
+            url.starts_with(&format!(
+                "{}/search/print?from=%E6%96%B0%E5%AE%BF&",
+                *BASE_URL
+            )),
+            "{url}"
+        );
+    }
+
+    #[test]
+    fn test_nearest_routable_name() {
+        use YxhooPlaceKind::*;
+
+        assert_eq!(place("渋谷", Station).nearest_routable_name(), "渋谷");
+        assert_eq!(
+            place("渋谷駅東口", BusStation).nearest_routable_name(),
+            "渋谷駅東口"
+        );
+        assert_eq!(
+            place("東京タワー", Facility).nearest_routable_name(),
+            "東京タワー"
+        );
+
+        let mut area = place("港区芝公園", Other);
+        assert_eq!(area.nearest_routable_name(), "港区芝公園");
+        area.address = " 東京都港区芝公園 ".into();
+        assert_eq!(area.nearest_routable_name(), "東京都港区芝公園");
+    }
+
+    #[test]
+    fn test_place_resolution() {
+        use YxhooPlaceKind::*;
+
+        assert!(matches!(
+            PlaceResolution::from_places(vec![]),
+            PlaceResolution::None
+        ));
+
+        let res = PlaceResolution::from_places(vec![place("美ら海水族館", Facility)]);
+        assert!(matches!(res, PlaceResolution::Unique(p) if p.suggest == "美ら海水族館"));
+
+        let res = PlaceResolution::from_places(vec![
+            place("新宿", Station),
+            place("新宿駅西口", BusStation),
+            place("新宿御苑", Facility),
+        ]);
+        assert!(matches!(res, PlaceResolution::Unique(p) if p.suggest == "新宿"));
+
+        let res = PlaceResolution::from_places(vec![
+            place("新宿", Station),
+            place("新宿三丁目", Station),
+        ]);
+        assert!(matches!(res, PlaceResolution::Multiple(v) if v.len() == 2));
+
+        let res = PlaceResolution::from_places(vec![
+            place("新宿駅西口", BusStation),
+            place("新宿御苑", Facility),
+        ]);
+        assert!(matches!(res, PlaceResolution::Multiple(v) if v.len() == 2));
+    }
+
+    #[test]
+    fn test_best_matches() {
+        use YxhooPlaceKind::*;
+
+        let places = vec![
+            place("新宿御苑", Facility),
+            place("渋谷", Station),
+            place("渋谷駅西口", BusStation),
+            place("渋谷ヒカリエ", Facility),
+            place("しぶや", Other),
+        ];
+        assert_eq!(
+            best_matches("渋やx", &places, 3),
+            vec!["渋谷", "渋谷駅西口", "しぶや"]
+        );
+        assert!(best_matches("渋谷", &[], 3).is_empty());
+    }
+
+    #[test]
+    fn test_default_args_query() {
+        let before = crate::args::jst_now();
+        let args = TransitArgs {
+            from: "新宿".into(),
+            to: "渋谷".into(),
+            ..Default::default()
+        };
+        let after = crate::args::jst_now();
+        assert!(before <= args.date && args.date <= after);
+        assert_eq!(args.date.offset().local_minus_utc(), 9 * 3600);
+
+        let q = build_transit_query(&args);
+        let get = |k: &str| q.iter().find(|(key, _)| key == k).map(|(_, v)| v.as_str());
+        assert_eq!(get("from"), Some("新宿"));
+        assert_eq!(get("to"), Some("渋谷"));
+        assert_eq!(get("y"), Some(args.date.year().to_string().as_str()));
+        assert_eq!(get("type"), Some("1"));
+        assert_eq!(get("no"), Some("1"));
+
+        let padded = TransitArgs {
+            from: "　新宿 ".into(),
+            to: "渋谷\u{3000}".into(),
+            ..args.clone()
+        };
+        assert_eq!(build_transit_query(&padded), q);
+        assert!(q.iter().all(|(key, _)| key != "via"));
+
+        // other offsets are sent as the same instant in JST
+        let utc = TransitArgs {
+            date: "2025-12-18T15:30:00Z".parse().unwrap(),
+            ..args.clone()
+        };
+        let q_utc = build_transit_query(&utc);
+        let get = |k: &str| {
+            q_utc
+                .iter()
+                .find(|(key, _)| key == k)
+                .map(|(_, v)| v.as_str())
+        };
+        assert_eq!(
+            [get("d"), get("hh"), get("m1"), get("m2")],
+            [Some("19"), Some("0"), Some("3"), Some("0")]
+        );
+
+        // no passenger parameter is known; child fares are computed locally
+        let mut child = args.clone();
+        child.options.passenger = Some(crate::args::PassengerType::Child);
+        assert_eq!(build_transit_query(&child), q);
+
+        let with_via = TransitArgs {
+            from: "新宿\n".into(),
+            via: vec!["新宿".into(), "代々木\u{3000}".into(), " 代々木".into()],
+            ..args.clone()
+        };
+        let q = build_transit_query(&with_via);
+        assert_eq!(
+            q[..3],
+            [
+                ("from".to_string(), "新宿".to_string()),
+                ("to".to_string(), "渋谷".to_string()),
+                ("via".to_string(), "代々木".to_string()),
+            ]
+        );
+        assert_eq!(q.iter().filter(|(key, _)| key == "via").count(), 1);
+    }
+
+    #[test]
+    fn test_place_kind_serde() {
+        use YxhooPlaceKind::*;
+        for (code, kind) in [
+            ("st", Station),
+            ("bu", BusStation),
+            ("la", Facility),
+            ("zz", Other),
+        ] {
+            let parsed: YxhooPlaceKind = serde_json::from_value(code.into()).unwrap();
+            assert_eq!(parsed, kind);
+
+            let json = serde_json::to_value(&kind).unwrap();
+            assert_eq!(json, kind.to_string().as_str());
+            assert_eq!(
+                serde_json::from_value::<YxhooPlaceKind>(json).unwrap(),
+                kind
+            );
+        }
+        assert_eq!(BusStation.to_string(), "busStop");
+    }
+
+    #[test]
+    fn test_parse_suggest_response() {
+        let body = r#"{"@query":"新宿","@totalResultsAvailable":1,"Result":[{"Suggest":"新宿","Yomi":"しんじゅく","Lat":"35.690921","Lon":"139.700258","Address":"東京都新宿区","Id":"st","Category":"駅","PrefCode":"13"}]}"#;
+        let response = parse_suggest_response(body).unwrap();
+        let json = serde_json::to_value(&response.results[0]).unwrap();
+
+        #[cfg(feature = "raw-fields")]
+        {
+            let raw = response.results[0].raw.as_ref().unwrap();
+            assert_eq!(raw["PrefCode"], "13");
+            assert_eq!(raw["Lat"], "35.690921");
+            assert_eq!(json["raw"]["Category"], "駅");
+        }
+        #[cfg(not(feature = "raw-fields"))]
+        assert!(json.get("raw").is_none());
+    }
+
+    #[cfg(feature = "schemars")]
+    #[test]
+    fn test_place_kind_schema() {
+        let schema = serde_json::to_value(schemars::schema_for!(YxhooPlaceKind)).unwrap();
+        assert_eq!(
+            schema["enum"],
+            serde_json::json!(["station", "busStop", "facility", "other"])
+        );
+    }
+
+    #[test]
+    fn test_encode_query() {
+        let q = |v: &str| encode_query(&[("from".into(), v.into())]);
+        assert_eq!(
+            q("名古屋港（ガーデンふ頭）"),
+            "from=%E5%90%8D%E5%8F%A4%E5%B1%8B%E6%B8%AF%EF%BC%88%E3%82%AC%E3%83%BC\
+             %E3%83%87%E3%83%B3%E3%81%B5%E9%A0%AD%EF%BC%89"
+        );
+        assert_eq!(
+            q("三井アウトレットパーク ジャズドリーム長島"),
+            "from=%E4%B8%89%E4%BA%95%E3%82%A2%E3%82%A6%E3%83%88%E3%83%AC%E3%83%83\
+             %E3%83%88%E3%83%91%E3%83%BC%E3%82%AF+%E3%82%B8%E3%83%A3%E3%82%BA%E3%83\
+             %89%E3%83%AA%E3%83%BC%E3%83%A0%E9%95%B7%E5%B3%B6"
+        );
+        assert_eq!(q("A&B・C=1"), "from=A%26B%E3%83%BBC%3D1");
+        assert_eq!(q("a+b~c/d"), "from=a%2Bb%7Ec%2Fd");
+        assert_eq!(
+            encode_query(&[("a".into(), "1".into()), ("b".into(), "".into())]),
+            "a=1&b="
+        );
+    }
+
     #[tokio::test]
     async fn test_suggest_places() {
         let query = "新宿";